@@ -0,0 +1,65 @@
+use axum::http::HeaderValue;
+use tower_http::cors::{AllowHeaders, AllowMethods, AllowOrigin, Any, CorsLayer};
+
+/// Compares a client-supplied token against the expected secret without short-circuiting
+/// on the first differing byte. An empty expected token never matches, so a missing
+/// secret can't be satisfied by an empty or absent credential.
+pub fn token_matches(provided: Option<&str>, expected: &str) -> bool {
+    let Some(provided) = provided else {
+        return false;
+    };
+    if expected.is_empty() || provided.len() != expected.len() {
+        return false;
+    }
+    provided
+        .bytes()
+        .zip(expected.bytes())
+        .fold(0u8, |acc, (a, b)| acc | (a ^ b))
+        == 0
+}
+
+/// Token carried by a WebSocket `{"type": "auth", "token": "..."}` message.
+pub fn ws_auth_token(message: &serde_json::Value) -> Option<&str> {
+    message.get("token").and_then(|v| v.as_str())
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ParseCorsOriginError(pub String);
+
+impl std::fmt::Display for ParseCorsOriginError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "invalid CORS origin: {:?} (expected e.g. https://rthmn.com)", self.0)
+    }
+}
+
+impl std::error::Error for ParseCorsOriginError {}
+
+/// Parses a comma-separated `CORS_ORIGINS` list, skipping blank entries. Origins are
+/// compared verbatim by the browser, so a trailing slash is stripped.
+pub fn parse_cors_origins(list: &str) -> Result<Vec<HeaderValue>, ParseCorsOriginError> {
+    list.split(',')
+        .map(|origin| origin.trim().trim_end_matches('/'))
+        .filter(|origin| !origin.is_empty())
+        .map(|origin| {
+            if !origin.contains("://") {
+                return Err(ParseCorsOriginError(origin.to_string()));
+            }
+            HeaderValue::from_str(origin).map_err(|_| ParseCorsOriginError(origin.to_string()))
+        })
+        .collect()
+}
+
+/// CORS for the HTTP API. With no `origins` any site may call it without credentials,
+/// which only suits the public read endpoints. With a list, only those origins get CORS
+/// headers and credentials are allowed; since the spec forbids wildcards alongside
+/// credentials, methods and headers mirror the preflight request instead.
+pub fn cors_layer(origins: Option<Vec<HeaderValue>>) -> CorsLayer {
+    match origins {
+        None => CorsLayer::new().allow_origin(Any).allow_methods(Any).allow_headers(Any),
+        Some(origins) => CorsLayer::new()
+            .allow_origin(AllowOrigin::list(origins))
+            .allow_methods(AllowMethods::mirror_request())
+            .allow_headers(AllowHeaders::mirror_request())
+            .allow_credentials(true),
+    }
+}
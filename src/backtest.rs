@@ -0,0 +1,138 @@
+use crate::deduplication::{DedupConfig, Deduplicator};
+use crate::metrics::Metrics;
+use crate::pipeline::{Pipeline, RuntimeConfig};
+use crate::scanner::MarketScanner;
+use crate::signal::SignalGenerator;
+use crate::tracker::{Settlement, SignalTracker};
+use crate::types::{BoxData, ParsedUpdate};
+use crate::volatility::VolatilityTracker;
+use serde::Serialize;
+use std::collections::BTreeMap;
+
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct LevelStats {
+    pub signals: usize,
+    pub wins: usize,
+    pub losses: usize,
+}
+
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct BacktestReport {
+    pub updates: usize,
+    /// Updates not replayed: invalid boxes or price, or an unparseable timestamp.
+    pub skipped: usize,
+    pub signals: usize,
+    pub wins: usize,
+    pub losses: usize,
+    /// Every other final settlement: stopped out after some targets were hit, expired or
+    /// closed manually. Neither a win nor a loss.
+    pub partials: usize,
+    /// Signals still active when the replay ended.
+    pub open: usize,
+    /// Wins (`success` and `trailed`) over all final settlements, so partials count
+    /// against it; 0 until something settles.
+    pub win_rate: f64,
+    /// Mean final-target risk/reward of the generated signals.
+    pub average_rr: f64,
+    pub by_level: BTreeMap<u32, LevelStats>,
+}
+
+impl BacktestReport {
+    fn record_settlement(&mut self, settlement: &Settlement) {
+        let level = self.by_level.entry(settlement.signal.level).or_default();
+        match settlement.status {
+            "success" | "trailed" => {
+                self.wins += 1;
+                level.wins += 1;
+            }
+            "failed" => {
+                self.losses += 1;
+                level.losses += 1;
+            }
+            _ => self.partials += 1,
+        }
+    }
+}
+
+/// Replays historical box snapshots through the live `Pipeline` (settle, detect, dedup,
+/// generate, min-RR, cooldown, track), with an in-memory tracker in place of Supabase and
+/// no forwarding. `config`, `dedup` and `atr_period` should match the server being
+/// evaluated. Each update's own timestamp stands in for the clock; updates with invalid
+/// boxes or price, or a timestamp that isn't RFC 3339, are skipped and counted.
+pub async fn run_backtest(
+    scanner: &MarketScanner,
+    generator: &SignalGenerator,
+    config: &RuntimeConfig,
+    dedup: DedupConfig,
+    atr_period: usize,
+    updates: impl Iterator<Item = BoxData>,
+) -> BacktestReport {
+    let tracker = SignalTracker::new(None, None);
+    let deduplicator = Deduplicator::with_config(dedup);
+    let volatility = VolatilityTracker::new(atr_period);
+    let metrics = Metrics::default();
+    let (last_fingerprints, last_fired_at) = Default::default();
+    let pipeline = Pipeline {
+        scanner,
+        generator,
+        volatility: &volatility,
+        tracker: &tracker,
+        deduplicator: &deduplicator,
+        metrics: &metrics,
+        config,
+        last_fingerprints: &last_fingerprints,
+        last_fired_at: &last_fired_at,
+        signal_ttl_ms: None,
+        trace: false,
+    };
+    let mut report = BacktestReport::default();
+    let mut rr_sum = 0.0;
+
+    for update in updates {
+        report.updates += 1;
+        let Some((parsed, timestamp_ms)) = replayable(&update) else {
+            report.skipped += 1;
+            continue;
+        };
+        if parsed.boxes.is_empty() {
+            continue;
+        }
+
+        let processed = pipeline.run(&update.pair, &parsed, timestamp_ms).await;
+        for settlement in processed.settlements.iter().filter(|s| s.is_final()) {
+            report.record_settlement(settlement);
+        }
+        for signal in &processed.signals {
+            report.signals += 1;
+            report.by_level.entry(signal.level).or_default().signals += 1;
+            rr_sum += signal.risk_reward.last().copied().unwrap_or(0.0);
+        }
+    }
+
+    report.open = tracker.get_active_count().await;
+    let settled = report.wins + report.losses + report.partials;
+    if settled > 0 {
+        report.win_rate = report.wins as f64 / settled as f64;
+    }
+    if report.signals > 0 {
+        report.average_rr = rr_sum / report.signals as f64;
+    }
+    report
+}
+
+/// The update as the live server would have parsed it, with its timestamp in epoch ms;
+/// `None` if the server would have rejected it or its time is unknown.
+fn replayable(update: &BoxData) -> Option<(ParsedUpdate, i64)> {
+    if update.boxes.iter().any(|b| b.validate().is_err()) || !update.price.is_finite() || update.price <= 0.0 {
+        return None;
+    }
+    let timestamp = chrono::DateTime::parse_from_rfc3339(&update.timestamp).ok()?.to_utc();
+    let parsed = ParsedUpdate {
+        boxes: update.boxes.clone(),
+        price: update.price,
+        tick_high: update.tick_high,
+        tick_low: update.tick_low,
+        timestamp: Some(timestamp),
+    };
+    Some((parsed, timestamp.timestamp_millis()))
+}
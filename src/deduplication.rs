@@ -1,9 +1,76 @@
-use crate::types::{BoxDetail, PatternMatch};
+use crate::types::{BoxDetail, PatternMatch, SignalMessage};
+use serde::Serialize;
 use std::collections::{HashMap, HashSet};
+use std::sync::atomic::{AtomicU64, Ordering};
 use tokio::sync::RwLock;
+use tracing::debug;
 
-const TOLERANCE: f64 = 0.00001;
-const BOX0_CHANGE_TOLERANCE: f64 = 0.00001;
+const DEFAULT_PRICE_TOLERANCE: f64 = 0.00001;
+const DEFAULT_RECENT_WINDOW_MS: i64 = 5 * 60 * 1000;
+
+#[derive(Debug, Clone, Copy)]
+pub struct DedupConfig {
+    /// Minimum price difference for two box edges or signal levels to count as different.
+    /// The effective tolerance per pair is the larger of this and half the pair's point
+    /// (see `instruments::get_instrument_config`), so coarse-tick instruments like BTCUSD
+    /// still dedup.
+    pub price_tolerance: f64,
+    /// How long a sent signal suppresses an identical re-send.
+    pub recent_window_ms: i64,
+    /// Coarse time bucket: a re-send in the same bucket as the original is suppressed
+    /// even once `recent_window_ms` has passed. 0 disables bucketing.
+    pub recent_bucket_ms: i64,
+}
+
+impl Default for DedupConfig {
+    fn default() -> Self {
+        Self {
+            price_tolerance: DEFAULT_PRICE_TOLERANCE,
+            recent_window_ms: DEFAULT_RECENT_WINDOW_MS,
+            recent_bucket_ms: 0,
+        }
+    }
+}
+
+/// Which dedup mechanism suppressed a pattern or signal.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum DedupReason {
+    /// An L1 signal for the same pair/direction is active and box 0 hasn't moved.
+    ActiveL1,
+    /// An identical signal was sent within `recent_window_ms`.
+    RecentWindow,
+    /// The tracked structural boxes are unchanged since the last signal.
+    StructuralUnchanged,
+}
+
+impl std::fmt::Display for DedupReason {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            Self::ActiveL1 => "active_l1",
+            Self::RecentWindow => "recent_window",
+            Self::StructuralUnchanged => "structural_unchanged",
+        })
+    }
+}
+
+/// Running totals of filtered patterns/signals per reason since startup.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DedupFilterCounts {
+    pub active_l1: u64,
+    pub recent_window: u64,
+    pub structural_unchanged: u64,
+}
+
+#[derive(Debug, Default)]
+struct FilterCounters {
+    active_l1: AtomicU64,
+    recent_window: AtomicU64,
+    structural_unchanged: AtomicU64,
+}
+
+type StructuralBoxes = HashMap<String, HashMap<i32, (f64, f64)>>;
 
 #[derive(Debug, Clone)]
 struct L1Signal {
@@ -11,21 +78,76 @@ struct L1Signal {
     box1_low: f64,
 }
 
+#[derive(Debug, Clone)]
+struct RecentSignal {
+    signal_type: String,
+    level: u32,
+    entry: f64,
+    stop: f64,
+    target: f64,
+    sent_at: i64,
+}
+
 pub struct Deduplicator {
+    config: DedupConfig,
     active_l1_signals: RwLock<HashMap<String, L1Signal>>,
     box1_states: RwLock<HashMap<String, (f64, f64)>>,
-    structural_boxes: RwLock<HashMap<String, HashMap<i32, (f64, f64)>>>,
+    structural_boxes: RwLock<StructuralBoxes>,
+    recent_signals: RwLock<HashMap<String, Vec<RecentSignal>>>,
+    filtered: FilterCounters,
 }
 
 impl Deduplicator {
     pub fn new() -> Self {
+        Self::with_config(DedupConfig::default())
+    }
+
+    pub fn with_config(config: DedupConfig) -> Self {
         Self {
+            config,
             active_l1_signals: RwLock::new(HashMap::new()),
             box1_states: RwLock::new(HashMap::new()),
             structural_boxes: RwLock::new(HashMap::new()),
+            recent_signals: RwLock::new(HashMap::new()),
+            filtered: FilterCounters::default(),
         }
     }
 
+    pub fn config(&self) -> DedupConfig {
+        self.config
+    }
+
+    pub fn filter_counts(&self) -> DedupFilterCounts {
+        DedupFilterCounts {
+            active_l1: self.filtered.active_l1.load(Ordering::Relaxed),
+            recent_window: self.filtered.recent_window.load(Ordering::Relaxed),
+            structural_unchanged: self.filtered.structural_unchanged.load(Ordering::Relaxed),
+        }
+    }
+
+    /// Half a tick for the pair, floored at the configured `price_tolerance`.
+    pub fn tolerance_for(&self, pair: &str) -> f64 {
+        let point = crate::instruments::get_instrument_config(pair).point;
+        (point * 0.5).max(self.config.price_tolerance)
+    }
+
+    /// Whether a signal sent at `sent_at` still suppresses re-sends at `now_ms`.
+    fn is_recent(&self, sent_at: i64, now_ms: i64) -> bool {
+        let bucket = self.config.recent_bucket_ms;
+        now_ms - sent_at <= self.config.recent_window_ms
+            || (bucket > 0 && sent_at.div_euclid(bucket) == now_ms.div_euclid(bucket))
+    }
+
+    fn record_filtered(&self, pair: &str, reason: DedupReason) {
+        let counter = match reason {
+            DedupReason::ActiveL1 => &self.filtered.active_l1,
+            DedupReason::RecentWindow => &self.filtered.recent_window,
+            DedupReason::StructuralUnchanged => &self.filtered.structural_unchanged,
+        };
+        counter.fetch_add(1, Ordering::Relaxed);
+        debug!("{}: dedup filtered ({})", pair, reason);
+    }
+
     pub async fn should_filter_pattern(
         &self,
         pair: &str,
@@ -40,10 +162,11 @@ impl Deduplicator {
         let mut active_l1 = self.active_l1_signals.write().await;
         let mut box1_states = self.box1_states.write().await;
 
+        let tolerance = self.tolerance_for(pair);
         let current_box1_state = (box1.high, box1.low);
         let box1_changed = if let Some(existing_state) = box1_states.get(pair) {
-            (existing_state.0 - box1.high).abs() >= BOX0_CHANGE_TOLERANCE
-                || (existing_state.1 - box1.low).abs() >= BOX0_CHANGE_TOLERANCE
+            (existing_state.0 - box1.high).abs() >= tolerance
+                || (existing_state.1 - box1.low).abs() >= tolerance
         } else {
             false
         };
@@ -54,10 +177,9 @@ impl Deduplicator {
 
         box1_states.insert(pair.to_string(), current_box1_state);
 
-        if pattern.level == 1 {
-            if self.should_filter_l1(pair, pattern, box1, &mut *active_l1, timestamp) {
-                return true;
-            }
+        if pattern.level == 1 && self.should_filter_l1(pair, pattern, box1, &mut active_l1, timestamp) {
+            self.record_filtered(pair, DedupReason::ActiveL1);
+            return true;
         }
 
         false
@@ -77,7 +199,7 @@ impl Deduplicator {
             .filter(|b| (is_long && b.integer_value > 0) || (!is_long && b.integer_value < 0))
             .collect();
         
-        structural.sort_by(|a, b| b.integer_value.abs().cmp(&a.integer_value.abs()));
+        structural.sort_by_key(|b| std::cmp::Reverse(b.integer_value.abs()));
 
         let tracked_structural: Vec<&BoxDetail> = structural
             .iter()
@@ -101,6 +223,7 @@ impl Deduplicator {
         // Include signal type in key to separate LONG and SHORT
         let tracking_key = format!("{}:{}:{}", pair, signal_type, structural_key);
 
+        let tolerance = self.tolerance_for(pair);
         let mut tracked = self.structural_boxes.write().await;
         let pattern_tracked = tracked.entry(tracking_key.clone()).or_insert_with(HashMap::new);
 
@@ -112,7 +235,8 @@ impl Deduplicator {
             let current = (box_detail.high, box_detail.low);
 
             if let Some(&tracked) = pattern_tracked.get(&integer_value) {
-                let changed = (tracked.0 - current.0).abs() >= TOLERANCE || (tracked.1 - current.1).abs() >= TOLERANCE;
+                let changed = (tracked.0 - current.0).abs() >= tolerance
+                    || (tracked.1 - current.1).abs() >= tolerance;
                 if changed {
                     any_changed = true;
                     all_match = false;
@@ -124,7 +248,11 @@ impl Deduplicator {
             }
         }
 
-        !any_changed && all_match
+        let unchanged = !any_changed && all_match;
+        if unchanged {
+            self.record_filtered(pair, DedupReason::StructuralUnchanged);
+        }
+        unchanged
     }
 
     fn should_filter_l1(
@@ -138,8 +266,9 @@ impl Deduplicator {
         let key = format!("{}:{}", pair, pattern.traversal_path.signal_type());
 
         if let Some(existing) = active_l1.get(&key) {
-            let box1_unchanged = (existing.box1_high - box1.high).abs() < BOX0_CHANGE_TOLERANCE
-                && (existing.box1_low - box1.low).abs() < BOX0_CHANGE_TOLERANCE;
+            let tolerance = self.tolerance_for(pair);
+            let box1_unchanged = (existing.box1_high - box1.high).abs() < tolerance
+                && (existing.box1_low - box1.low).abs() < tolerance;
 
             if box1_unchanged {
                 return true;
@@ -151,16 +280,127 @@ impl Deduplicator {
         false
     }
 
+    /// Filters a signal identical (type, level, entry, first stop, final target) to one sent for
+    /// the same pair within `recent_window_ms` (or the same `recent_bucket_ms` bucket). Prices
+    /// are identical when within `tolerance_for` or when they round to the same tick, so
+    /// sub-tick noise either side of a rounding boundary doesn't make a new signal. Expired
+    /// entries are pruned here.
+    pub async fn should_filter_recent_signal(&self, pair: &str, signal: &SignalMessage, now_ms: i64) -> bool {
+        let (Some(entry), Some(stop), Some(target)) = (
+            signal.entry,
+            signal.stop_losses.first().map(|sl| sl.price),
+            signal.targets.last().map(|t| t.price),
+        ) else {
+            return false;
+        };
+
+        let tolerance = self.tolerance_for(pair);
+        let point = crate::instruments::get_instrument_config(pair).point;
+        let same_price = |a: f64, b: f64| (a - b).abs() < tolerance || (a / point).round() == (b / point).round();
+        let mut recent = self.recent_signals.write().await;
+        let sent = recent.entry(pair.to_string()).or_default();
+        sent.retain(|r| self.is_recent(r.sent_at, now_ms));
+
+        let duplicate = sent.iter().any(|r| {
+            r.signal_type == signal.signal_type
+                && r.level == signal.level
+                && same_price(r.entry, entry)
+                && same_price(r.stop, stop)
+                && same_price(r.target, target)
+        });
+
+        if duplicate {
+            self.record_filtered(pair, DedupReason::RecentWindow);
+        } else {
+            sent.push(RecentSignal {
+                signal_type: signal.signal_type.clone(),
+                level: signal.level,
+                entry,
+                stop,
+                target,
+                sent_at: now_ms,
+            });
+        }
+
+        duplicate
+    }
+
+    /// Evicts recent signals older than the window across all pairs, including pairs that
+    /// have gone quiet and would never be pruned by `should_filter_recent_signal`.
+    pub async fn sweep(&self, now_ms: i64) -> usize {
+        let mut recent = self.recent_signals.write().await;
+        let before: usize = recent.values().map(|v| v.len()).sum();
+        for sent in recent.values_mut() {
+            sent.retain(|r| self.is_recent(r.sent_at, now_ms));
+        }
+        recent.retain(|_, sent| !sent.is_empty());
+        let after: usize = recent.values().map(|v| v.len()).sum();
+        before - after
+    }
+
+    pub async fn recent_signal_count(&self) -> usize {
+        self.recent_signals.read().await.values().map(|v| v.len()).sum()
+    }
+
+    /// Forgets every pair's dedup state, so replayed box updates signal again. Filter
+    /// counts are kept. Returns the number of entries dropped.
+    pub async fn reset(&self) -> usize {
+        let mut active_l1 = self.active_l1_signals.write().await;
+        let mut box1_states = self.box1_states.write().await;
+        let mut structural = self.structural_boxes.write().await;
+        let mut recent = self.recent_signals.write().await;
+        let cleared = active_l1.len() + box1_states.len() + structural.len() + recent.values().map(|v| v.len()).sum::<usize>();
+        active_l1.clear();
+        box1_states.clear();
+        structural.clear();
+        recent.clear();
+        cleared
+    }
+
+    /// `reset` for one pair (case-insensitive); other pairs keep their state.
+    pub async fn reset_pair(&self, pair: &str) -> usize {
+        let is_pair = |key: &str| key.split(':').next().is_some_and(|p| p.eq_ignore_ascii_case(pair));
+        let mut cleared = 0;
+
+        let mut active_l1 = self.active_l1_signals.write().await;
+        let before = active_l1.len();
+        active_l1.retain(|key, _| !is_pair(key));
+        cleared += before - active_l1.len();
+
+        let mut box1_states = self.box1_states.write().await;
+        let before = box1_states.len();
+        box1_states.retain(|key, _| !is_pair(key));
+        cleared += before - box1_states.len();
+
+        let mut structural = self.structural_boxes.write().await;
+        let before = structural.len();
+        structural.retain(|key, _| !is_pair(key));
+        cleared += before - structural.len();
+
+        let mut recent = self.recent_signals.write().await;
+        recent.retain(|key, sent| {
+            let keep = !is_pair(key);
+            if !keep {
+                cleared += sent.len();
+            }
+            keep
+        });
+        cleared
+    }
+
     pub async fn remove_l1_signal(&self, pair: &str, signal_type: &str) {
         let mut active_l1 = self.active_l1_signals.write().await;
         let key = format!("{}:{}", pair, signal_type);
         active_l1.remove(&key);
     }
 
+    /// Drops patterns whose values are a subset of a higher-level pattern in the same
+    /// direction. Runs after `should_filter_pattern`, so a suppressed higher level never
+    /// hides a lower one; the survivors each produce their own signal.
     pub fn remove_subset_duplicates(&self, patterns: Vec<PatternMatch>) -> Vec<PatternMatch> {
         let mut unique_patterns = Vec::new();
         let mut sorted_patterns = patterns;
-        sorted_patterns.sort_by(|a, b| b.level.cmp(&a.level));
+        sorted_patterns.sort_by_key(|p| std::cmp::Reverse(p.level));
         
         for pattern in sorted_patterns {
             let pattern_values: HashSet<i32> = pattern.traversal_path.path.iter().copied().collect();
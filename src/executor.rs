@@ -0,0 +1,77 @@
+use std::collections::HashMap;
+use std::future::Future;
+use std::pin::Pin;
+use tokio::sync::{mpsc, oneshot, RwLock};
+
+/// Default jobs queued per pair before `submit` waits for the pair to catch up.
+pub const DEFAULT_QUEUE_CAPACITY: usize = 256;
+
+type Job = Pin<Box<dyn Future<Output = ()> + Send>>;
+
+/// Runs jobs for different pairs in parallel while keeping each pair's jobs strictly in
+/// submission order. Every pair gets its own queue, drained one job at a time by a task
+/// spawned on first use, so a box update never overtakes an earlier one for the same pair
+/// (which would settle against a stale price or confuse the dedup box-1 state).
+pub struct PairExecutor {
+    queues: RwLock<HashMap<String, mpsc::Sender<Job>>>,
+    capacity: usize,
+}
+
+impl PairExecutor {
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            queues: RwLock::new(HashMap::new()),
+            capacity: capacity.max(1),
+        }
+    }
+
+    /// Queues `job` behind every job already submitted for `pair` (case-insensitive).
+    /// Waits only while the pair's queue is full. Await the returned receiver for the
+    /// job's output, or drop it to fire and forget.
+    pub async fn submit<F>(&self, pair: &str, job: F) -> oneshot::Receiver<F::Output>
+    where
+        F: Future + Send + 'static,
+        F::Output: Send + 'static,
+    {
+        let (done_tx, done_rx) = oneshot::channel();
+        let job: Job = Box::pin(async move {
+            let _ = done_tx.send(job.await);
+        });
+        let pair = pair.to_uppercase();
+        if let Err(mpsc::error::SendError(job)) = self.queue(&pair).await.send(job).await {
+            // The pair's worker died with a panicking job; start a fresh one
+            self.queues.write().await.remove(&pair);
+            let _ = self.queue(&pair).await.send(job).await;
+        }
+        done_rx
+    }
+
+    /// Pairs that have had at least one job submitted.
+    pub async fn pair_count(&self) -> usize {
+        self.queues.read().await.len()
+    }
+
+    async fn queue(&self, pair: &str) -> mpsc::Sender<Job> {
+        if let Some(queue) = self.queues.read().await.get(pair) {
+            return queue.clone();
+        }
+        let mut queues = self.queues.write().await;
+        if let Some(queue) = queues.get(pair) {
+            return queue.clone();
+        }
+        let (tx, mut rx) = mpsc::channel::<Job>(self.capacity);
+        tokio::spawn(async move {
+            while let Some(job) = rx.recv().await {
+                job.await;
+            }
+        });
+        queues.insert(pair.to_string(), tx.clone());
+        tx
+    }
+}
+
+impl Default for PairExecutor {
+    fn default() -> Self {
+        Self::new(DEFAULT_QUEUE_CAPACITY)
+    }
+}
@@ -0,0 +1,35 @@
+use crate::supabase::SettledSignalRow;
+
+pub const SETTLED_CSV_HEADER: &str =
+    "pair,type,level,entry,stop,target,rr,status,settled_price,created_at,settled_at\n";
+
+/// One CSV line for a settled signal: first stop, final target and final-target RR.
+/// Missing values are left empty.
+pub fn settled_csv_record(row: &SettledSignalRow) -> String {
+    let num = |v: Option<f64>| v.map(|v| v.to_string()).unwrap_or_default();
+    let fields = [
+        csv_field(&row.pair),
+        csv_field(&row.signal_type),
+        row.level.to_string(),
+        num(row.entry),
+        num(row.stop_losses.as_ref().and_then(|s| s.first()).map(|s| s.price)),
+        num(row.targets.as_ref().and_then(|t| t.last()).map(|t| t.price)),
+        num(row.risk_reward.as_ref().and_then(|rr| rr.last()).copied()),
+        csv_field(&row.status),
+        num(row.settled_price),
+        csv_field(row.created_at.as_deref().unwrap_or_default()),
+        csv_field(row.settled_at.as_deref().unwrap_or_default()),
+    ];
+    let mut line = fields.join(",");
+    line.push('\n');
+    line
+}
+
+/// Quotes a field containing a comma, quote or newline, doubling embedded quotes.
+fn csv_field(value: &str) -> String {
+    if value.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", value.replace('"', "\"\""))
+    } else {
+        value.to_string()
+    }
+}
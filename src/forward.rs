@@ -0,0 +1,57 @@
+use crate::types::SignalMessage;
+
+/// Body encoding for signals forwarded downstream, chosen by `FORWARD_FORMAT`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ForwardFormat {
+    #[default]
+    Json,
+    /// Named-field msgpack, the same framing as the `/ws/signals` stream.
+    Msgpack,
+}
+
+impl ForwardFormat {
+    pub fn content_type(self) -> &'static str {
+        match self {
+            Self::Json => "application/json",
+            Self::Msgpack => "application/msgpack",
+        }
+    }
+
+    pub fn encode(self, signal: &SignalMessage) -> Result<Vec<u8>, String> {
+        match self {
+            Self::Json => serde_json::to_vec(signal).map_err(|e| e.to_string()),
+            Self::Msgpack => rmp_serde::to_vec_named(signal).map_err(|e| e.to_string()),
+        }
+    }
+}
+
+impl std::fmt::Display for ForwardFormat {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self { Self::Json => "json", Self::Msgpack => "msgpack" })
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ParseForwardFormatError(pub String);
+
+impl std::fmt::Display for ParseForwardFormatError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "unknown forward format: {:?} (expected json or msgpack)", self.0)
+    }
+}
+
+impl std::error::Error for ParseForwardFormatError {}
+
+impl std::str::FromStr for ForwardFormat {
+    type Err = ParseForwardFormatError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if s.eq_ignore_ascii_case("json") {
+            Ok(Self::Json)
+        } else if s.eq_ignore_ascii_case("msgpack") {
+            Ok(Self::Msgpack)
+        } else {
+            Err(ParseForwardFormatError(s.to_string()))
+        }
+    }
+}
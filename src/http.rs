@@ -0,0 +1,18 @@
+use std::time::Duration;
+
+/// Default whole-request timeout for outbound HTTP, overridable with `HTTP_TIMEOUT_SECS`.
+pub const DEFAULT_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Builds an outbound HTTP client whose requests give up after `timeout`, so a hung peer
+/// can't stall the caller. Clones share one connection pool; `main` builds a single client
+/// and hands it to Supabase, the forwarders and the settlement webhook.
+pub fn client(timeout: Duration) -> reqwest::Client {
+    reqwest::Client::builder()
+        .timeout(timeout)
+        .connect_timeout(timeout.min(Duration::from_secs(5)))
+        .pool_idle_timeout(Duration::from_secs(90))
+        .pool_max_idle_per_host(16)
+        .tcp_keepalive(Duration::from_secs(60))
+        .build()
+        .expect("HTTP client configuration is valid")
+}
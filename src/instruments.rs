@@ -1,6 +1,7 @@
 use lazy_static::lazy_static;
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 use std::sync::RwLock;
+use tracing::info;
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum AssetClass {
@@ -9,20 +10,21 @@ pub enum AssetClass {
     Crypto,
 }
 
-fn get_asset_class(pair: &str) -> AssetClass {
+/// Metals are traded like forex. Crypto is a known base asset quoted in USD; the whole
+/// base must match, so e.g. `UNIXUSD` isn't mistaken for the `UNI` token. Any other six
+/// letters ending in a major currency are forex; everything else is a stock.
+pub fn get_asset_class(pair: &str) -> AssetClass {
     if pair == "XAUUSD" || pair == "XAGUSD" {
         return AssetClass::Forex;
     }
     
-    if pair.ends_with("USD") && pair.len() >= 6 {
-        let crypto_prefixes = ["ADA", "APT", "ASM", "BIGTIME", "BTC", "CLV", "ETH", "FET",
+    if let Some(base) = pair.strip_suffix("USD") {
+        let crypto_bases = ["ADA", "APT", "ASM", "BIGTIME", "BTC", "CLV", "ETH", "FET",
             "FIDA", "JTO", "LTC", "MEW", "PLU", "RARI", "SAND", "SEAM",
             "SOL", "TAO", "TOKEN", "UNI", "USDC", "USDG", "USDT", "XLM",
             "XMR", "XRP", "ZEC"];
-        for prefix in &crypto_prefixes {
-            if pair.len() >= prefix.len() && pair.starts_with(prefix) {
-                return AssetClass::Crypto;
-            }
+        if crypto_bases.contains(&base) {
+            return AssetClass::Crypto;
         }
     }
     
@@ -92,33 +94,150 @@ fn calculate_point_from_price(price: f64, asset_class: AssetClass) -> f64 {
     }
 }
 
+/// Per-pair pricing metadata. `point` and `digits` come from the live price once one has
+/// been seen; the rest follow from the asset class.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct InstrumentConfig {
+    /// Smallest price increment.
+    pub point: f64,
+    /// Decimal places prices are quoted to.
+    pub digits: u8,
+    /// Units in one lot: 100,000 for currency pairs, 100 oz for gold, 5,000 oz for
+    /// silver, 1 otherwise.
+    pub contract_size: f64,
+    /// Quote-currency value of one pip per lot. A pip is 10 points on forex and metals
+    /// (the fractional-pip convention) and one point elsewhere.
+    pub pip_value: f64,
+    /// Box values are signed opposite to price direction for this pair, so detection
+    /// negates them (swapping LONG and SHORT). Set via `set_inverted_pairs`.
+    pub invert: bool,
+}
+
+impl InstrumentConfig {
+    fn new(pair: &str, asset_class: AssetClass, point: f64) -> Self {
+        let contract_size = match pair {
+            "XAUUSD" => 100.0,
+            "XAGUSD" => 5_000.0,
+            _ => match asset_class {
+                AssetClass::Forex => 100_000.0,
+                AssetClass::Crypto | AssetClass::Stocks => 1.0,
+            },
+        };
+        let pip = match asset_class {
+            AssetClass::Forex => point * 10.0,
+            AssetClass::Crypto | AssetClass::Stocks => point,
+        };
+        Self {
+            point,
+            digits: calculate_digits_from_point(point),
+            contract_size,
+            pip_value: pip * contract_size,
+            invert: INVERTED_PAIRS.read().unwrap().contains(&pair.to_uppercase()),
+        }
+    }
+}
+
 lazy_static! {
-    static ref CONFIG_CACHE: RwLock<HashMap<String, (f64, u8)>> = 
+    static ref CONFIG_CACHE: RwLock<HashMap<String, InstrumentConfig>> = 
         RwLock::new(HashMap::with_capacity(2000));
     static ref PRICE_CACHE: RwLock<HashMap<String, f64>> = 
         RwLock::new(HashMap::with_capacity(2000));
+    static ref INVERTED_PAIRS: RwLock<HashSet<String>> = RwLock::new(HashSet::new());
+    static ref POINT_OVERRIDES: RwLock<HashMap<String, f64>> = RwLock::new(HashMap::new());
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ParsePointOverrideError(pub String);
+
+impl std::fmt::Display for ParsePointOverrideError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "invalid point override: {:?} (expected PAIR=point with a positive point)", self.0)
+    }
+}
+
+impl std::error::Error for ParsePointOverrideError {}
+
+/// Parses one `PAIR=point` entry of `INSTRUMENT_POINTS`, e.g. `DOGEUSD=0.00001`.
+pub fn parse_point_override(entry: &str) -> Result<(String, f64), ParsePointOverrideError> {
+    let err = || ParsePointOverrideError(entry.to_string());
+    let (pair, point) = entry.split_once('=').ok_or_else(err)?;
+    let pair = pair.trim().to_uppercase();
+    let point: f64 = point.trim().parse().map_err(|_| err())?;
+    if pair.is_empty() || !point.is_finite() || point <= 0.0 {
+        return Err(err());
+    }
+    Ok((pair, point))
+}
+
+/// Pins the point of each pair, taking precedence over both the static fallback and the
+/// price-derived value, for instruments the heuristics get wrong.
+pub fn set_point_overrides<I: IntoIterator<Item = (String, f64)>>(overrides: I) {
+    let mut pinned = POINT_OVERRIDES.write().unwrap();
+    let mut cache = CONFIG_CACHE.write().unwrap();
+    for (pair, point) in overrides {
+        let pair = pair.to_uppercase();
+        cache.insert(pair.clone(), InstrumentConfig::new(&pair, get_asset_class(&pair), point));
+        pinned.insert(pair, point);
+    }
+}
+
+/// Marks pairs whose box values are signed opposite to price direction (see
+/// `InstrumentConfig::invert`). Adds to the set; already-cached configs are updated too.
+pub fn set_inverted_pairs<I: IntoIterator<Item = S>, S: AsRef<str>>(pairs: I) {
+    // Released before CONFIG_CACHE is taken: `set_point_overrides` holds CONFIG_CACHE while
+    // `InstrumentConfig::new` reads INVERTED_PAIRS, so holding both here would deadlock
+    let inverted = {
+        let mut inverted = INVERTED_PAIRS.write().unwrap();
+        inverted.extend(pairs.into_iter().map(|p| p.as_ref().to_uppercase()));
+        inverted.clone()
+    };
+    for (pair, config) in CONFIG_CACHE.write().unwrap().iter_mut() {
+        config.invert = inverted.contains(&pair.to_uppercase());
+    }
 }
 
+/// Derives the pair's point from its first valid live price and caches it. Called for every
+/// box update before detection; returns true when the config was (re)computed. The config
+/// is recomputed when the price moves clearly out of the power of ten of the price it was
+/// derived from, so e.g. a token rallying from $8 to $90 stops using the $8 point, while one
+/// trading around $10 doesn't swap points on every crossing (see `MAGNITUDE_HYSTERESIS`).
 pub fn update_instrument_price(pair: &str, price: f64) -> bool {
+    // Caches and overrides are keyed by the uppercase pair, whatever case the feed uses
+    let pair = &pair.to_uppercase();
+    if !price.is_finite() || price <= 0.0 || POINT_OVERRIDES.read().unwrap().contains_key(pair) {
+        return false;
+    }
     let asset_class = get_asset_class(pair);
     
-    let should_update = {
+    let cached_price = {
         let price_cache = PRICE_CACHE.read().unwrap();
-        !price_cache.contains_key(pair)
+        price_cache.get(pair).copied()
+    };
+    
+    let should_update = match cached_price {
+        None => true,
+        Some(cached) => left_magnitude(cached, price),
     };
     
     if should_update {
         let point = calculate_point_from_price(price, asset_class);
-        let digits = calculate_digits_from_point(point);
+        let config = InstrumentConfig::new(pair, asset_class, point);
         
         {
             let mut price_cache = PRICE_CACHE.write().unwrap();
             price_cache.insert(pair.to_string(), price);
         }
         
-        {
+        let previous = {
             let mut config_cache = CONFIG_CACHE.write().unwrap();
-            config_cache.insert(pair.to_string(), (point, digits));
+            config_cache.insert(pair.to_string(), config)
+        };
+        
+        if let (Some(cached), Some(previous)) = (cached_price, previous) {
+            info!(
+                "[Instruments] {} moved from {} to {}; point {} -> {}",
+                pair, cached, price, previous.point, config.point
+            );
         }
         
         true
@@ -127,7 +246,27 @@ pub fn update_instrument_price(pair: &str, price: f64) -> bool {
     }
 }
 
-pub fn get_instrument_config(pair: &str) -> (f64, u8) {
+fn order_of_magnitude(price: f64) -> i32 {
+    price.log10().floor() as i32
+}
+
+/// How far past a power-of-ten boundary the price must go before the point is recomputed,
+/// as a fraction of the boundary. Each recompute changes the box integer values, so a
+/// price oscillating around e.g. 10.0 would otherwise churn pattern matching and dedup.
+const MAGNITUDE_HYSTERESIS: f64 = 0.1;
+
+/// True when `price` is outside the power of ten of `derived_from`, widened on both sides
+/// by `MAGNITUDE_HYSTERESIS`.
+fn left_magnitude(derived_from: f64, price: f64) -> bool {
+    let floor = 10f64.powi(order_of_magnitude(derived_from));
+    price < floor * (1.0 - MAGNITUDE_HYSTERESIS) || price >= floor * 10.0 * (1.0 + MAGNITUDE_HYSTERESIS)
+}
+
+/// Prefers the price-derived config cached by `update_instrument_price`. Until a price
+/// has been seen, a static per-asset-class fallback is returned without being cached, so
+/// it never shadows the live value.
+pub fn get_instrument_config(pair: &str) -> InstrumentConfig {
+    let pair = &pair.to_uppercase();
     {
         let cache = CONFIG_CACHE.read().unwrap();
         if let Some(&config) = cache.get(pair) {
@@ -155,14 +294,6 @@ pub fn get_instrument_config(pair: &str) -> (f64, u8) {
         }
         AssetClass::Stocks => 0.01,
     };
-    
-    let digits = calculate_digits_from_point(point);
-    let config = (point, digits);
-    
-    {
-        let mut cache = CONFIG_CACHE.write().unwrap();
-        cache.insert(pair.to_string(), config);
-    }
-    
-    config
+
+    InstrumentConfig::new(pair, asset_class, point)
 }
@@ -1,11 +1,22 @@
+pub mod auth;
+pub mod backtest;
 pub mod deduplication;
+pub mod executor;
+pub mod export;
+pub mod forward;
+pub mod http;
 pub mod instruments;
+pub mod metrics;
 pub mod patterns;
+pub mod pipeline;
+pub mod rate_limit;
 pub mod scanner;
 pub mod signal;
 pub mod supabase;
 pub mod tracker;
 pub mod types;
+pub mod volatility;
+pub mod webhook;
 
 // Re-export for tests
 pub use scanner::MarketScanner;
@@ -1,43 +1,182 @@
 use signals_rthmn::{
-    deduplication::Deduplicator,
-    scanner::MarketScanner,
-    signal::SignalGenerator,
-    supabase::SupabaseClient,
-    tracker::{ActiveSignal, SignalTracker},
-    types::{SignalMessage, SignalType},
+    auth::{cors_layer, parse_cors_origins, token_matches, ws_auth_token},
+    deduplication::{DedupConfig, Deduplicator},
+    executor::PairExecutor,
+    export::{settled_csv_record, SETTLED_CSV_HEADER},
+    forward::ForwardFormat,
+    metrics::Metrics,
+    pipeline::{box_integer_values, parse_pair_list, Pipeline, RuntimeConfig},
+    patterns::PatternGraph,
+    rate_limit::PairRateLimiter,
+    scanner::{MarketScanner, ScanLimits},
+    signal::{DirectionFilter, SignalGenerator, TargetModel},
+    supabase::{SignalIdMode, SignalRow, SupabaseClient},
+    tracker::{ActiveSignal, SettlementModel, SignalTracker},
+    types::{parse_box_update, BoxData, BoxUpdateError, ParsedUpdate, SignalFeedMessage, SignalMessage, SignalType},
+    volatility::VolatilityTracker,
+    webhook::SettlementWebhook,
 };
 use axum::{
     extract::{
-        ws::{Message, WebSocket, WebSocketUpgrade},
-        State,
+        ws::{close_code, CloseFrame, Message, WebSocket, WebSocketUpgrade},
+        Path, Query, State,
     },
+    http::{header, HeaderMap, StatusCode},
     response::IntoResponse,
-    routing::get,
+    routing::{get, post},
     Json, Router,
 };
 use chrono::Utc;
 use futures_util::{SinkExt, StreamExt};
-use std::{env, sync::Arc};
-use tokio::sync::{mpsc, RwLock};
-use tower_http::cors::{Any, CorsLayer};
+use std::{
+    collections::{HashMap, HashSet, VecDeque},
+    env,
+    sync::{
+        atomic::{AtomicI64, AtomicU64, Ordering},
+        Arc,
+    },
+};
+use tokio::sync::{broadcast, mpsc, watch, RwLock};
 use tracing::{debug, info, warn};
 
 pub struct AppState {
     scanner: RwLock<MarketScanner>,
     generator: SignalGenerator,
+    /// Per-pair ATR from recent box updates, for `TargetModel::Volatility` targets.
+    volatility: VolatilityTracker,
     tracker: SignalTracker,
     deduplicator: Deduplicator,
-    signals_sent: RwLock<u64>,
-    main_server_url: String,
-    signal_tx: mpsc::Sender<SignalMessage>,
+    metrics: Metrics,
+    forward_max_attempts: u32,
+    forward_format: ForwardFormat,
+    auth_token: String,
+    ws_token: String,
+    ws_ping_interval: tokio::time::Duration,
+    ws_idle_timeout: tokio::time::Duration,
+    forward_targets: Vec<ForwardTarget>,
+    /// Shared outbound client; clones reuse its connection pool.
+    http: reqwest::Client,
+    signal_broadcast: broadcast::Sender<SignalMessage>,
+    signal_ttl_ms: Option<i64>,
+    /// Thresholds `POST /api/admin/reload` can change; see `RuntimeConfig`.
+    config: RwLock<Arc<RuntimeConfig>>,
+    /// Epoch ms of the last emitted signal per (pair, type), for the cooldown.
+    last_fired_at: RwLock<HashMap<(String, SignalType), i64>>,
+    dry_run: bool,
+    /// Pair (uppercase) whose box updates are traced step by step at info level.
+    debug_pair: Option<String>,
+    history: RwLock<HashMap<String, VecDeque<BoxData>>>,
+    history_size: usize,
+    /// Box updates run through here: pairs in parallel, each pair in arrival order.
+    pair_executor: PairExecutor,
+    /// Per-pair box update rate from `BOX_UPDATE_RATE`; `None` leaves updates unthrottled.
+    box_rate_limiter: Option<PairRateLimiter>,
+    /// Latest over-rate update per pair (uppercase), waiting for the pair's next token.
+    parked_updates: RwLock<HashMap<String, ParkedUpdate>>,
+    /// Most recent emitted signals, exactly as forwarded, for `/api/signals/:id/resend`.
+    sent_signals: RwLock<VecDeque<SignalMessage>>,
+    sent_cache_size: usize,
+    shutdown: watch::Receiver<bool>,
+    last_fingerprints: RwLock<HashMap<String, u64>>,
+    started_at_ms: i64,
+    /// Epoch ms of the last box update received over `/ws` or `POST /api/box-update`; 0
+    /// until the first one.
+    last_box_update_ms: AtomicI64,
+    ready_box_stale_ms: i64,
+    ready_supabase_stale_ms: i64,
+}
+
+/// One downstream endpoint; each has its own queue and forwarder task so a slow or
+/// failing target never holds up the others.
+pub struct ForwardTarget {
+    url: String,
+    tx: mpsc::Sender<SignalMessage>,
+    sent: AtomicU64,
+    failed: AtomicU64,
+    dropped: AtomicU64,
+}
+
+impl ForwardTarget {
+    fn new(url: String) -> (Self, mpsc::Receiver<SignalMessage>) {
+        let (tx, rx) = mpsc::channel::<SignalMessage>(1000);
+        let target = Self {
+            url,
+            tx,
+            sent: AtomicU64::new(0),
+            failed: AtomicU64::new(0),
+            dropped: AtomicU64::new(0),
+        };
+        (target, rx)
+    }
+
+    fn status(&self) -> serde_json::Value {
+        serde_json::json!({
+            "url": self.url,
+            "sent": self.sent.load(Ordering::Relaxed),
+            "failed": self.failed.load(Ordering::Relaxed),
+            "dropped": self.dropped.load(Ordering::Relaxed)
+        })
+    }
+}
+
+impl AppState {
+    fn record_forward_sent(&self, target: &ForwardTarget) {
+        target.sent.fetch_add(1, Ordering::Relaxed);
+        self.metrics.record_sent();
+    }
+
+    fn record_forward_dropped(&self, target: &ForwardTarget) {
+        target.dropped.fetch_add(1, Ordering::Relaxed);
+        self.metrics.record_dropped();
+    }
+
+    /// The current runtime config; hold on to it for a whole box update so a concurrent
+    /// reload can't mix old and new thresholds.
+    async fn config(&self) -> Arc<RuntimeConfig> {
+        self.config.read().await.clone()
+    }
+
+    async fn remember_sent(&self, signal: SignalMessage) {
+        if self.sent_cache_size == 0 {
+            return;
+        }
+        let mut sent = self.sent_signals.write().await;
+        if sent.len() >= self.sent_cache_size {
+            sent.pop_front();
+        }
+        sent.push_back(signal);
+    }
+
+
+    /// Whether the request carries `Authorization: Bearer <SUPABASE_SERVICE_ROLE_KEY>`.
+    fn is_authorized(&self, headers: &HeaderMap) -> bool {
+        let token = headers
+            .get(header::AUTHORIZATION)
+            .and_then(|v| v.to_str().ok())
+            .and_then(|v| v.strip_prefix("Bearer "));
+        token_matches(token, &self.auth_token)
+    }
+}
+
+/// Comma-separated pair list from `name`; unset yields an empty set.
+fn env_pair_set(name: &str) -> HashSet<String> {
+    parse_pair_list(&env::var(name).unwrap_or_default())
 }
 
 #[tokio::main]
 async fn main() -> Result<(), Box<dyn std::error::Error>> {
-    tracing_subscriber::fmt()
-        .with_env_filter("signals_rthmn=info")
-        .init();
     dotenvy::dotenv().ok();
+    if env::var("LOG_FORMAT").is_ok_and(|v| v.eq_ignore_ascii_case("json")) {
+        tracing_subscriber::fmt()
+            .json()
+            .flatten_event(true)
+            .with_env_filter("signals_rthmn=info")
+            .init();
+    } else {
+        tracing_subscriber::fmt()
+            .with_env_filter("signals_rthmn=info")
+            .init();
+    }
 
     info!("==================================================");
     info!("  SIGNALS.RTHMN.COM - Rust Edition");
@@ -52,53 +191,453 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
     let auth_token = env::var("SUPABASE_SERVICE_ROLE_KEY").expect("SUPABASE_SERVICE_ROLE_KEY required");
     let supabase_url = env::var("SUPABASE_URL").expect("SUPABASE_URL required");
     let supabase_key = auth_token.clone();
+    let ws_token = env::var("BOXES_WS_TOKEN").unwrap_or_else(|_| auth_token.clone());
+
+    let forward_max_attempts: u32 = env::var("FORWARD_MAX_ATTEMPTS")
+        .ok()
+        .and_then(|v| v.parse().ok())
+        .unwrap_or(5)
+        .max(1);
+
+    // An unparseable list aborts startup: falling back to Any would silently open the
+    // admin endpoints to every site
+    let cors_origins = match env::var("CORS_ORIGINS") {
+        Ok(list) => Some(parse_cors_origins(&list)?),
+        Err(_) => None,
+    };
+    if let Some(origins) = &cors_origins {
+        info!("CORS restricted to {} origin(s) with credentials", origins.len());
+    }
+
+    let forward_format = match env::var("FORWARD_FORMAT") {
+        Ok(v) => v.parse().unwrap_or_else(|e| {
+            warn!("{}, falling back to json", e);
+            ForwardFormat::Json
+        }),
+        Err(_) => ForwardFormat::Json,
+    };
+    let forward_urls: Vec<String> = match env::var("FORWARD_URLS") {
+        Ok(urls) => urls
+            .split(',')
+            .map(|u| u.trim().to_string())
+            .filter(|u| !u.is_empty())
+            .collect(),
+        Err(_) => vec![format!("{}/signals/raw", main_server_url.trim_end_matches('/'))],
+    };
 
     info!("Supabase URL: {}", supabase_url);
-    info!("Main server URL: {}", main_server_url);
+    info!("Forwarding {} to {} target(s): {:?}", forward_format, forward_urls.len(), forward_urls);
 
-    let mut scanner = MarketScanner::default();
-    scanner.initialize();
+    let graph = match env::var("PATTERNS_FILE") {
+        Ok(path) => {
+            let graph = PatternGraph::from_file(&path)?;
+            info!(
+                "Loaded pattern graph from {}: {} starting point(s), {} box(es), leaf values {:?}",
+                path,
+                graph.starting_points.len(),
+                graph.boxes.len(),
+                graph.leaf_values()
+            );
+            graph
+        }
+        Err(_) => PatternGraph::builtin(),
+    };
+    let scan_defaults = ScanLimits::default();
+    let limits = ScanLimits {
+        max_depth: env::var("SCANNER_MAX_DEPTH")
+            .ok()
+            .and_then(|v| v.parse().ok())
+            .unwrap_or(scan_defaults.max_depth),
+        max_paths: env::var("SCANNER_MAX_PATHS")
+            .ok()
+            .and_then(|v| v.parse().ok())
+            .unwrap_or(scan_defaults.max_paths),
+        max_level: env::var("SCANNER_MAX_LEVEL")
+            .ok()
+            .and_then(|v| v.parse().ok())
+            .unwrap_or(scan_defaults.max_level),
+        min_boxes: env::var("SCANNER_MIN_BOXES")
+            .ok()
+            .and_then(|v| v.parse().ok())
+            .unwrap_or(scan_defaults.min_boxes),
+    };
+    let value_tolerance: f64 = env::var("SCANNER_VALUE_TOLERANCE")
+        .ok()
+        .and_then(|v| v.parse().ok())
+        .unwrap_or(0.0);
+    let mut scanner = MarketScanner::with_limits(limits).with_value_tolerance(value_tolerance);
+    scanner.initialize_with(graph);
     info!("MarketScanner initialized with {} paths", scanner.path_count());
 
-    let supabase = SupabaseClient::new(&supabase_url, &supabase_key);
-    let tracker = SignalTracker::new(supabase);
-    info!("SignalTracker initialized");
+    let supabase_max_attempts: u32 = env::var("SUPABASE_MAX_ATTEMPTS")
+        .ok()
+        .and_then(|v| v.parse().ok())
+        .unwrap_or(signals_rthmn::supabase::DEFAULT_MAX_ATTEMPTS);
+    let http_timeout = env::var("HTTP_TIMEOUT_SECS")
+        .ok()
+        .and_then(|v| v.parse().ok())
+        .map_or(signals_rthmn::http::DEFAULT_TIMEOUT, std::time::Duration::from_secs);
+    let http = signals_rthmn::http::client(http_timeout);
+    let signal_id_mode = match env::var("SIGNAL_ID_BUCKET_SECS").ok().and_then(|v| v.parse::<u64>().ok()) {
+        Some(bucket_secs) if bucket_secs > 0 => SignalIdMode::Bucketed { bucket_secs },
+        _ => SignalIdMode::Timestamp,
+    };
+    let supabase = SupabaseClient::new(&supabase_url, &supabase_key)
+        .with_client(http.clone())
+        .with_max_attempts(supabase_max_attempts)
+        .with_signal_id_mode(signal_id_mode);
+    let max_signals_per_pair: Option<usize> = env::var("MAX_SIGNALS_PER_PAIR")
+        .ok()
+        .and_then(|v| v.parse().ok());
+    let runtime_config = RuntimeConfig::from_vars(&env::vars().collect());
+    if !runtime_config.min_rr.by_level.is_empty() {
+        let mut overrides: Vec<_> = runtime_config.min_rr.by_level.iter().collect();
+        overrides.sort_by_key(|(level, _)| **level);
+        info!("Minimum RR {} with per-level overrides {:?}", runtime_config.min_rr.default, overrides);
+    }
+    let dry_run = env::var("DRY_RUN").is_ok_and(|v| v == "1" || v.eq_ignore_ascii_case("true"));
+    if dry_run {
+        warn!("DRY RUN: Supabase writes and main-server forwarding are disabled");
+    }
+    let settlement_model = match env::var("SETTLEMENT_MODEL") {
+        Ok(v) => v.parse().unwrap_or_else(|e| {
+            warn!("{}, falling back to touch", e);
+            SettlementModel::Touch
+        }),
+        Err(_) => SettlementModel::Touch,
+    };
+    let mut tracker = SignalTracker::new((!dry_run).then_some(supabase), max_signals_per_pair)
+        .with_settlement_model(settlement_model);
+    if let Some(fraction) = env::var("NEAR_STOP_FRACTION").ok().and_then(|v| v.parse::<f64>().ok()) {
+        if fraction > 0.0 && fraction < 1.0 {
+            info!("Near-stop warnings at {:.0}% of the distance to the stop", fraction * 100.0);
+            tracker = tracker.with_near_stop_warning(fraction);
+        } else {
+            warn!("NEAR_STOP_FRACTION {} is outside (0, 1), near-stop warnings disabled", fraction);
+        }
+    }
+    if let Ok(list) = env::var("TAKE_PROFIT_FRACTIONS") {
+        match signals_rthmn::tracker::parse_take_profit_fractions(&list) {
+            Ok(fractions) => {
+                info!("Scaled exits: {:?} of the position at the targets before the last", fractions);
+                tracker = tracker.with_take_profit_fractions(fractions);
+            }
+            Err(e) => warn!("{}, falling back to a single exit at the final target", e),
+        }
+    }
+    if let Some(risk_multiple) = env::var("TRAIL_RISK_MULTIPLE").ok().and_then(|v| v.parse::<f64>().ok()) {
+        let min_level: u32 = env::var("TRAIL_MIN_LEVEL").ok().and_then(|v| v.parse().ok()).unwrap_or(3);
+        if risk_multiple > 0.0 {
+            info!("Trailing stops: L{}+ signals trail the best price by {}x their initial risk", min_level, risk_multiple);
+            tracker = tracker.with_trailing_stop(min_level, risk_multiple);
+        } else {
+            warn!("TRAIL_RISK_MULTIPLE {} is not positive, trailing stops disabled", risk_multiple);
+        }
+    }
+    if let Ok(url) = env::var("SETTLEMENT_WEBHOOK_URL") {
+        if dry_run {
+            warn!("DRY RUN: settlement webhook {} is disabled", url);
+        } else {
+            let secret = env::var("SETTLEMENT_WEBHOOK_SECRET").ok().filter(|s| !s.is_empty());
+            if secret.is_none() {
+                warn!("SETTLEMENT_WEBHOOK_SECRET not set, settlement webhook requests will be unsigned");
+            }
+            info!("Settlement webhook: {}", url);
+            tracker = tracker.with_settlement_webhook(SettlementWebhook::new(&url, secret).with_client(http.clone()));
+        }
+    }
+    let signal_ttl_ms: Option<i64> = env::var("SIGNAL_TTL_SECS")
+        .ok()
+        .and_then(|v| v.parse::<i64>().ok())
+        .map(|secs| secs * 1000);
+    // 0 re-tracks every active row, however old
+    let max_hydration_age = match env::var("HYDRATION_MAX_AGE_HOURS").ok().and_then(|v| v.parse::<u64>().ok()) {
+        Some(0) => None,
+        Some(hours) => Some(std::time::Duration::from_secs(hours * 3600)),
+        None => Some(signals_rthmn::tracker::DEFAULT_MAX_HYDRATION_AGE),
+    };
+    let tracker = tracker.with_max_hydration_age(max_hydration_age);
+    tracker.hydrate_from_supabase().await;
+    info!("SignalTracker initialized with {} active signal(s)", tracker.get_active_count().await);
+
+    let defaults = DedupConfig::default();
+    let dedup_config = DedupConfig {
+        price_tolerance: env::var("DEDUP_PRICE_TOLERANCE")
+            .ok()
+            .and_then(|v| v.parse().ok())
+            .unwrap_or(defaults.price_tolerance),
+        recent_window_ms: env::var("DEDUP_WINDOW_MS")
+            .ok()
+            .and_then(|v| v.parse().ok())
+            .unwrap_or(defaults.recent_window_ms),
+        recent_bucket_ms: env::var("DEDUP_BUCKET_MS")
+            .ok()
+            .and_then(|v| v.parse().ok())
+            .unwrap_or(defaults.recent_bucket_ms),
+    };
+    info!(
+        "Deduplicator: price tolerance {}, recent window {}ms",
+        dedup_config.price_tolerance, dedup_config.recent_window_ms
+    );
 
-    let (signal_tx, signal_rx) = mpsc::channel::<SignalMessage>(1000);
+    let debug_pair = env::var("DEBUG_PAIR").ok().map(|p| p.trim().to_uppercase()).filter(|p| !p.is_empty());
+    if let Some(pair) = &debug_pair {
+        info!("Tracing every box update for {}", pair);
+    }
+    let box_rate_limiter = env::var("BOX_UPDATE_RATE")
+        .ok()
+        .and_then(|v| v.parse::<f64>().ok())
+        .filter(|rate| *rate > 0.0)
+        .map(|rate| {
+            let burst = env::var("BOX_UPDATE_BURST").ok().and_then(|v| v.parse().ok()).unwrap_or(rate);
+            PairRateLimiter::new(rate, burst)
+        });
+    if let Some(limiter) = &box_rate_limiter {
+        info!("Box updates limited to {}/s per pair (burst {}), coalescing to the latest", limiter.rate(), limiter.burst());
+    }
+    let point_overrides: Vec<(String, f64)> = env::var("INSTRUMENT_POINTS")
+        .unwrap_or_default()
+        .split(',')
+        .filter(|entry| !entry.trim().is_empty())
+        .filter_map(|entry| match signals_rthmn::instruments::parse_point_override(entry) {
+            Ok(point) => Some(point),
+            Err(e) => {
+                warn!("{}, ignoring it", e);
+                None
+            }
+        })
+        .collect();
+    if !point_overrides.is_empty() {
+        info!("Instrument point overrides: {:?}", point_overrides);
+        signals_rthmn::instruments::set_point_overrides(point_overrides);
+    }
+    let inverted_pairs = env_pair_set("INVERTED_PAIRS");
+    if !inverted_pairs.is_empty() {
+        info!("Inverting box values for {:?}", inverted_pairs);
+        signals_rthmn::instruments::set_inverted_pairs(&inverted_pairs);
+    }
+    if !runtime_config.pairs_allowlist.is_empty() || !runtime_config.pairs_blocklist.is_empty() {
+        info!("Pair filter: allow {:?}, block {:?}", runtime_config.pairs_allowlist, runtime_config.pairs_blocklist);
+    }
+
+    let mid_entry_rules = env::var("MID_ENTRY_RULES").is_ok_and(|v| v == "1" || v.eq_ignore_ascii_case("true"));
+    if mid_entry_rules {
+        info!("Experimental MID-entry trade rules enabled");
+    }
+
+    let ws_ping_interval_secs: u64 = env::var("WS_PING_INTERVAL_SECS")
+        .ok()
+        .and_then(|v| v.parse().ok())
+        .unwrap_or(30)
+        .max(1);
+    let ws_idle_timeout_secs: u64 = env::var("WS_IDLE_TIMEOUT_SECS")
+        .ok()
+        .and_then(|v| v.parse().ok())
+        .unwrap_or(90)
+        .max(1);
+
+    let ready_box_stale_secs: i64 = env::var("READY_BOX_STALE_SECS")
+        .ok()
+        .and_then(|v| v.parse().ok())
+        .unwrap_or(300);
+    let ready_supabase_stale_secs: i64 = env::var("READY_SUPABASE_STALE_SECS")
+        .ok()
+        .and_then(|v| v.parse().ok())
+        .unwrap_or(300);
+
+    let direction_filter = match env::var("DIRECTION_FILTER") {
+        Ok(v) => v.parse().unwrap_or_else(|e| {
+            warn!("{}, falling back to both", e);
+            DirectionFilter::Both
+        }),
+        Err(_) => DirectionFilter::Both,
+    };
+    if direction_filter != DirectionFilter::Both {
+        info!("Only generating {} signals", direction_filter);
+    }
+    let min_box_size_ticks: f64 = env::var("MIN_BOX_SIZE_TICKS")
+        .ok()
+        .and_then(|v| v.parse().ok())
+        .unwrap_or(0.0);
+    let target_multiplier: f64 = env::var("TARGET_MULTIPLIER")
+        .ok()
+        .and_then(|v| v.parse().ok())
+        .unwrap_or(1.0);
+    if target_multiplier != 1.0 {
+        info!("Final target at {}x box size", target_multiplier);
+    }
+    let target_model = match env::var("TARGET_MODEL") {
+        Ok(v) => v.parse().unwrap_or_else(|e| {
+            warn!("{}, falling back to box_size", e);
+            TargetModel::BoxSize
+        }),
+        Err(_) => TargetModel::BoxSize,
+    };
+    let atr_period: usize = env::var("ATR_PERIOD")
+        .ok()
+        .and_then(|v| v.parse().ok())
+        .unwrap_or(signals_rthmn::volatility::DEFAULT_ATR_PERIOD);
+    let atr_multiplier: f64 = env::var("ATR_TARGET_MULTIPLIER")
+        .ok()
+        .and_then(|v| v.parse().ok())
+        .unwrap_or(1.0);
+    if target_model == TargetModel::Volatility {
+        info!("Final target at {} ATR(s) from entry, ATR over {} box updates", atr_multiplier, atr_period);
+    }
+    let target_box: usize = env::var("TARGET_BOX")
+        .ok()
+        .and_then(|v| v.parse().ok())
+        .unwrap_or(0);
+    if target_box != 0 {
+        info!("Targets anchored at box {}", target_box);
+    }
+
+    let risk_per_trade: Option<f64> = env::var("RISK_PER_TRADE")
+        .ok()
+        .and_then(|v| v.parse().ok());
+
+    let history_size: usize = env::var("HISTORY_SIZE")
+        .ok()
+        .and_then(|v| v.parse().ok())
+        .unwrap_or(20);
+    let sent_cache_size: usize = env::var("SENT_CACHE_SIZE")
+        .ok()
+        .and_then(|v| v.parse().ok())
+        .unwrap_or(500);
+
+    let (forward_targets, forward_rxs): (Vec<ForwardTarget>, Vec<_>) =
+        forward_urls.into_iter().map(ForwardTarget::new).unzip();
+    let (signal_broadcast, _) = broadcast::channel::<SignalMessage>(256);
+    let (shutdown_tx, shutdown_rx) = watch::channel(false);
 
     let state = Arc::new(AppState {
         scanner: RwLock::new(scanner),
-        generator: SignalGenerator::default(),
+        generator: SignalGenerator::with_mid_entry_rules(mid_entry_rules)
+            .with_target_multiplier(target_multiplier)
+            .with_target_box(target_box)
+            .with_target_model(target_model)
+            .with_atr_multiplier(atr_multiplier)
+            .with_risk_per_trade(risk_per_trade)
+            .with_min_box_size(min_box_size_ticks)
+            .with_direction_filter(direction_filter),
+        volatility: VolatilityTracker::new(atr_period),
         tracker,
-        deduplicator: Deduplicator::new(),
-        signals_sent: RwLock::new(0),
-        main_server_url,
-        signal_tx,
+        deduplicator: Deduplicator::with_config(dedup_config),
+        metrics: Metrics::default(),
+        forward_max_attempts,
+        forward_format,
+        auth_token,
+        ws_token,
+        ws_ping_interval: tokio::time::Duration::from_secs(ws_ping_interval_secs),
+        ws_idle_timeout: tokio::time::Duration::from_secs(ws_idle_timeout_secs),
+        forward_targets,
+        http,
+        signal_broadcast,
+        signal_ttl_ms,
+        config: RwLock::new(Arc::new(runtime_config)),
+        last_fired_at: RwLock::new(HashMap::new()),
+        dry_run,
+        debug_pair,
+        history: RwLock::new(HashMap::new()),
+        history_size,
+        pair_executor: PairExecutor::default(),
+        box_rate_limiter,
+        parked_updates: RwLock::new(HashMap::new()),
+        sent_signals: RwLock::new(VecDeque::new()),
+        sent_cache_size,
+        shutdown: shutdown_rx,
+        last_fingerprints: RwLock::new(HashMap::new()),
+        started_at_ms: Utc::now().timestamp_millis(),
+        last_box_update_ms: AtomicI64::new(0),
+        ready_box_stale_ms: ready_box_stale_secs * 1000,
+        ready_supabase_stale_ms: ready_supabase_stale_secs * 1000,
     });
 
+    let forwarders: Vec<_> = forward_rxs
+        .into_iter()
+        .enumerate()
+        .map(|(idx, rx)| tokio::spawn(main_server_forwarder(Arc::clone(&state), idx, rx)))
+        .collect();
+
     let state_clone = Arc::clone(&state);
     tokio::spawn(async move {
-        main_server_forwarder(state_clone, auth_token, signal_rx).await;
+        let mut interval = tokio::time::interval(tokio::time::Duration::from_secs(60));
+        loop {
+            interval.tick().await;
+            let now_ms = Utc::now().timestamp_millis();
+            if let Some(supabase) = state_clone.tracker.supabase() {
+                let _ = supabase.ping().await;
+            }
+            let evicted = state_clone.deduplicator.sweep(now_ms).await;
+            if evicted > 0 {
+                debug!("Deduplicator sweep evicted {} recent signal(s)", evicted);
+            }
+            for settlement in state_clone.tracker.expire_stale(now_ms).await {
+                if settlement.signal.level == 1 {
+                    state_clone
+                        .deduplicator
+                        .remove_l1_signal(&settlement.signal.pair, &settlement.signal.signal_type.to_string())
+                        .await;
+                }
+            }
+        }
     });
 
     let app = Router::new()
         .route("/health", get(health))
+        .route("/ready", get(ready))
         .route("/api/status", get(status))
+        .route("/metrics", get(metrics))
+        .route("/api/history/:pair", get(history))
+        .route("/api/box-update", post(box_update))
+        .route("/api/admin/reload", post(admin_reload))
+        .route("/api/admin/dedup/reset", post(admin_dedup_reset))
+        .route("/api/analyze", post(analyze))
+        .route("/api/signals/export", get(export_signals))
+        .route("/api/signals/:signal_id", get(signal_lookup))
+        .route("/api/signals/:signal_id/close", post(close_signal))
+        .route("/api/signals/:signal_id/resend", post(resend_signal))
         .route("/ws", get(ws_handler))
-        .layer(
-            CorsLayer::new()
-                .allow_origin(Any)
-                .allow_methods(Any)
-                .allow_headers(Any),
-        )
+        .route("/ws/signals", get(ws_signals_handler))
+        .layer(cors_layer(cors_origins))
         .with_state(state);
 
     let listener = tokio::net::TcpListener::bind(format!("0.0.0.0:{}", port)).await?;
     info!("Server running on port {} (WebSocket at /ws)", port);
-    axum::serve(listener, app).await?;
+    axum::serve(listener, app)
+        .with_graceful_shutdown(async move {
+            shutdown_signal().await;
+            info!("Shutdown requested, closing connections and flushing pending forwards");
+            let _ = shutdown_tx.send(true);
+        })
+        .await?;
+    for forwarder in forwarders {
+        let _ = forwarder.await;
+    }
     Ok(())
 }
 
+async fn shutdown_signal() {
+    let ctrl_c = async {
+        tokio::signal::ctrl_c().await.expect("failed to install Ctrl+C handler");
+    };
+
+    #[cfg(unix)]
+    let terminate = async {
+        tokio::signal::unix::signal(tokio::signal::unix::SignalKind::terminate())
+            .expect("failed to install SIGTERM handler")
+            .recv()
+            .await;
+    };
+    #[cfg(not(unix))]
+    let terminate = std::future::pending::<()>();
+
+    tokio::select! {
+        _ = ctrl_c => {},
+        _ = terminate => {},
+    }
+}
+
 async fn health() -> Json<serde_json::Value> {
     Json(serde_json::json!({
         "status": "ok",
@@ -107,25 +646,491 @@ async fn health() -> Json<serde_json::Value> {
     }))
 }
 
+/// Readiness: 503 once the boxes.rthmn.com feed or Supabase has been silent for longer than
+/// its staleness threshold. Both clocks start at process start, so a fresh instance is ready
+/// until the thresholds elapse; Supabase is skipped in dry-run mode.
+async fn ready(State(s): State<Arc<AppState>>) -> impl IntoResponse {
+    let now_ms = Utc::now().timestamp_millis();
+    let to_rfc3339 = |ms: i64| chrono::DateTime::from_timestamp_millis(ms).map(|t| t.to_rfc3339());
+
+    let last_box_update = Some(s.last_box_update_ms.load(Ordering::Relaxed)).filter(|&ms| ms > 0);
+    let box_fresh = now_ms - last_box_update.unwrap_or(s.started_at_ms) <= s.ready_box_stale_ms;
+
+    let supabase_last_success = s.tracker.supabase().and_then(|sb| sb.last_success_ms());
+    let supabase_fresh = s.tracker.supabase().is_none()
+        || now_ms - supabase_last_success.unwrap_or(s.started_at_ms) <= s.ready_supabase_stale_ms;
+
+    let ready = box_fresh && supabase_fresh;
+    let code = if ready { StatusCode::OK } else { StatusCode::SERVICE_UNAVAILABLE };
+    (
+        code,
+        Json(serde_json::json!({
+            "status": if ready { "ready" } else { "stale" },
+            "boxFeed": {
+                "ok": box_fresh,
+                "lastBoxUpdateAt": last_box_update.and_then(to_rfc3339)
+            },
+            "supabase": {
+                "ok": supabase_fresh,
+                "enabled": s.tracker.supabase().is_some(),
+                "lastSuccessAt": supabase_last_success.and_then(to_rfc3339)
+            },
+            "timestamp": Utc::now().to_rfc3339()
+        })),
+    )
+}
+
 async fn status(State(s): State<Arc<AppState>>) -> Json<serde_json::Value> {
     let scanner = s.scanner.read().await;
-    let signals = *s.signals_sent.read().await;
+    let signals = s.metrics.signals_sent();
+    let dropped = s.metrics.signals_dropped();
     let active_signals = s.tracker.get_active_count().await;
     let active_by_pair = s.tracker.get_active_by_pair().await;
+    let breakdown = s.tracker.get_active_breakdown().await;
+    // Counters only ever grow; `since` marks the process start they count from
+    let mut outcomes = serde_json::to_value(s.metrics.outcomes()).unwrap_or_default();
+    outcomes["since"] = serde_json::json!(chrono::DateTime::from_timestamp_millis(s.started_at_ms).map(|t| t.to_rfc3339()));
+    let rejected = s.tracker.get_rejected_count();
+    let config = s.config().await.to_json();
 
     Json(serde_json::json!({
         "scanner": {
             "totalPaths": scanner.path_count(),
+            "indexedStartValues": scanner.start_index_len(),
+            "minBoxes": scanner.limits().min_boxes,
             "isInitialized": true
         },
+        "dryRun": s.dry_run,
+        "pairs": config["pairs"],
+        "directionFilter": s.generator.direction_filter().to_string(),
         "signalsSent": signals,
+        "signalsDropped": dropped,
+        "signalsCooledDown": s.metrics.signals_cooled_down(),
+        "boxUpdatesThrottled": s.metrics.box_updates_throttled(),
+        "boxUpdatesCoalesced": s.metrics.box_updates_coalesced(),
+        "forwardTargets": s.forward_targets.iter().map(ForwardTarget::status).collect::<Vec<_>>(),
+        "rejectedSignals": rejected,
+        "dedupFiltered": s.deduplicator.filter_counts(),
+        "outcomes": outcomes,
         "activeSignals": {
             "total": active_signals,
-            "byPair": active_by_pair
+            "byPair": active_by_pair,
+            "byLevel": breakdown.by_level,
+            "byType": breakdown.by_type
         }
     }))
 }
 
+async fn metrics(State(s): State<Arc<AppState>>) -> impl IntoResponse {
+    let active_by_pair = s.tracker.get_active_by_pair().await;
+    let body = s.metrics.render(&active_by_pair, s.tracker.get_rejected_count());
+    ([(header::CONTENT_TYPE, "text/plain; version=0.0.4")], body)
+}
+
+/// Recent box updates for a pair, oldest first.
+async fn history(Path(pair): Path<String>, State(s): State<Arc<AppState>>) -> Json<Vec<BoxData>> {
+    let history = s.history.read().await;
+    Json(
+        history
+            .get(&pair.to_uppercase())
+            .map(|h| h.iter().cloned().collect())
+            .unwrap_or_default(),
+    )
+}
+
+/// HTTP alternative to the `/ws` boxUpdate message for sources that can only POST.
+async fn box_update(
+    State(s): State<Arc<AppState>>,
+    headers: HeaderMap,
+    Json(body): Json<serde_json::Value>,
+) -> impl IntoResponse {
+    if !s.is_authorized(&headers) {
+        return (StatusCode::UNAUTHORIZED, Json(serde_json::json!({"error": "unauthorized"})));
+    }
+
+    let (Some(pair), Some(data)) = (body.get("pair").and_then(|v| v.as_str()), body.get("data")) else {
+        return (
+            StatusCode::BAD_REQUEST,
+            Json(serde_json::json!({"error": "expected {pair, data: {boxes, price, timestamp}}"})),
+        );
+    };
+
+    debug!("Received HTTP boxUpdate for {}", pair);
+    let Some(done) = submit_box_update(&s, pair, data).await else {
+        return (StatusCode::ACCEPTED, Json(serde_json::json!({"throttled": true})));
+    };
+    match done.await {
+        Ok(Ok(signals)) => (StatusCode::ACCEPTED, Json(serde_json::json!({"signals": signals}))),
+        Ok(Err(e)) => (StatusCode::BAD_REQUEST, Json(serde_json::json!({"error": e.to_string()}))),
+        Err(_) => (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            Json(serde_json::json!({"error": "box update processing failed"})),
+        ),
+    }
+}
+
+/// Partial override for `POST /api/admin/reload`; omitted fields keep their current value.
+#[derive(serde::Deserialize)]
+#[serde(rename_all = "camelCase", deny_unknown_fields)]
+struct ReloadRequest {
+    min_rr: Option<f64>,
+    /// Replaces all per-level overrides when present.
+    min_rr_by_level: Option<HashMap<u32, f64>>,
+    signal_cooldown_ms: Option<i64>,
+    pairs_allowlist: Option<Vec<String>>,
+    pairs_blocklist: Option<Vec<String>>,
+}
+
+/// Swaps in a new `RuntimeConfig`: with an empty body it is re-read from the environment,
+/// with `.env` values taking precedence so edits to the file apply; with a JSON body the
+/// given fields are applied over the current config.
+async fn admin_reload(
+    State(s): State<Arc<AppState>>,
+    headers: HeaderMap,
+    body: axum::body::Bytes,
+) -> impl IntoResponse {
+    if !s.is_authorized(&headers) {
+        return (StatusCode::UNAUTHORIZED, Json(serde_json::json!({"error": "unauthorized"})));
+    }
+
+    let (config, source) = if body.iter().all(u8::is_ascii_whitespace) {
+        let mut vars: HashMap<String, String> = env::vars().collect();
+        if let Ok(dotenv) = dotenvy::dotenv_iter() {
+            vars.extend(dotenv.flatten());
+        }
+        (RuntimeConfig::from_vars(&vars), "env")
+    } else {
+        let request: ReloadRequest = match serde_json::from_slice(&body) {
+            Ok(request) => request,
+            Err(e) => return (StatusCode::BAD_REQUEST, Json(serde_json::json!({"error": e.to_string()}))),
+        };
+        let mut config = (*s.config().await).clone();
+        if let Some(rr) = request.min_rr {
+            config.min_rr.default = rr;
+        }
+        if let Some(by_level) = request.min_rr_by_level {
+            config.min_rr.by_level = by_level;
+        }
+        if let Some(ms) = request.signal_cooldown_ms {
+            config.signal_cooldown_ms = ms;
+        }
+        if let Some(pairs) = request.pairs_allowlist {
+            config.pairs_allowlist = parse_pair_list(&pairs.join(","));
+        }
+        if let Some(pairs) = request.pairs_blocklist {
+            config.pairs_blocklist = parse_pair_list(&pairs.join(","));
+        }
+        (config, "body")
+    };
+
+    let effective = config.to_json();
+    *s.config.write().await = Arc::new(config);
+    info!("Runtime config reloaded from {}: {}", source, effective);
+    (StatusCode::OK, Json(serde_json::json!({"source": source, "config": effective})))
+}
+
+#[derive(serde::Deserialize)]
+struct DedupResetQuery {
+    pair: Option<String>,
+}
+
+/// Clears the deduplicator's state, for all pairs or just `?pair=`, so recorded box updates
+/// can be replayed without restarting.
+async fn admin_dedup_reset(
+    State(s): State<Arc<AppState>>,
+    headers: HeaderMap,
+    Query(query): Query<DedupResetQuery>,
+) -> impl IntoResponse {
+    if !s.is_authorized(&headers) {
+        return (StatusCode::UNAUTHORIZED, Json(serde_json::json!({"error": "unauthorized"})));
+    }
+
+    let pair = query.pair.map(|p| p.trim().to_uppercase()).filter(|p| !p.is_empty());
+    let cleared = match &pair {
+        Some(pair) => s.deduplicator.reset_pair(pair).await,
+        None => s.deduplicator.reset().await,
+    };
+    // Also forget the last box fingerprint, or a replay starting with the last update seen
+    // for a pair would be skipped as identical before it reaches dedup
+    let mut fingerprints = s.last_fingerprints.write().await;
+    let cleared = cleared
+        + match &pair {
+            Some(pair) => usize::from(fingerprints.remove(pair).is_some()),
+            None => std::mem::take(&mut *fingerprints).len(),
+        };
+    drop(fingerprints);
+    info!("Dedup state reset for {}: {} entries cleared", pair.as_deref().unwrap_or("all pairs"), cleared);
+    (StatusCode::OK, Json(serde_json::json!({"pair": pair, "cleared": cleared})))
+}
+
+/// Where a signal stands: its tracked state while still active, otherwise its Supabase
+/// row (settled, or active but not hydrated into this instance).
+async fn signal_lookup(
+    State(s): State<Arc<AppState>>,
+    Path(signal_id): Path<i64>,
+    headers: HeaderMap,
+) -> impl IntoResponse {
+    if !s.is_authorized(&headers) {
+        return (StatusCode::UNAUTHORIZED, Json(serde_json::json!({"error": "unauthorized"})));
+    }
+
+    if let Some(signal) = s.tracker.find_by_id(signal_id).await {
+        return (
+            StatusCode::OK,
+            Json(serde_json::json!({
+                "source": "memory",
+                "signal": {
+                    "id": signal.id,
+                    "pair": signal.pair,
+                    "signalType": signal.signal_type.to_string(),
+                    "level": signal.level,
+                    "entry": signal.entry,
+                    "stopLosses": signal.stop_losses,
+                    "targets": signal.targets,
+                    "riskReward": signal.risk_reward,
+                    "patternSequence": signal.pattern_sequence,
+                    "legsFilled": signal.legs_filled,
+                    "remainingFraction": signal.remaining_fraction(),
+                    "trailingStop": signal.trailing_stop(),
+                    "mfe": signal.mfe,
+                    "mae": signal.mae,
+                    "createdAt": chrono::DateTime::from_timestamp_millis(signal.created_at).map(|t| t.to_rfc3339()),
+                    "expiresAt": signal.expires_at.and_then(chrono::DateTime::from_timestamp_millis).map(|t| t.to_rfc3339()),
+                }
+            })),
+        );
+    }
+
+    let Some(supabase) = s.tracker.supabase() else {
+        return (
+            StatusCode::NOT_FOUND,
+            Json(serde_json::json!({"error": format!("signal {} is not active (Supabase is disabled)", signal_id)})),
+        );
+    };
+    match supabase.fetch_signal(signal_id).await {
+        Ok(Some(row)) => (StatusCode::OK, Json(serde_json::json!({"source": "supabase", "signal": row}))),
+        Ok(None) => (
+            StatusCode::NOT_FOUND,
+            Json(serde_json::json!({"error": format!("signal {} not found", signal_id)})),
+        ),
+        Err(e) => (StatusCode::BAD_GATEWAY, Json(serde_json::json!({"error": e.to_string()}))),
+    }
+}
+
+/// Queues an already-emitted signal onto every forward target again, for a downstream
+/// that missed it. The exact message is used while it is still cached, otherwise one
+/// rebuilt from the tracked signal or its Supabase row.
+async fn resend_signal(
+    State(s): State<Arc<AppState>>,
+    Path(signal_id): Path<i64>,
+    headers: HeaderMap,
+) -> impl IntoResponse {
+    if !s.is_authorized(&headers) {
+        return (StatusCode::UNAUTHORIZED, Json(serde_json::json!({"error": "unauthorized"})));
+    }
+
+    let cached = s.sent_signals.read().await.iter().find(|m| m.id == Some(signal_id)).cloned();
+    let (message, source) = if let Some(message) = cached {
+        (message, "cache")
+    } else if let Some(signal) = s.tracker.find_by_id(signal_id).await {
+        (signal.to_message(), "memory")
+    } else {
+        let Some(supabase) = s.tracker.supabase() else {
+            return (
+                StatusCode::NOT_FOUND,
+                Json(serde_json::json!({"error": format!("signal {} is not cached or active (Supabase is disabled)", signal_id)})),
+            );
+        };
+        match supabase.fetch_signal(signal_id).await {
+            Ok(Some(row)) => match serde_json::from_value::<SignalRow>(row) {
+                Ok(row) => (ActiveSignal::from_row(row).to_message(), "supabase"),
+                Err(e) => {
+                    return (
+                        StatusCode::BAD_GATEWAY,
+                        Json(serde_json::json!({"error": format!("signal {} row is unreadable: {}", signal_id, e)})),
+                    )
+                }
+            },
+            Ok(None) => {
+                return (
+                    StatusCode::NOT_FOUND,
+                    Json(serde_json::json!({"error": format!("signal {} not found", signal_id)})),
+                )
+            }
+            Err(e) => return (StatusCode::BAD_GATEWAY, Json(serde_json::json!({"error": e.to_string()}))),
+        }
+    };
+
+    let dropped = enqueue_forward(&s, &message);
+    let queued: Vec<&str> = s
+        .forward_targets
+        .iter()
+        .map(|t| t.url.as_str())
+        .filter(|url| !dropped.contains(url))
+        .collect();
+    info!("Resending {} {} L{} (id: {}, from {}) to {} target(s)", message.pair, message.signal_type, message.level, signal_id, source, queued.len());
+    let status = if queued.is_empty() && !dropped.is_empty() {
+        StatusCode::SERVICE_UNAVAILABLE
+    } else {
+        StatusCode::OK
+    };
+    (
+        status,
+        Json(serde_json::json!({"id": signal_id, "source": source, "queued": queued, "dropped": dropped})),
+    )
+}
+
+/// Force-closes an active signal at the pair's last-seen price.
+async fn close_signal(
+    State(s): State<Arc<AppState>>,
+    Path(signal_id): Path<i64>,
+    headers: HeaderMap,
+) -> impl IntoResponse {
+    if !s.is_authorized(&headers) {
+        return (StatusCode::UNAUTHORIZED, Json(serde_json::json!({"error": "unauthorized"})));
+    }
+
+    let Some(settlement) = s.tracker.close_by_id(signal_id).await else {
+        return (
+            StatusCode::NOT_FOUND,
+            Json(serde_json::json!({"error": format!("signal {} is not active", signal_id)})),
+        );
+    };
+    if settlement.signal.level == 1 {
+        s.deduplicator
+            .remove_l1_signal(&settlement.signal.pair, &settlement.signal.signal_type.to_string())
+            .await;
+    }
+
+    let signal = &settlement.signal;
+    (
+        StatusCode::OK,
+        Json(serde_json::json!({
+            "id": signal.id,
+            "pair": signal.pair,
+            "signalType": signal.signal_type.to_string(),
+            "level": signal.level,
+            "status": settlement.status,
+            "reason": settlement.reason,
+            "settledPrice": settlement.settled_price,
+            "mfe": signal.mfe,
+            "mae": signal.mae,
+        })),
+    )
+}
+
+#[derive(serde::Deserialize)]
+struct ExportQuery {
+    from: Option<String>,
+    to: Option<String>,
+}
+
+/// Rows requested from Supabase per page while streaming an export.
+const EXPORT_PAGE_SIZE: usize = 1000;
+
+/// Settled signals with `settled_at` in `[from, to)` as CSV. Pages are fetched from
+/// Supabase as the body is consumed, so large ranges are never held in memory at once.
+async fn export_signals(
+    State(s): State<Arc<AppState>>,
+    Query(query): Query<ExportQuery>,
+    headers: HeaderMap,
+) -> axum::response::Response {
+    if !s.is_authorized(&headers) {
+        return (StatusCode::UNAUTHORIZED, Json(serde_json::json!({"error": "unauthorized"}))).into_response();
+    }
+    let Some(supabase) = s.tracker.supabase().cloned() else {
+        return (
+            StatusCode::SERVICE_UNAVAILABLE,
+            Json(serde_json::json!({"error": "Supabase is disabled (dry run)"})),
+        )
+            .into_response();
+    };
+
+    let parse = |value: Option<String>, default: String| match value {
+        None => Ok(default),
+        Some(v) => chrono::DateTime::parse_from_rfc3339(&v)
+            .map(|t| t.to_utc().to_rfc3339())
+            .map_err(|e| format!("invalid timestamp {:?}: {}", v, e)),
+    };
+    let range = parse(query.from, "1970-01-01T00:00:00+00:00".to_string())
+        .and_then(|from| Ok((from, parse(query.to, Utc::now().to_rfc3339())?)));
+    let (from, to) = match range {
+        Ok(range) => range,
+        Err(e) => return (StatusCode::BAD_REQUEST, Json(serde_json::json!({"error": e}))).into_response(),
+    };
+
+    let header_chunk = futures_util::stream::once(async { Ok::<_, std::io::Error>(SETTLED_CSV_HEADER.to_string()) });
+    let rows = futures_util::stream::try_unfold(Some(0usize), move |offset| {
+        let (supabase, from, to) = (supabase.clone(), from.clone(), to.clone());
+        async move {
+            let Some(offset) = offset else {
+                return Ok(None);
+            };
+            let page = supabase
+                .fetch_settled_signals(&from, &to, offset, EXPORT_PAGE_SIZE)
+                .await
+                .map_err(std::io::Error::other)?;
+            let next = (page.len() == EXPORT_PAGE_SIZE).then_some(offset + page.len());
+            let chunk: String = page.iter().map(settled_csv_record).collect();
+            Ok(Some((chunk, next)))
+        }
+    });
+
+    (
+        [
+            (header::CONTENT_TYPE, "text/csv; charset=utf-8"),
+            (header::CONTENT_DISPOSITION, "attachment; filename=\"signals.csv\""),
+        ],
+        axum::body::Body::from_stream(header_chunk.chain(rows)),
+    )
+        .into_response()
+}
+
+#[derive(serde::Deserialize)]
+struct AnalyzeRequest {
+    pair: String,
+    boxes: Vec<signals_rthmn::types::Box>,
+    #[serde(default)]
+    price: f64,
+}
+
+/// Stateless detection for a box set: returns the signals it would generate without
+/// deduplicating, tracking or forwarding anything.
+/// Largest box set `/api/analyze` accepts. boxes.rthmn.com sends 38 per pair; detection
+/// cost grows with the set, so anything far beyond that is refused rather than scanned.
+const MAX_ANALYZE_BOXES: usize = 64;
+
+async fn analyze(
+    State(s): State<Arc<AppState>>,
+    headers: HeaderMap,
+    Json(req): Json<AnalyzeRequest>,
+) -> impl IntoResponse {
+    if !s.is_authorized(&headers) {
+        return (StatusCode::UNAUTHORIZED, Json(serde_json::json!({"error": "unauthorized"})));
+    }
+    if req.boxes.len() > MAX_ANALYZE_BOXES {
+        return (
+            StatusCode::PAYLOAD_TOO_LARGE,
+            Json(serde_json::json!({"error": format!("{} boxes, at most {} allowed", req.boxes.len(), MAX_ANALYZE_BOXES)})),
+        );
+    }
+    if let Some((idx, e)) = req.boxes.iter().enumerate().find_map(|(i, b)| b.validate().err().map(|e| (i, e))) {
+        return (
+            StatusCode::BAD_REQUEST,
+            Json(serde_json::json!({"error": format!("box {} invalid: {}", idx, e)})),
+        );
+    }
+
+    let pair = req.pair.to_uppercase();
+    let patterns = s.scanner.read().await.detect_patterns(&pair, &req.boxes);
+    let atr = s.volatility.atr(&pair).await;
+    let signals = s.generator.generate_signals_with_atr(&pair, &patterns, &req.boxes, req.price, atr);
+    (
+        StatusCode::OK,
+        Json(serde_json::json!({"patterns": patterns.len(), "matches": patterns, "signals": signals})),
+    )
+}
+
 async fn ws_handler(ws: WebSocketUpgrade, State(state): State<Arc<AppState>>) -> impl IntoResponse {
     info!("WebSocket upgrade request received");
     ws.on_upgrade(|socket| {
@@ -139,42 +1144,55 @@ async fn handle_socket(socket: WebSocket, state: Arc<AppState>) {
     info!("WebSocket client connected (boxes.rthmn.com)");
 
     let auth_msg = rmp_serde::to_vec(&serde_json::json!({"type": "authRequired"})).unwrap();
-    let _ = sender.send(Message::Binary(auth_msg.into())).await;
+    let _ = sender.send(Message::Binary(auth_msg)).await;
 
     let mut authenticated = false;
-    let mut heartbeat_interval = tokio::time::interval(tokio::time::Duration::from_secs(30));
-    let mut last_heartbeat = std::time::Instant::now();
+    let mut heartbeat_interval = tokio::time::interval(state.ws_ping_interval);
+    // Any inbound frame (data, heartbeat, pong) counts as the producer being alive
+    let mut last_seen = std::time::Instant::now();
+    let mut shutdown = state.shutdown.clone();
 
     loop {
         tokio::select! {
+            _ = shutdown.changed() => break,
             msg = receiver.next() => {
+                if matches!(msg, Some(Ok(_))) {
+                    last_seen = std::time::Instant::now();
+                }
                 match msg {
                     Some(Ok(Message::Binary(data))) => {
                         if let Ok(m) = rmp_serde::from_slice::<serde_json::Value>(&data) {
                             match m.get("type").and_then(|v| v.as_str()) {
                                 Some("auth") => {
+                                    if !token_matches(ws_auth_token(&m), &state.ws_token) {
+                                        warn!("WebSocket auth rejected: invalid token");
+                                        let _ = sender
+                                            .send(Message::Close(Some(CloseFrame {
+                                                code: close_code::POLICY,
+                                                reason: "invalid token".into(),
+                                            })))
+                                            .await;
+                                        break;
+                                    }
                                     authenticated = true;
                                     let welcome =
                                         rmp_serde::to_vec(&serde_json::json!({"type": "welcome"})).unwrap();
-                                    let _ = sender.send(Message::Binary(welcome.into())).await;
+                                    let _ = sender.send(Message::Binary(welcome)).await;
                                     info!("boxes.rthmn.com authenticated");
-                                    last_heartbeat = std::time::Instant::now();
                                 }
                                 Some("boxUpdate") if authenticated => {
                                     if let (Some(pair), Some(data)) =
                                         (m.get("pair").and_then(|v| v.as_str()), m.get("data"))
                                     {
                                         debug!("Received boxUpdate for {}", pair);
-                                        process_box_update(&state, pair, data).await;
-                                        last_heartbeat = std::time::Instant::now();
+                                        // The result isn't awaited: the next frame is read while this
+                                        // pair is processed, and other pairs run alongside it
+                                        drop(submit_box_update(&state, pair, data).await);
                                     }
                                 }
-                                Some("heartbeat") => {
-                                    last_heartbeat = std::time::Instant::now();
-                                    if authenticated {
-                                        let heartbeat_response = rmp_serde::to_vec(&serde_json::json!({"type": "heartbeat"})).unwrap();
-                                        let _ = sender.send(Message::Binary(heartbeat_response.into())).await;
-                                    }
+                                Some("heartbeat") if authenticated => {
+                                    let heartbeat_response = rmp_serde::to_vec(&serde_json::json!({"type": "heartbeat"})).unwrap();
+                                    let _ = sender.send(Message::Binary(heartbeat_response)).await;
                                 }
                                 _ => {}
                             }
@@ -188,160 +1206,399 @@ async fn handle_socket(socket: WebSocket, state: Arc<AppState>) {
                     _ => {}
                 }
             }
-            _ = heartbeat_interval.tick(), if authenticated => {
-                if last_heartbeat.elapsed() > tokio::time::Duration::from_secs(90) {
-                    warn!("No heartbeat from boxes.rthmn.com for 90s, closing connection");
+            _ = heartbeat_interval.tick() => {
+                if last_seen.elapsed() > state.ws_idle_timeout {
+                    warn!(
+                        "Stale WebSocket connection: nothing received from boxes.rthmn.com for {:?}, closing",
+                        state.ws_idle_timeout
+                    );
+                    let _ = sender.send(Message::Close(None)).await;
                     break;
                 }
-                let heartbeat = rmp_serde::to_vec(&serde_json::json!({"type": "heartbeat"})).unwrap();
-                if sender.send(Message::Binary(heartbeat.into())).await.is_err() {
+                if sender.send(Message::Ping(Vec::new())).await.is_err() {
                     break;
                 }
+                if authenticated {
+                    let heartbeat = rmp_serde::to_vec(&serde_json::json!({"type": "heartbeat"})).unwrap();
+                    if sender.send(Message::Binary(heartbeat)).await.is_err() {
+                        break;
+                    }
+                }
             }
         }
     }
     info!("WebSocket client disconnected");
 }
 
-async fn main_server_forwarder(state: Arc<AppState>, token: String, mut signal_rx: mpsc::Receiver<SignalMessage>) {
-    let client = reqwest::Client::new();
-    let url = format!("{}/signals/raw", state.main_server_url.trim_end_matches('/'));
-    
-    while let Some(signal) = signal_rx.recv().await {
-        let result = client
-            .post(&url)
-            .header("Authorization", format!("Bearer {}", token))
-            .header("Content-Type", "application/json")
-            .json(&signal)
-            .send()
-            .await;
+/// Signals waiting to be retried are capped so a long outage can't grow memory unbounded.
+const MAX_RETRY_QUEUE: usize = 1000;
+const SHUTDOWN_FLUSH_TIMEOUT: tokio::time::Duration = tokio::time::Duration::from_secs(10);
+
+struct PendingForward {
+    signal: SignalMessage,
+    attempt: u32,
+    retry_at: tokio::time::Instant,
+}
+
+/// Backoff before retry `attempt`: `min(2^attempt * 100ms, 30s)`.
+fn forward_backoff(attempt: u32) -> tokio::time::Duration {
+    let millis = 100u64.saturating_mul(1u64 << attempt.min(20));
+    tokio::time::Duration::from_millis(millis).min(tokio::time::Duration::from_secs(30))
+}
+
+async fn ws_signals_handler(ws: WebSocketUpgrade, State(state): State<Arc<AppState>>) -> impl IntoResponse {
+    ws.on_upgrade(|socket| handle_signals_socket(socket, state))
+}
+
+/// Streams generated signals to a dashboard client, starting with a snapshot of the
+/// active ones so a reconnecting client converges on the current state. A client that
+/// falls behind the broadcast buffer is disconnected rather than allowed to slow the
+/// producer.
+async fn handle_signals_socket(socket: WebSocket, state: Arc<AppState>) {
+    let (mut sender, mut receiver) = socket.split();
+    // Subscribe before taking the snapshot so nothing generated in between is missed; a
+    // signal can then arrive both in the snapshot and live, which clients dedupe by id
+    let mut signals = state.signal_broadcast.subscribe();
+    info!("Signal subscriber connected ({} total)", state.signal_broadcast.receiver_count());
+    let mut shutdown = state.shutdown.clone();
+
+    let active = state.tracker.active_messages().await;
+    match rmp_serde::to_vec_named(&SignalFeedMessage::Snapshot { signals: &active }) {
+        Ok(payload) => {
+            if sender.send(Message::Binary(payload)).await.is_err() {
+                return;
+            }
+        }
+        Err(e) => warn!("Failed to encode signal snapshot: {}", e),
+    }
 
-        match result {
-            Ok(resp) if resp.status().is_success() => {
-                *state.signals_sent.write().await += 1;
-                info!("Forwarded raw signal to main server: {} {} L{}", signal.pair, signal.signal_type, signal.level);
+    loop {
+        tokio::select! {
+            _ = shutdown.changed() => break,
+            msg = receiver.next() => {
+                match msg {
+                    Some(Ok(Message::Close(_))) | None => break,
+                    Some(Err(e)) => {
+                        warn!("Signal subscriber WebSocket error: {}", e);
+                        break;
+                    }
+                    _ => {}
+                }
+            }
+            signal = signals.recv() => {
+                match signal {
+                    Ok(signal) => {
+                        let Ok(payload) = rmp_serde::to_vec_named(&SignalFeedMessage::Signal { data: &signal }) else {
+                            continue;
+                        };
+                        if sender.send(Message::Binary(payload)).await.is_err() {
+                            break;
+                        }
+                    }
+                    Err(broadcast::error::RecvError::Lagged(skipped)) => {
+                        warn!("Signal subscriber lagged by {} signal(s), disconnecting", skipped);
+                        break;
+                    }
+                    Err(broadcast::error::RecvError::Closed) => break,
+                }
             }
-            Ok(resp) => warn!("Failed to forward raw signal to main server: {}", resp.status()),
-            Err(e) => warn!("Failed to forward raw signal to main server: {}", e),
         }
     }
+    info!("Signal subscriber disconnected");
 }
 
-async fn process_box_update(state: &Arc<AppState>, pair: &str, data: &serde_json::Value) {
-    let boxes: Vec<signals_rthmn::types::Box> = data
-        .get("boxes")
-        .and_then(|v| serde_json::from_value(v.clone()).ok())
-        .unwrap_or_default();
-    let price = data.get("price").and_then(|v| v.as_f64()).unwrap_or(0.0);
+async fn main_server_forwarder(state: Arc<AppState>, target_idx: usize, mut signal_rx: mpsc::Receiver<SignalMessage>) {
+    let token = &state.auth_token;
+    let client = &state.http;
+    let target = &state.forward_targets[target_idx];
+    let url = target.url.as_str();
+    let mut retry_queue: Vec<PendingForward> = Vec::new();
+    let mut shutdown = state.shutdown.clone();
 
-    if boxes.is_empty() {
-        return;
-    }
+    loop {
+        let next_retry = retry_queue
+            .iter()
+            .enumerate()
+            .min_by_key(|(_, p)| p.retry_at)
+            .map(|(idx, p)| (idx, p.retry_at));
+        let retry_sleep = tokio::time::sleep_until(next_retry.map_or_else(tokio::time::Instant::now, |(_, at)| at));
 
-    signals_rthmn::instruments::update_instrument_price(pair, price);
+        let (signal, attempt) = tokio::select! {
+            received = signal_rx.recv() => match received {
+                Some(signal) => (signal, 0),
+                None => break,
+            },
+            _ = retry_sleep, if next_retry.is_some() => {
+                let pending = retry_queue.swap_remove(next_retry.map_or(0, |(idx, _)| idx));
+                (pending.signal, pending.attempt)
+            }
+            _ = shutdown.changed() => break,
+        };
 
-    // Step 1: Check existing active signals for price hits (stop loss or targets)
-    let pair_upper = pair.to_uppercase();
-    let settlements = state.tracker.check_price(&pair_upper, price).await;
-    if !settlements.is_empty() {
-        info!(
-            "{} @ ${:.5} - {} signal(s) settled",
-            pair,
-            price,
-            settlements.len()
-        );
-        
-        for settlement in &settlements {
-            if settlement.signal.level == 1 {
-                state
-                    .deduplicator
-                    .remove_l1_signal(pair, &settlement.signal.signal_type.to_string())
-                    .await;
+        if state.dry_run {
+            info!("DRY RUN: would forward raw signal to {}: {} {} L{}", url, signal.pair, signal.signal_type, signal.level);
+            continue;
+        }
+
+        match forward_raw_signal(client, url, token, state.forward_format, &signal).await {
+            Ok(()) => {
+                state.record_forward_sent(target);
+                info!("Forwarded raw signal to {}: {} {} L{}", url, signal.pair, signal.signal_type, signal.level);
+                continue;
             }
+            Err(e) => {
+                target.failed.fetch_add(1, Ordering::Relaxed);
+                warn!("Failed to forward raw signal to {} (attempt {}): {}", url, attempt + 1, e);
+            }
+        }
+
+        let attempt = attempt + 1;
+        if attempt >= state.forward_max_attempts || retry_queue.len() >= MAX_RETRY_QUEUE {
+            state.record_forward_dropped(target);
+            warn!(
+                "Dropping raw signal for {} after {} attempt(s): {} {} L{}",
+                url, attempt, signal.pair, signal.signal_type, signal.level
+            );
+            continue;
         }
+
+        let delay = forward_backoff(attempt);
+        debug!("Retrying {} {} L{} in {:?}", signal.pair, signal.signal_type, signal.level, delay);
+        retry_queue.push(PendingForward {
+            signal,
+            attempt,
+            retry_at: tokio::time::Instant::now() + delay,
+        });
     }
 
-    // Step 2: Detect new patterns and generate signals
-    let all_patterns = state.scanner.read().await.detect_patterns(pair, &boxes);
-    if all_patterns.is_empty() {
-        let (point, _) = signals_rthmn::instruments::get_instrument_config(pair);
-        let integer_values: Vec<i32> = boxes.iter().map(|b| (b.value / point).round() as i32).collect();
-        debug!("{}: No patterns detected. Box integer values: {:?}", pair, integer_values);
-        return;
+    // Shutdown: no new signals are accepted; everything queued or awaiting retry gets
+    // one more attempt until the flush deadline.
+    signal_rx.close();
+    let mut pending: Vec<SignalMessage> = retry_queue.into_iter().map(|p| p.signal).collect();
+    while let Some(signal) = signal_rx.recv().await {
+        pending.push(signal);
+    }
+
+    let deadline = tokio::time::Instant::now() + SHUTDOWN_FLUSH_TIMEOUT;
+    let (mut flushed, mut dropped) = (0usize, 0usize);
+    for signal in pending {
+        let sent = state.dry_run
+            || matches!(
+                tokio::time::timeout_at(deadline, forward_raw_signal(client, url, token, state.forward_format, &signal)).await,
+                Ok(Ok(()))
+            );
+        if sent {
+            if !state.dry_run {
+                state.record_forward_sent(target);
+            }
+            flushed += 1;
+        } else {
+            state.record_forward_dropped(target);
+            dropped += 1;
+        }
     }
-    
-    info!("{}: Detected {} pattern(s)", pair, all_patterns.len());
+    info!("Shutdown: flushed {} pending signal(s) to {}, dropped {}", flushed, url, dropped);
+}
 
-    let timestamp_ms = chrono::Utc::now().timestamp_millis();
+async fn forward_raw_signal(
+    client: &reqwest::Client,
+    url: &str,
+    token: &str,
+    format: ForwardFormat,
+    signal: &SignalMessage,
+) -> Result<(), String> {
+    let body = format.encode(signal)?;
+    let resp = client
+        .post(url)
+        .header("Authorization", format!("Bearer {}", token))
+        .header("Content-Type", format.content_type())
+        .body(body)
+        .send()
+        .await
+        .map_err(|e| if e.is_timeout() { format!("timed out: {}", e) } else { e.to_string() })?;
+    if resp.status().is_success() {
+        Ok(())
+    } else {
+        Err(resp.status().to_string())
+    }
+}
 
-    let mut filtered_patterns = Vec::new();
-    for pattern in &all_patterns {
-        if !state.deduplicator.should_filter_pattern(pair, pattern, &boxes, timestamp_ms).await {
-            filtered_patterns.push(pattern.clone());
+/// Queues `signal` on every forward target and returns the URLs whose queue was full.
+/// try_send so a target whose queue is backed up loses signals rather than stalling the
+/// pipeline for every other target.
+fn enqueue_forward<'a>(state: &'a AppState, signal: &SignalMessage) -> Vec<&'a str> {
+    let mut dropped = Vec::new();
+    for target in &state.forward_targets {
+        if target.tx.try_send(signal.clone()).is_err() {
+            state.record_forward_dropped(target);
+            warn!("Forward queue for {} is full, dropping {} {} L{}", target.url, signal.pair, signal.signal_type, signal.level);
+            dropped.push(target.url.as_str());
         }
     }
+    dropped
+}
 
-    if filtered_patterns.is_empty() {
-        debug!("{}: All {} pattern(s) filtered by deduplicator", pair, all_patterns.len());
+async fn record_history(state: &AppState, pair: &str, update: &ParsedUpdate) {
+    if state.history_size == 0 {
         return;
     }
-    
-    info!("{}: {} pattern(s) passed deduplication", pair, filtered_patterns.len());
+    let mut history = state.history.write().await;
+    let entries = history.entry(pair.to_uppercase()).or_default();
+    if entries.len() >= state.history_size {
+        entries.pop_front();
+    }
+    entries.push_back(BoxData {
+        pair: pair.to_uppercase(),
+        boxes: update.boxes.clone(),
+        price: update.price,
+        tick_high: update.tick_high,
+        tick_low: update.tick_low,
+        timestamp: update.timestamp.unwrap_or_else(Utc::now).to_rfc3339(),
+    });
+}
 
-    let unique_patterns = state.deduplicator.remove_subset_duplicates(filtered_patterns);
-    info!("{} @ ${:.2} - {} pattern(s) after deduplication", pair, price, unique_patterns.len());
+/// A box update held back by `BOX_UPDATE_RATE` until its pair has a token.
+struct ParkedUpdate {
+    pair: String,
+    data: serde_json::Value,
+    /// Parked updates this one replaced.
+    superseded: u64,
+}
 
-    for signal in state.generator.generate_signals(pair, &unique_patterns, &boxes, price) {
-        if signal.entry.is_none() || signal.stop_losses.is_empty() || signal.targets.is_empty() {
-            continue;
+impl ParkedUpdate {
+    /// Swaps in a newer update for the same pair, counting it as throttled and coalesced.
+    fn supersede(&mut self, data: &serde_json::Value, metrics: &Metrics) {
+        self.data = data.clone();
+        self.superseded += 1;
+        metrics.record_throttled();
+        metrics.record_coalesced();
+    }
+}
+
+/// Queues a box update behind any still pending for the same pair; see `PairExecutor`.
+/// Over `BOX_UPDATE_RATE` it is parked instead and `None` returned: a newer update for
+/// the pair replaces it, and whichever is latest is queued once the pair has a token.
+async fn submit_box_update(
+    state: &Arc<AppState>,
+    pair: &str,
+    data: &serde_json::Value,
+) -> Option<tokio::sync::oneshot::Receiver<Result<usize, BoxUpdateError>>> {
+    // Throttled updates count too: the feed is alive even when they're parked
+    state.last_box_update_ms.store(Utc::now().timestamp_millis(), Ordering::Relaxed);
+    if let Some(limiter) = &state.box_rate_limiter {
+        let key = pair.to_uppercase();
+        // While one is parked, newer updates must queue behind it rather than overtake it.
+        // The parked map is only locked around lookups and inserts, never across the
+        // limiter, so pairs don't serialize on it.
+        if let Some(waiting) = state.parked_updates.write().await.get_mut(&key) {
+            waiting.supersede(data, &state.metrics);
+            return None;
+        }
+        let acquired = limiter.try_acquire(&key, std::time::Instant::now()).await;
+        let mut parked = state.parked_updates.write().await;
+        // Another update for the pair may have been parked while the token was checked
+        if let Some(waiting) = parked.get_mut(&key) {
+            waiting.supersede(data, &state.metrics);
+            return None;
         }
+        if let Err(wait) = acquired {
+            debug!("{}: over {}/s, parking box update for {:?}", key, limiter.rate(), wait);
+            parked.insert(key.clone(), ParkedUpdate { pair: pair.to_string(), data: data.clone(), superseded: 0 });
+            state.metrics.record_throttled();
+            tokio::spawn(release_parked_update(Arc::clone(state), key, wait));
+            return None;
+        }
+    }
+    Some(queue_box_update(state, pair, data.clone()).await)
+}
 
-        let signal_type_enum = match signal.signal_type.as_str() {
-            "LONG" => SignalType::LONG,
-            _ => SignalType::SHORT,
-        };
-        
-        if state.deduplicator.should_filter_structural_boxes(pair, &signal.box_details, signal_type_enum, signal.level).await {
-            info!("FILTERED: {} {} L{} - duplicate signal (structural boxes unchanged)", signal.pair, signal.signal_type, signal.level);
-            continue;
+/// Waits for the pair's next token, then queues its latest parked update.
+async fn release_parked_update(state: Arc<AppState>, key: String, mut wait: std::time::Duration) {
+    let Some(limiter) = &state.box_rate_limiter else {
+        return;
+    };
+    loop {
+        tokio::time::sleep(wait).await;
+        match limiter.try_acquire(&key, std::time::Instant::now()).await {
+            Ok(()) => break,
+            Err(next) => wait = next,
         }
+    }
+    let Some(parked) = state.parked_updates.write().await.remove(&key) else {
+        return;
+    };
+    if parked.superseded > 0 {
+        info!("{}: coalesced {} box update(s) over the {}/s limit, processing the latest", key, parked.superseded, limiter.rate());
+    }
+    drop(queue_box_update(&state, &parked.pair, parked.data).await);
+}
 
-        let entry = signal.entry.unwrap_or(0.0);
-        let stop_losses = signal.stop_losses.clone();
-        let targets = signal.targets.clone();
-        let first_stop = stop_losses.first().map(|sl| sl.price).unwrap_or(0.0);
-        let final_target = targets.last().map(|t| t.price).unwrap_or(0.0);
+async fn queue_box_update(
+    state: &Arc<AppState>,
+    pair: &str,
+    data: serde_json::Value,
+) -> tokio::sync::oneshot::Receiver<Result<usize, BoxUpdateError>> {
+    let job_state = Arc::clone(state);
+    let job_pair = pair.to_string();
+    state
+        .pair_executor
+        .submit(pair, async move { process_box_update(&job_state, &job_pair, &data).await })
+        .await
+}
 
-        info!("SIGNAL: {} {} L{} {:?}", signal.pair, signal.signal_type, signal.level, signal.pattern_sequence);
-        for (i, b) in signal.box_details.iter().enumerate() {
-            info!("  Box {}: {} H:{:.5} L:{:.5}", i, b.integer_value, b.high, b.low);
-        }
-        let final_rr = signal.risk_reward.last().copied().unwrap_or(0.0);
-        let stop_prices: Vec<f64> = stop_losses.iter().map(|sl| sl.price).collect();
-        let target_prices: Vec<f64> = targets.iter().map(|t| t.price).collect();
-        info!("  E:{:.5} S:{:?} (first: {:.5}) T:{:?} (final: {:.5}) R:R:{:?} (final: {:.2})", entry, stop_prices, first_stop, target_prices, final_target, signal.risk_reward, final_rr);
-
-        let pair_upper = pair.to_uppercase();
-
-        let active_signal = ActiveSignal {
-            id: 0, // Will be set after Supabase insert
-            pair: pair_upper,
-            signal_type: signal_type_enum,
-            level: signal.level,
-            entry,
-            stop_losses,
-            targets,
-            risk_reward: signal.risk_reward.clone(),
-            pattern_sequence: signal.pattern_sequence.clone(),
-            box_details: signal.box_details.clone(),
-            created_at: chrono::Utc::now().timestamp_millis(),
-        };
+/// Runs one box update through the signal `Pipeline`, then broadcasts and forwards what it
+/// emitted. Shared by the WebSocket and HTTP ingestion paths; returns the number of signals
+/// emitted, or why `data` was rejected (already logged).
+async fn process_box_update(
+    state: &Arc<AppState>,
+    pair: &str,
+    data: &serde_json::Value,
+) -> Result<usize, BoxUpdateError> {
+    let update = parse_box_update(data).inspect_err(|e| warn!("{}: dropping box update, {}", pair, e))?;
+    if update.boxes.is_empty() {
+        return Ok(0);
+    }
+    let (boxes, price) = (&update.boxes, update.price);
 
-        let signal_id = state.tracker.add_signal(active_signal).await;
-        let signal_with_id = signals_rthmn::types::SignalMessage {
-            id: Some(signal_id),
-            ..signal
-        };
-        let _ = state.signal_tx.send(signal_with_id).await;
+    record_history(state, pair, &update).await;
+
+    let pair_upper = pair.to_uppercase();
+    let trace = state.debug_pair.as_deref() == Some(pair_upper.as_str());
+    if trace {
+        info!(
+            pair = %pair_upper,
+            price,
+            tick_low = ?update.tick_low,
+            tick_high = ?update.tick_high,
+            values = ?box_integer_values(pair, boxes),
+            "TRACE box update"
+        );
+    }
+
+    let config = state.config().await;
+    let scanner = state.scanner.read().await;
+    let pipeline = Pipeline {
+        scanner: &scanner,
+        generator: &state.generator,
+        volatility: &state.volatility,
+        tracker: &state.tracker,
+        deduplicator: &state.deduplicator,
+        metrics: &state.metrics,
+        config: &config,
+        last_fingerprints: &state.last_fingerprints,
+        last_fired_at: &state.last_fired_at,
+        signal_ttl_ms: state.signal_ttl_ms,
+        trace,
+    };
+    let processed = pipeline.run(pair, &update, Utc::now().timestamp_millis()).await;
+    drop(scanner);
+
+    let emitted = processed.signals.len();
+    for signal in processed.signals {
+        // No subscribers is the normal case, so a send error is ignored
+        let _ = state.signal_broadcast.send(signal.clone());
+        enqueue_forward(state, &signal);
+        state.remember_sent(signal).await;
     }
+    Ok(emitted)
 }
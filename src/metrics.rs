@@ -0,0 +1,171 @@
+use serde::Serialize;
+use std::collections::{BTreeMap, HashMap};
+use std::fmt::Write;
+use std::sync::atomic::{AtomicU64, Ordering};
+
+/// Histogram buckets for signal levels; trade rules currently cover L1-L6.
+const LEVEL_BUCKETS: [u32; 6] = [1, 2, 3, 4, 5, 6];
+
+/// Process-lifetime counters, rendered in Prometheus text exposition format.
+#[derive(Default)]
+pub struct Metrics {
+    signals_sent: AtomicU64,
+    signals_dropped: AtomicU64,
+    signals_cooled_down: AtomicU64,
+    box_updates_throttled: AtomicU64,
+    box_updates_coalesced: AtomicU64,
+    level_counts: [AtomicU64; LEVEL_BUCKETS.len() + 1],
+    level_sum: AtomicU64,
+    /// Final settlements by level bucket, as in `level_counts`.
+    wins: [AtomicU64; LEVEL_BUCKETS.len() + 1],
+    losses: [AtomicU64; LEVEL_BUCKETS.len() + 1],
+}
+
+/// Wins and losses among final settlements since process start.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct OutcomeCounts {
+    pub wins: u64,
+    pub losses: u64,
+    /// `wins / (wins + losses)`; `None` until something has won or lost.
+    pub win_rate: Option<f64>,
+}
+
+impl OutcomeCounts {
+    fn new(wins: u64, losses: u64) -> Self {
+        let decided = wins + losses;
+        Self { wins, losses, win_rate: (decided > 0).then(|| wins as f64 / decided as f64) }
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct OutcomeStats {
+    #[serde(flatten)]
+    pub total: OutcomeCounts,
+    /// Keyed `"1"`..`"6"`, with `"7+"` for anything deeper; levels with no outcomes are
+    /// left out.
+    pub by_level: BTreeMap<String, OutcomeCounts>,
+}
+
+fn level_bucket(level: u32) -> usize {
+    LEVEL_BUCKETS.iter().position(|&le| level <= le).unwrap_or(LEVEL_BUCKETS.len())
+}
+
+impl Metrics {
+    pub fn record_sent(&self) {
+        self.signals_sent.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn record_dropped(&self) {
+        self.signals_dropped.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn record_cooled_down(&self) {
+        self.signals_cooled_down.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// A box update arrived over its pair's rate and was parked.
+    pub fn record_throttled(&self) {
+        self.box_updates_throttled.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// A parked box update was replaced by a newer one and never processed.
+    pub fn record_coalesced(&self) {
+        self.box_updates_coalesced.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn record_level(&self, level: u32) {
+        let bucket = level_bucket(level);
+        self.level_counts[bucket].fetch_add(1, Ordering::Relaxed);
+        self.level_sum.fetch_add(level as u64, Ordering::Relaxed);
+    }
+
+    /// Counts a final settlement: `success` is a win, `failed` a loss. Partial, trailed,
+    /// expired and manual settlements count as neither (backtest reports count `trailed`
+    /// as a win).
+    pub fn record_outcome(&self, level: u32, status: &str) {
+        let counters = match status {
+            "success" => &self.wins,
+            "failed" => &self.losses,
+            _ => return,
+        };
+        counters[level_bucket(level)].fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn outcomes(&self) -> OutcomeStats {
+        let mut stats = OutcomeStats::default();
+        let (mut wins, mut losses) = (0, 0);
+        for bucket in 0..=LEVEL_BUCKETS.len() {
+            let (w, l) = (self.wins[bucket].load(Ordering::Relaxed), self.losses[bucket].load(Ordering::Relaxed));
+            if w + l == 0 {
+                continue;
+            }
+            let key = LEVEL_BUCKETS.get(bucket).map_or_else(|| format!("{}+", LEVEL_BUCKETS.len() + 1), |le| le.to_string());
+            stats.by_level.insert(key, OutcomeCounts::new(w, l));
+            wins += w;
+            losses += l;
+        }
+        stats.total = OutcomeCounts::new(wins, losses);
+        stats
+    }
+
+    pub fn signals_sent(&self) -> u64 {
+        self.signals_sent.load(Ordering::Relaxed)
+    }
+
+    pub fn signals_dropped(&self) -> u64 {
+        self.signals_dropped.load(Ordering::Relaxed)
+    }
+
+    pub fn signals_cooled_down(&self) -> u64 {
+        self.signals_cooled_down.load(Ordering::Relaxed)
+    }
+
+    pub fn box_updates_throttled(&self) -> u64 {
+        self.box_updates_throttled.load(Ordering::Relaxed)
+    }
+
+    pub fn box_updates_coalesced(&self) -> u64 {
+        self.box_updates_coalesced.load(Ordering::Relaxed)
+    }
+
+    pub fn render(&self, active_by_pair: &HashMap<String, usize>, rejected: u64) -> String {
+        let mut out = String::new();
+
+        write_counter(&mut out, "signals_sent_total", "Signals forwarded to the main server.", self.signals_sent());
+        write_counter(&mut out, "signals_dropped_total", "Signals dropped after exhausting forward retries.", self.signals_dropped());
+        write_counter(&mut out, "signals_cooled_down_total", "Signals suppressed by the per-pair signal cooldown.", self.signals_cooled_down());
+        write_counter(&mut out, "box_updates_throttled_total", "Box updates over their pair's rate limit.", self.box_updates_throttled());
+        write_counter(&mut out, "box_updates_coalesced_total", "Throttled box updates superseded by a newer one and never processed.", self.box_updates_coalesced());
+        write_counter(&mut out, "signals_rejected_total", "Signals rejected by the per-pair active cap.", rejected);
+
+        let _ = writeln!(out, "# HELP active_signals Signals currently tracked for settlement.");
+        let _ = writeln!(out, "# TYPE active_signals gauge");
+        let mut pairs: Vec<_> = active_by_pair.iter().collect();
+        pairs.sort();
+        for (pair, count) in pairs {
+            let _ = writeln!(out, "active_signals{{pair=\"{}\"}} {}", pair, count);
+        }
+
+        let _ = writeln!(out, "# HELP signal_level Level of generated signals.");
+        let _ = writeln!(out, "# TYPE signal_level histogram");
+        let mut cumulative = 0;
+        for (idx, le) in LEVEL_BUCKETS.iter().enumerate() {
+            cumulative += self.level_counts[idx].load(Ordering::Relaxed);
+            let _ = writeln!(out, "signal_level_bucket{{le=\"{}\"}} {}", le, cumulative);
+        }
+        cumulative += self.level_counts[LEVEL_BUCKETS.len()].load(Ordering::Relaxed);
+        let _ = writeln!(out, "signal_level_bucket{{le=\"+Inf\"}} {}", cumulative);
+        let _ = writeln!(out, "signal_level_sum {}", self.level_sum.load(Ordering::Relaxed));
+        let _ = writeln!(out, "signal_level_count {}", cumulative);
+
+        out
+    }
+}
+
+fn write_counter(out: &mut String, name: &str, help: &str, value: u64) {
+    let _ = writeln!(out, "# HELP {} {}", name, help);
+    let _ = writeln!(out, "# TYPE {} counter", name);
+    let _ = writeln!(out, "{} {}", name, value);
+}
@@ -1,5 +1,7 @@
 use lazy_static::lazy_static;
-use std::collections::HashMap;
+use serde::Deserialize;
+use std::collections::{BTreeSet, HashMap};
+use std::path::Path;
 
 lazy_static! {
     pub static ref BOXES: HashMap<i32, Vec<Vec<i32>>> = {
@@ -577,3 +579,103 @@ pub const STARTING_POINTS: &[i32] = &[
     // 32, 28, 24, 21, 18, 14, 12, 10,
 ];
 
+/// A pattern graph: traversal starts and, per box value, the patterns that can follow it.
+/// Values without an entry in `boxes` end a path.
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct PatternGraph {
+    pub starting_points: Vec<i32>,
+    pub boxes: HashMap<i32, Vec<Vec<i32>>>,
+}
+
+#[derive(Debug)]
+pub enum PatternGraphError {
+    Io(std::io::Error),
+    Json(serde_json::Error),
+    NoStartingPoints,
+    NonPositiveStart(i32),
+    NonPositiveKey(i32),
+    EmptyPattern(i32),
+    ZeroValue(i32),
+}
+
+impl std::fmt::Display for PatternGraphError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Io(e) => write!(f, "failed to read patterns file: {}", e),
+            Self::Json(e) => write!(f, "invalid patterns JSON: {}", e),
+            Self::NoStartingPoints => f.write_str("no starting points"),
+            Self::NonPositiveStart(v) => write!(f, "starting point {} must be positive", v),
+            Self::NonPositiveKey(k) => write!(f, "box key {} must be positive", k),
+            Self::EmptyPattern(k) => write!(f, "box {} has an empty pattern", k),
+            Self::ZeroValue(k) => write!(f, "box {} has a pattern containing 0", k),
+        }
+    }
+}
+
+impl std::error::Error for PatternGraphError {}
+
+impl PatternGraph {
+    /// The compiled-in `STARTING_POINTS` and `BOXES`.
+    pub fn builtin() -> Self {
+        Self {
+            starting_points: STARTING_POINTS.to_vec(),
+            boxes: BOXES.clone(),
+        }
+    }
+
+    /// Loads `{"starting_points": [...], "boxes": {"23": [[-20, 13], ...]}}` and validates it.
+    pub fn from_file(path: impl AsRef<Path>) -> Result<Self, PatternGraphError> {
+        let json = std::fs::read_to_string(path).map_err(PatternGraphError::Io)?;
+        let graph: Self = serde_json::from_str(&json).map_err(PatternGraphError::Json)?;
+        graph.validate()?;
+        Ok(graph)
+    }
+
+    pub fn validate(&self) -> Result<(), PatternGraphError> {
+        if self.starting_points.is_empty() {
+            return Err(PatternGraphError::NoStartingPoints);
+        }
+        if let Some(&sp) = self.starting_points.iter().find(|&&sp| sp <= 0) {
+            return Err(PatternGraphError::NonPositiveStart(sp));
+        }
+        for (&key, patterns) in &self.boxes {
+            if key <= 0 {
+                return Err(PatternGraphError::NonPositiveKey(key));
+            }
+            for pattern in patterns {
+                if pattern.is_empty() {
+                    return Err(PatternGraphError::EmptyPattern(key));
+                }
+                if pattern.contains(&0) {
+                    return Err(PatternGraphError::ZeroValue(key));
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Starting points with no `boxes` entry, in order. Each becomes a lone length-1 path,
+    /// which is almost always a data-entry mistake.
+    pub fn starts_without_patterns(&self) -> Vec<i32> {
+        self.starting_points.iter().copied().filter(|sp| !self.boxes.contains_key(sp)).collect()
+    }
+
+    /// Pattern values that have no `boxes` entry and therefore end a path. Listed at
+    /// startup so a mistyped value (which silently becomes a leaf) is easy to spot.
+    pub fn leaf_values(&self) -> BTreeSet<i32> {
+        self.boxes
+            .values()
+            .flatten()
+            .flatten()
+            .map(|v| v.abs())
+            .filter(|v| !self.boxes.contains_key(v))
+            .collect()
+    }
+}
+
+/// Checks that every compiled-in `STARTING_POINTS` value has patterns in `BOXES`;
+/// the error lists the ones that don't.
+pub fn validate() -> Result<(), Vec<i32>> {
+    let missing = PatternGraph::builtin().starts_without_patterns();
+    if missing.is_empty() { Ok(()) } else { Err(missing) }
+}
@@ -0,0 +1,331 @@
+use crate::deduplication::Deduplicator;
+use crate::instruments::update_instrument_price;
+use crate::metrics::Metrics;
+use crate::scanner::{box_fingerprint, MarketScanner};
+use crate::signal::{MinRr, SignalGenerator};
+use crate::tracker::{ActiveSignal, Settlement, SignalTracker};
+use crate::types::{ParsedUpdate, SignalMessage, SignalType};
+use crate::volatility::VolatilityTracker;
+use std::collections::{HashMap, HashSet};
+use tokio::sync::RwLock;
+use tracing::{debug, info, warn};
+
+/// Signal thresholds that can be retuned on a running instance via `POST /api/admin/reload`.
+/// Replaced as a whole, never edited in place.
+#[derive(Debug, Clone, Default)]
+pub struct RuntimeConfig {
+    pub min_rr: MinRr,
+    /// Minimum gap between emitted signals of one type on one pair; 0 disables.
+    pub signal_cooldown_ms: i64,
+    pub pairs_allowlist: HashSet<String>,
+    pub pairs_blocklist: HashSet<String>,
+}
+
+impl RuntimeConfig {
+    /// Reads `MIN_RR`, `MIN_RR_L<n>`, `SIGNAL_COOLDOWN_MS`, `PAIRS_ALLOWLIST` and
+    /// `PAIRS_BLOCKLIST` from `vars`.
+    pub fn from_vars(vars: &HashMap<String, String>) -> Self {
+        let pairs = |name: &str| vars.get(name).map(|v| parse_pair_list(v)).unwrap_or_default();
+        Self {
+            min_rr: MinRr::from_vars(vars.clone()),
+            signal_cooldown_ms: vars.get("SIGNAL_COOLDOWN_MS").and_then(|v| v.parse().ok()).unwrap_or(0),
+            pairs_allowlist: pairs("PAIRS_ALLOWLIST"),
+            pairs_blocklist: pairs("PAIRS_BLOCKLIST"),
+        }
+    }
+
+    /// An empty allowlist allows every pair; the blocklist always wins.
+    pub fn pair_enabled(&self, pair: &str) -> bool {
+        (self.pairs_allowlist.is_empty() || self.pairs_allowlist.contains(pair))
+            && !self.pairs_blocklist.contains(pair)
+    }
+
+    pub fn to_json(&self) -> serde_json::Value {
+        let sorted = |set: &HashSet<String>| {
+            let mut pairs: Vec<&String> = set.iter().collect();
+            pairs.sort();
+            serde_json::json!(pairs)
+        };
+        let by_level: std::collections::BTreeMap<String, f64> =
+            self.min_rr.by_level.iter().map(|(level, rr)| (level.to_string(), *rr)).collect();
+        serde_json::json!({
+            "minRr": self.min_rr.default,
+            "minRrByLevel": by_level,
+            "signalCooldownMs": self.signal_cooldown_ms,
+            "pairs": {
+                "allowlist": sorted(&self.pairs_allowlist),
+                "blocklist": sorted(&self.pairs_blocklist)
+            }
+        })
+    }
+}
+
+/// Comma-separated pair list, uppercased; blank yields an empty set.
+pub fn parse_pair_list(list: &str) -> HashSet<String> {
+    list.split(',')
+        .map(|p| p.trim().to_uppercase())
+        .filter(|p| !p.is_empty())
+        .collect()
+}
+
+/// Box values in ticks, as detection rounds them (negated for inverted pairs).
+pub fn box_integer_values(pair: &str, boxes: &[crate::types::Box]) -> Vec<i32> {
+    let config = crate::instruments::get_instrument_config(pair);
+    let sign = if config.invert { -1.0 } else { 1.0 };
+    boxes.iter().map(|b| (sign * b.value / config.point).round() as i32).collect()
+}
+
+/// What one box update did: the settlements it triggered and the signals it started
+/// tracking, with their ids.
+#[derive(Debug, Default)]
+pub struct Processed {
+    pub settlements: Vec<Settlement>,
+    pub signals: Vec<SignalMessage>,
+}
+
+/// The settle → detect → dedup → generate → filter → track steps of a box update, over
+/// borrowed state. The server runs every update through it before forwarding; backtests
+/// run it over their own fresh state, so both apply exactly the same filters.
+pub struct Pipeline<'a> {
+    pub scanner: &'a MarketScanner,
+    pub generator: &'a SignalGenerator,
+    pub volatility: &'a VolatilityTracker,
+    pub tracker: &'a SignalTracker,
+    pub deduplicator: &'a Deduplicator,
+    pub metrics: &'a Metrics,
+    pub config: &'a RuntimeConfig,
+    /// Fingerprint of the last update per pair (uppercase); an identical update skips detection.
+    pub last_fingerprints: &'a RwLock<HashMap<String, u64>>,
+    /// Epoch ms of the last emitted signal per (pair, type), for the cooldown.
+    pub last_fired_at: &'a RwLock<HashMap<(String, SignalType), i64>>,
+    pub signal_ttl_ms: Option<i64>,
+    /// Trace the update step by step at info level (`DEBUG_PAIR`).
+    pub trace: bool,
+}
+
+impl Pipeline<'_> {
+    /// Whether a signal of this type on `pair` was emitted less than `cooldown_ms` ago;
+    /// counts the hit if so.
+    async fn in_cooldown(&self, pair: &str, signal_type: SignalType, cooldown_ms: i64, now_ms: i64) -> bool {
+        if cooldown_ms <= 0 {
+            return false;
+        }
+        let last = self.last_fired_at.read().await.get(&(pair.to_uppercase(), signal_type)).copied();
+        let hit = last.is_some_and(|at| now_ms - at < cooldown_ms);
+        if hit {
+            self.metrics.record_cooled_down();
+        }
+        hit
+    }
+
+    /// Recorded even while the cooldown is off, so enabling it by reload takes effect at once.
+    async fn mark_fired(&self, pair: &str, signal_type: SignalType, now_ms: i64) {
+        self.last_fired_at.write().await.insert((pair.to_uppercase(), signal_type), now_ms);
+    }
+
+    /// Runs one validated update for `pair`. `timestamp_ms` is the update's time: the wall
+    /// clock live, the recorded timestamp in a replay. Dedup windows, the cooldown,
+    /// `created_at` and the TTL are all measured from it.
+    pub async fn run(&self, pair: &str, update: &ParsedUpdate, timestamp_ms: i64) -> Processed {
+        let mut processed = Processed::default();
+        let (boxes, price) = (&update.boxes, update.price);
+
+        update_instrument_price(pair, price);
+        self.volatility
+            .record(pair, update.tick_high.unwrap_or(price), update.tick_low.unwrap_or(price), price)
+            .await;
+
+        let pair_upper = pair.to_uppercase();
+        let trace = self.trace;
+
+        // Step 1: Check existing active signals for price hits (stop loss or targets)
+        processed.settlements = self
+            .tracker
+            .check_price_range(&pair_upper, update.tick_low.unwrap_or(price), update.tick_high.unwrap_or(price), price)
+            .await;
+        if trace {
+            for s in &processed.settlements {
+                info!(pair = %pair_upper, id = s.signal.id, status = s.status, reason = %s.reason, price = s.settled_price, "TRACE settled");
+            }
+        }
+        if !processed.settlements.is_empty() {
+            info!(
+                "{} @ ${:.5} - {} signal(s) settled",
+                pair,
+                price,
+                processed.settlements.len()
+            );
+
+            for settlement in processed.settlements.iter().filter(|s| s.is_final()) {
+                self.metrics.record_outcome(settlement.signal.level, settlement.status);
+                if settlement.signal.level == 1 {
+                    self.deduplicator
+                        .remove_l1_signal(pair, &settlement.signal.signal_type.to_string())
+                        .await;
+                }
+            }
+        }
+
+        // Step 2: Detect new patterns and generate signals
+        let config = self.config;
+        if !config.pair_enabled(&pair_upper) {
+            if trace {
+                info!(pair = %pair_upper, "TRACE pair disabled by allowlist/blocklist, no detection");
+            }
+            return processed;
+        }
+
+        let fingerprint = box_fingerprint(pair, boxes, price);
+        if self.last_fingerprints.write().await.insert(pair_upper.clone(), fingerprint) == Some(fingerprint) {
+            debug!("SKIP: identical box update for {}", pair);
+            if trace {
+                info!(pair = %pair_upper, "TRACE identical to the previous update, no detection");
+            }
+            return processed;
+        }
+        let all_patterns = self.scanner.detect_patterns(pair, boxes);
+        if trace {
+            for p in &all_patterns {
+                info!(pair = %pair_upper, level = p.level, path = ?p.traversal_path.path, "TRACE matched");
+            }
+        }
+        if all_patterns.is_empty() {
+            debug!("{}: No patterns detected. Box integer values: {:?}", pair, box_integer_values(pair, boxes));
+            if trace {
+                info!(pair = %pair_upper, "TRACE no patterns matched");
+            }
+            return processed;
+        }
+
+        info!("{}: Detected {} pattern(s)", pair, all_patterns.len());
+
+        let mut filtered_patterns = Vec::new();
+        for pattern in &all_patterns {
+            if !self.deduplicator.should_filter_pattern(pair, pattern, boxes, timestamp_ms).await {
+                filtered_patterns.push(pattern.clone());
+            } else if trace {
+                info!(pair = %pair_upper, level = pattern.level, path = ?pattern.traversal_path.path, "TRACE filtered by pattern dedup");
+            }
+        }
+
+        if filtered_patterns.is_empty() {
+            debug!("{}: All {} pattern(s) filtered by deduplicator", pair, all_patterns.len());
+            return processed;
+        }
+
+        info!("{}: {} pattern(s) passed deduplication", pair, filtered_patterns.len());
+
+        let before_subsets = filtered_patterns.len();
+        let unique_patterns = self.deduplicator.remove_subset_duplicates(filtered_patterns);
+        if trace {
+            info!(
+                pair = %pair_upper,
+                removed = before_subsets - unique_patterns.len(),
+                kept = ?unique_patterns.iter().map(|p| &p.traversal_path.path).collect::<Vec<_>>(),
+                "TRACE subset duplicates removed"
+            );
+        }
+        info!("{} @ ${:.2} - {} pattern(s) after deduplication", pair, price, unique_patterns.len());
+
+        let atr = self.volatility.atr(pair).await;
+        for signal in self.generator.generate_signals_with_atr(pair, &unique_patterns, boxes, price, atr) {
+            if signal.entry.is_none() || signal.stop_losses.is_empty() || signal.targets.is_empty() {
+                continue;
+            }
+
+            // Checked before the stateful dedup filters so a low-RR signal doesn't mark its
+            // structure as seen and block a later, better variant
+            let final_rr = signal.risk_reward.last().copied().unwrap_or(0.0);
+            let min_rr = config.min_rr.for_level(signal.level);
+            if final_rr < min_rr {
+                info!("FILTERED: {} {} L{} - low RR {:.2} < {:.2}", signal.pair, signal.signal_type, signal.level, final_rr, min_rr);
+                if signal.level == 1 {
+                    self.deduplicator.remove_l1_signal(pair, &signal.signal_type).await;
+                }
+                continue;
+            }
+
+            let signal_type_enum: SignalType = match signal.signal_type.parse() {
+                Ok(signal_type) => signal_type,
+                Err(e) => {
+                    warn!("{}: skipping signal: {}", pair, e);
+                    continue;
+                }
+            };
+
+            // Also before the stateful dedup filters, so a suppressed signal isn't remembered
+            if self.in_cooldown(pair, signal_type_enum, config.signal_cooldown_ms, timestamp_ms).await {
+                info!("FILTERED: {} {} L{} - pair in cooldown ({}ms)", signal.pair, signal.signal_type, signal.level, config.signal_cooldown_ms);
+                if signal.level == 1 {
+                    self.deduplicator.remove_l1_signal(pair, &signal.signal_type).await;
+                }
+                continue;
+            }
+
+            // Likewise the per-pair cap, which add_signal would only enforce after the
+            // filters below had recorded the signal
+            if self.tracker.at_capacity(pair).await {
+                info!("FILTERED: {} {} L{} - pair at its active signal limit", signal.pair, signal.signal_type, signal.level);
+                if signal.level == 1 {
+                    self.deduplicator.remove_l1_signal(pair, &signal.signal_type).await;
+                }
+                continue;
+            }
+
+            // Structural before recent-window: the structural filter records the new box state
+            // even when it lets a signal through, while the recent-window filter only records
+            // signals that are actually about to be sent
+            if self.deduplicator.should_filter_structural_boxes(pair, &signal.box_details, signal_type_enum, signal.level).await {
+                info!("FILTERED: {} {} L{} - duplicate signal (structural boxes unchanged)", signal.pair, signal.signal_type, signal.level);
+                continue;
+            }
+
+            if self.deduplicator.should_filter_recent_signal(pair, &signal, timestamp_ms).await {
+                info!("FILTERED: {} {} L{} - duplicate signal (sent within recent window)", signal.pair, signal.signal_type, signal.level);
+                continue;
+            }
+
+            let entry = signal.entry.unwrap_or(0.0);
+            let stop_losses = signal.stop_losses.clone();
+            let targets = signal.targets.clone();
+            let first_stop = stop_losses.first().map(|sl| sl.price).unwrap_or(0.0);
+            let final_target = targets.last().map(|t| t.price).unwrap_or(0.0);
+
+            info!(
+                pair = %signal.pair,
+                signal_type = %signal.signal_type,
+                level = signal.level,
+                entry,
+                stop = first_stop,
+                target = final_target,
+                rr = final_rr,
+                pattern = ?signal.pattern_sequence,
+                "SIGNAL"
+            );
+            for (i, b) in signal.box_details.iter().enumerate() {
+                info!("  Box {}: {} H:{:.5} L:{:.5}", i, b.integer_value, b.high, b.low);
+            }
+            let stop_prices: Vec<f64> = stop_losses.iter().map(|sl| sl.price).collect();
+            let target_prices: Vec<f64> = targets.iter().map(|t| t.price).collect();
+            info!("  E:{:.5} S:{:?} (first: {:.5}) T:{:?} (final: {:.5}) R:R:{:?} (final: {:.2})", entry, stop_prices, first_stop, target_prices, final_target, signal.risk_reward, final_rr);
+
+            let created_at = timestamp_ms;
+            let Some(mut active_signal) = ActiveSignal::from_message(&signal, created_at) else {
+                continue;
+            };
+            active_signal.expires_at = self.signal_ttl_ms.map(|ttl| created_at + ttl);
+
+            let Some(signal_id) = self.tracker.add_signal(active_signal).await else {
+                continue;
+            };
+            let signal_with_id = SignalMessage {
+                id: Some(signal_id),
+                ..signal
+            };
+            self.metrics.record_level(signal_with_id.level);
+            self.mark_fired(pair, signal_type_enum, timestamp_ms).await;
+            processed.signals.push(signal_with_id);
+        }
+        processed
+    }
+}
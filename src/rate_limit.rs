@@ -0,0 +1,53 @@
+use std::collections::HashMap;
+use std::time::{Duration, Instant};
+use tokio::sync::RwLock;
+
+struct Bucket {
+    tokens: f64,
+    refilled_at: Instant,
+}
+
+/// Token bucket per pair: each pair may burst up to `burst` updates, then gets `rate`
+/// per second. Pairs are independent, so one noisy pair never throttles another.
+pub struct PairRateLimiter {
+    rate: f64,
+    burst: f64,
+    buckets: RwLock<HashMap<String, Bucket>>,
+}
+
+impl PairRateLimiter {
+    /// `rate` must be positive; `burst` is raised to at least one update.
+    pub fn new(rate: f64, burst: f64) -> Self {
+        Self {
+            rate,
+            burst: burst.max(1.0),
+            buckets: RwLock::new(HashMap::new()),
+        }
+    }
+
+    pub fn rate(&self) -> f64 {
+        self.rate
+    }
+
+    pub fn burst(&self) -> f64 {
+        self.burst
+    }
+
+    /// Takes a token for `pair` at `now`. When the bucket is empty, returns how long
+    /// until the next token is due instead.
+    pub async fn try_acquire(&self, pair: &str, now: Instant) -> Result<(), Duration> {
+        let mut buckets = self.buckets.write().await;
+        let bucket = buckets.entry(pair.to_uppercase()).or_insert(Bucket { tokens: self.burst, refilled_at: now });
+
+        let elapsed = now.saturating_duration_since(bucket.refilled_at).as_secs_f64();
+        bucket.tokens = (bucket.tokens + elapsed * self.rate).min(self.burst);
+        bucket.refilled_at = now;
+
+        if bucket.tokens >= 1.0 {
+            bucket.tokens -= 1.0;
+            Ok(())
+        } else {
+            Err(Duration::from_secs_f64((1.0 - bucket.tokens) / self.rate))
+        }
+    }
+}
@@ -1,33 +1,189 @@
 use crate::instruments::get_instrument_config;
-use crate::patterns::{BOXES, STARTING_POINTS};
-use crate::types::{Box, BoxDetail, PatternMatch, TraversalPath};
-use std::collections::HashSet;
+use crate::patterns::PatternGraph;
+use crate::types::{Box, BoxDetail, MatchExplanation, PatternMatch, TraversalPath, ValueSource};
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet};
+use std::hash::{DefaultHasher, Hash, Hasher};
+use tracing::{debug, warn};
+
+const DEFAULT_MAX_DEPTH: usize = 512;
+const DEFAULT_MAX_PATHS: usize = 50_000_000;
+/// Highest level with a trade rule in `signal.rs`.
+const DEFAULT_MAX_LEVEL: u32 = 6;
+
+/// Bounds on path generation, so a malformed pattern graph can't recurse forever or
+/// exhaust memory, and on the levels detection reports. The path defaults are far
+/// above what the compiled-in graph needs.
+#[derive(Debug, Clone, Copy)]
+pub struct ScanLimits {
+    /// Maximum values per path; longer paths are cut off and stored as-is.
+    pub max_depth: usize,
+    /// Maximum stored paths; traversal stops once reached.
+    pub max_paths: usize,
+    /// Detected levels above this are reported as this level, so deeper patterns use the
+    /// highest trade rule instead of matching none.
+    pub max_level: u32,
+    /// Minimum boxes a pair must report before detection runs; fewer yields no matches.
+    pub min_boxes: usize,
+}
+
+impl Default for ScanLimits {
+    fn default() -> Self {
+        Self {
+            max_depth: DEFAULT_MAX_DEPTH,
+            max_paths: DEFAULT_MAX_PATHS,
+            max_level: DEFAULT_MAX_LEVEL,
+            min_boxes: 1,
+        }
+    }
+}
+
+/// Shape of the path set a pattern graph expands to; see `analyze_graph`.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct GraphStats {
+    pub total_paths: usize,
+    /// Path length (number of values) -> paths of that length.
+    pub by_length: BTreeMap<usize, usize>,
+    /// Level -> paths at that level.
+    pub by_level: BTreeMap<u32, usize>,
+    /// First path of the greatest length.
+    pub longest_path: Vec<i32>,
+}
+
+/// Paths present in only one of two path sets; see `diff_paths`.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct PathDiff {
+    pub added: BTreeSet<String>,
+    pub removed: BTreeSet<String>,
+}
+
+impl PathDiff {
+    pub fn is_empty(&self) -> bool {
+        self.added.is_empty() && self.removed.is_empty()
+    }
+}
 
 #[derive(Default)]
 pub struct MarketScanner {
+    limits: ScanLimits,
+    /// Paths cut off at `max_depth` during the last initialize.
+    truncated_paths: usize,
+    /// Whether the last initialize skipped traversals because `max_paths` was reached.
+    hit_path_limit: bool,
     all_paths: Vec<TraversalPath>,
+    /// Pattern graph the paths were built from; levels are recomputed against it.
+    boxes: HashMap<i32, Vec<Vec<i32>>>,
+    /// First path value -> indices into `all_paths` (u32 keeps the index at ~4 bytes/path).
+    start_index: HashMap<i32, Vec<u32>>,
+    /// Absolute path value -> bit position in `path_masks`; empty if the graph has more
+    /// than 64 distinct values, in which case detection falls back to per-value lookups.
+    value_bits: HashMap<i32, u32>,
+    /// Per path: bit `id` set for each positive value, bit `id + 64` for each negative one.
+    path_masks: Vec<u128>,
+    /// Ticks a box value may sit from a path value and still match it. Up to 0.5 is plain
+    /// nearest-integer rounding.
+    value_tolerance: f64,
 }
 
 impl MarketScanner {
+    pub fn with_limits(limits: ScanLimits) -> Self {
+        Self { limits, ..Self::default() }
+    }
+
+    /// Lets a box match any path value within `ticks` of its raw `value / point`, not just
+    /// the nearest integer, so values straddling a rounding boundary (x.49 vs x.51) keep
+    /// matching the same paths between updates. Values of 0.5 or less change nothing.
+    pub fn with_value_tolerance(mut self, ticks: f64) -> Self {
+        self.value_tolerance = if ticks.is_finite() { ticks.max(0.0) } else { 0.0 };
+        self
+    }
+
+    /// Builds paths from the compiled-in pattern graph.
     pub fn initialize(&mut self) {
+        self.initialize_with(PatternGraph::builtin());
+    }
+
+    pub fn initialize_with(&mut self, graph: PatternGraph) {
+        let missing = graph.starts_without_patterns();
+        if !missing.is_empty() {
+            warn!("Starting point(s) {:?} have no patterns and only yield length-1 paths", missing);
+        }
         self.all_paths.clear();
+        self.truncated_paths = 0;
+        self.hit_path_limit = false;
         // Only generate LONG paths (positive), check inverted version during detection
-        for &sp in STARTING_POINTS {
-            self.traverse_all_paths(sp, vec![sp], sp);
+        for &sp in &graph.starting_points {
+            self.traverse_all_paths(&graph.boxes, sp, vec![sp], sp);
         }
+        self.boxes = graph.boxes;
+
+        if self.truncated_paths > 0 {
+            warn!("{} path(s) truncated at depth {}", self.truncated_paths, self.limits.max_depth);
+        }
+        if self.hit_path_limit {
+            warn!("Path limit {} reached, remaining traversals skipped", self.limits.max_paths);
+        }
+
+        self.start_index.clear();
+        for (idx, path) in self.all_paths.iter().enumerate() {
+            self.start_index.entry(path.starting_point()).or_default().push(idx as u32);
+        }
+        self.build_path_masks();
+    }
+
+    fn build_path_masks(&mut self) {
+        self.value_bits.clear();
+        self.path_masks.clear();
+        for v in self.all_paths.iter().flat_map(|p| &p.path) {
+            let next = self.value_bits.len() as u32;
+            self.value_bits.entry(v.abs()).or_insert(next);
+        }
+        if self.value_bits.len() > 64 {
+            debug!("{} distinct path values, path masks disabled", self.value_bits.len());
+            self.value_bits.clear();
+            return;
+        }
+        let masks: Vec<u128> = self.all_paths.iter().map(|p| self.value_mask(&p.path)).collect();
+        self.path_masks = masks;
+    }
+
+    fn value_mask(&self, values: &[i32]) -> u128 {
+        values.iter().fold(0, |mask, v| match self.value_bits.get(&v.abs()) {
+            Some(&bit) => mask | 1u128 << (bit + if *v < 0 { 64 } else { 0 }),
+            None => mask,
+        })
     }
 
     fn make_path(&self, path: Vec<i32>, _start: i32) -> TraversalPath {
         TraversalPath { path }
     }
 
-    fn traverse_all_paths(&mut self, current_key: i32, current_path: Vec<i32>, original_start: i32) {
-        let Some(patterns) = BOXES.get(&current_key.abs()).filter(|p| !p.is_empty()) else {
+    fn traverse_all_paths(
+        &mut self,
+        boxes: &HashMap<i32, Vec<Vec<i32>>>,
+        current_key: i32,
+        current_path: Vec<i32>,
+        original_start: i32,
+    ) {
+        if self.all_paths.len() >= self.limits.max_paths {
+            self.hit_path_limit = true;
+            return;
+        }
+        if current_path.len() >= self.limits.max_depth {
+            self.truncated_paths += 1;
+            self.all_paths.push(self.make_path(current_path, original_start));
+            return;
+        }
+
+        let Some(patterns) = boxes.get(&current_key.abs()).filter(|p| !p.is_empty()) else {
             self.all_paths.push(self.make_path(current_path, original_start));
             return;
         };
 
         for pattern in patterns {
+            if self.all_paths.len() >= self.limits.max_paths {
+                self.hit_path_limit = true;
+                return;
+            }
             let adjusted: Vec<i32> = if current_key > 0 {
                 pattern.clone()
             } else {
@@ -48,53 +204,214 @@ impl MarketScanner {
             if last.abs() == current_key.abs() {
                 self.all_paths.push(self.make_path(full_path, original_start));
             } else {
-                self.traverse_all_paths(last, full_path, original_start);
+                self.traverse_all_paths(boxes, last, full_path, original_start);
             }
         }
     }
 
+    /// Limits this scanner was built with.
+    pub fn limits(&self) -> ScanLimits {
+        self.limits
+    }
+
+    /// Paths cut off at `ScanLimits::max_depth` during the last initialize.
+    pub fn truncated_count(&self) -> usize {
+        self.truncated_paths
+    }
+
+    /// Whether the last initialize stopped early at `ScanLimits::max_paths`. Ending with
+    /// exactly `max_paths` paths after a complete traversal doesn't count.
+    pub fn path_limit_hit(&self) -> bool {
+        self.hit_path_limit
+    }
+
+    /// Number of stored (LONG) paths. Each is also matched inverted as a SHORT during
+    /// detection, so the number of detectable patterns is twice this.
     pub fn path_count(&self) -> usize {
         self.all_paths.len()
     }
 
+    /// Length and level distribution of the stored paths. Levels are capped at
+    /// `ScanLimits::max_level`, as in detection.
+    pub fn graph_stats(&self) -> GraphStats {
+        let mut stats = GraphStats { total_paths: self.all_paths.len(), ..GraphStats::default() };
+        for traversal in &self.all_paths {
+            *stats.by_length.entry(traversal.length()).or_default() += 1;
+            *stats.by_level.entry(self.calculate_level(&traversal.path)).or_default() += 1;
+            if traversal.length() > stats.longest_path.len() {
+                stats.longest_path = traversal.path.clone();
+            }
+        }
+        stats
+    }
+
+    /// Every stored path with its values joined by `_` (e.g. `100_-86_75`), for saving
+    /// before a graph edit and comparing with `diff_paths` after.
+    pub fn path_fingerprints(&self) -> BTreeSet<String> {
+        self.all_paths
+            .iter()
+            .map(|p| p.path.iter().map(|v| v.to_string()).collect::<Vec<_>>().join("_"))
+            .collect()
+    }
+
+    /// Number of distinct starting values in the first-value index.
+    pub fn start_index_len(&self) -> usize {
+        self.start_index.len()
+    }
+
+    /// Stored paths; all start at a positive value. SHORT paths are never materialized.
     pub fn get_paths(&self) -> &[TraversalPath] {
         &self.all_paths
     }
 
+    /// Stored paths beginning at `key`. Negative keys yield nothing; look up the
+    /// absolute value to see the traversals a SHORT match would mirror.
+    pub fn paths_starting_at(&self, key: i32) -> impl Iterator<Item = &TraversalPath> {
+        self.start_index
+            .get(&key)
+            .into_iter()
+            .flatten()
+            .map(|&idx| &self.all_paths[idx as usize])
+    }
+
+    /// Boxes are expected largest-first by absolute value; an unsorted array is sorted
+    /// here so box details stay consistent with the path order.
     pub fn detect_patterns(&self, pair: &str, boxes: &[Box]) -> Vec<PatternMatch> {
         if boxes.is_empty() { return vec![]; }
+        if boxes.len() < self.limits.min_boxes {
+            debug!("{}: {} box(es), below min_boxes {}, skipping detection", pair, boxes.len(), self.limits.min_boxes);
+            return vec![];
+        }
+
+        let sorted;
+        let boxes = if boxes.windows(2).all(|w| w[0].value.abs() >= w[1].value.abs()) {
+            boxes
+        } else {
+            debug!("{}: boxes not sorted by absolute value, sorting", pair);
+            sorted = {
+                let mut b = boxes.to_vec();
+                b.sort_by(|a, b| b.value.abs().total_cmp(&a.value.abs()));
+                b
+            };
+            &sorted
+        };
+
+        let candidates = self.value_candidates(pair, boxes);
+        let live_values: Vec<i32> = candidates.iter().flatten().copied().collect();
+        let value_set: HashSet<i32> = live_values.iter().copied().collect();
 
-        let (point, _) = get_instrument_config(pair);
-        let integer_values: Vec<i32> = boxes.iter().map(|b| (b.value / point).round() as i32).collect();
-        let value_set: HashSet<i32> = integer_values.iter().copied().collect();
+        // Only paths starting at a live value can match; a negative value selects the
+        // inverted (SHORT) form of the paths starting at its absolute value. Buckets are
+        // visited in path order so output matches a full scan, LONG before SHORT.
+        let mut buckets: Vec<(i32, &Vec<u32>)> = value_set
+            .iter()
+            .map(|v| v.abs())
+            .collect::<HashSet<i32>>()
+            .into_iter()
+            .filter_map(|key| self.start_index.get(&key).map(|idx| (key, idx)))
+            .collect();
+        buckets.sort_unstable_by_key(|(_, idx)| idx[0]);
+
+        let live_mask = self.value_mask(&live_values);
 
         let mut matches = Vec::new();
-        
-        for path in &self.all_paths {
-            // Check LONG pattern (original - all paths are now LONG)
-            let first = path.path[0];
-            if value_set.contains(&first) && path.path.iter().all(|v| value_set.contains(v)) {
-                matches.push(self.create_pattern_match(pair, path, boxes, &integer_values));
-            }
-            
-            // Check SHORT pattern (inverted) - flip all values
-            let inverted_first = -first;
-            if value_set.contains(&inverted_first) {
-                let inverted_path: Vec<i32> = path.path.iter().copied().map(|v| -v).collect();
-                if inverted_path.iter().all(|v| value_set.contains(v)) {
-                    let inverted_traversal = TraversalPath { path: inverted_path };
-                    matches.push(self.create_pattern_match(pair, &inverted_traversal, boxes, &integer_values));
+        for (key, indices) in buckets {
+            let (long, short) = (value_set.contains(&key), value_set.contains(&-key));
+            for &idx in indices {
+                let path = &self.all_paths[idx as usize];
+                if long && self.is_live(idx, false, live_mask, &value_set) {
+                    matches.push(self.create_pattern_match(pair, path, boxes, &candidates));
+                }
+                if short && self.is_live(idx, true, live_mask, &value_set) {
+                    let inverted_traversal = TraversalPath { path: path.path.iter().map(|v| -v).collect() };
+                    matches.push(self.create_pattern_match(pair, &inverted_traversal, boxes, &candidates));
                 }
             }
         }
-        
+
         matches
     }
 
-    fn create_pattern_match(&self, pair: &str, traversal: &TraversalPath, boxes: &[Box], integer_values: &[i32]) -> PatternMatch {
+    /// `detect_patterns` plus, for each match, which of the given `boxes` (by original
+    /// index) each path value came from. Slower; meant for debugging.
+    pub fn detect_patterns_explained(&self, pair: &str, boxes: &[Box]) -> Vec<MatchExplanation> {
+        let box_candidates = self.value_candidates(pair, boxes);
+
+        self.detect_patterns(pair, boxes)
+            .into_iter()
+            .map(|pattern| {
+                let sources = pattern
+                    .traversal_path
+                    .path
+                    .iter()
+                    .zip(&pattern.box_details)
+                    .map(|(&value, detail)| {
+                        let candidates: Vec<usize> = box_candidates
+                            .iter()
+                            .enumerate()
+                            .filter(|(_, c)| c.contains(&value))
+                            .map(|(i, _)| i)
+                            .collect();
+                        let box_index = candidates
+                            .iter()
+                            .copied()
+                            .find(|&i| boxes[i].high == detail.high && boxes[i].low == detail.low);
+                        ValueSource { value, box_index, candidates }
+                    })
+                    .collect();
+                MatchExplanation { pattern, sources }
+            })
+            .collect()
+    }
+
+    /// Whether every value of path `idx` (inverted for SHORT) is live. With masks a path
+    /// matches when it has no bits outside the live mask; swapping the halves of a mask
+    /// gives the mask of the inverted path.
+    fn is_live(&self, idx: u32, inverted: bool, live_mask: u128, value_set: &HashSet<i32>) -> bool {
+        let idx = idx as usize;
+        match (self.path_masks.get(idx), inverted) {
+            (Some(&mask), false) => mask & !live_mask == 0,
+            (Some(&mask), true) => mask.rotate_left(64) & !live_mask == 0,
+            (None, false) => self.all_paths[idx].path.iter().all(|v| value_set.contains(v)),
+            (None, true) => self.all_paths[idx].path.iter().all(|v| value_set.contains(&-v)),
+        }
+    }
+
+    /// Integer values each box can stand for: its rounded value first, then, when the
+    /// value tolerance exceeds half a tick, every other integer within the tolerance.
+    /// Values of an inverted pair are negated first.
+    fn value_candidates(&self, pair: &str, boxes: &[Box]) -> Vec<Vec<i32>> {
+        let config = get_instrument_config(pair);
+        let sign = if config.invert { -1.0 } else { 1.0 };
+        boxes
+            .iter()
+            .map(|b| {
+                let raw = sign * b.value / config.point;
+                let rounded = raw.round() as i32;
+                let mut candidates = vec![rounded];
+                if self.value_tolerance > 0.5 {
+                    let lo = (raw - self.value_tolerance).ceil() as i32;
+                    let hi = (raw + self.value_tolerance).floor() as i32;
+                    candidates.extend((lo..=hi).filter(|&k| k != rounded));
+                }
+                candidates
+            })
+            .collect()
+    }
+
+    /// Each path position binds to the next unused box with its value, so two boxes that
+    /// round to the same integer are both reachable. Boxes whose rounded value matches are
+    /// preferred over ones only within the value tolerance. A path repeating a value more
+    /// often than the boxes do reuses the first match.
+    fn create_pattern_match(&self, pair: &str, traversal: &TraversalPath, boxes: &[Box], candidates: &[Vec<i32>]) -> PatternMatch {
+        let mut used = vec![false; candidates.len()];
         let box_details: Vec<BoxDetail> = traversal.path.iter()
             .filter_map(|&path_value| {
-                integer_values.iter().position(|&v| v == path_value).map(|i| BoxDetail {
+                let unused = candidates.iter().enumerate().position(|(i, c)| c[0] == path_value && !used[i])
+                    .or_else(|| candidates.iter().enumerate().position(|(i, c)| c.contains(&path_value) && !used[i]));
+                let i = unused.or_else(|| candidates.iter().position(|c| c.contains(&path_value)))?;
+                used[i] = true;
+                Some(BoxDetail {
                     integer_value: path_value,
                     high: boxes[i].high,
                     low: boxes[i].low,
@@ -120,12 +437,13 @@ impl MarketScanner {
         let mut key = path[0];
 
         while idx < path.len() - 1 {
-            let Some(patterns) = BOXES.get(&key.abs()).filter(|p| !p.is_empty()) else { break };
+            let Some(patterns) = self.boxes.get(&key.abs()).filter(|p| !p.is_empty()) else { break };
 
+            let sign = if key > 0 { 1 } else { -1 };
             let found = patterns.iter().find_map(|pattern| {
-                let adjusted: Vec<i32> = if key > 0 { pattern.clone() } else { pattern.iter().copied().map(|v| -v).collect() };
-                let end = idx + 1 + adjusted.len();
-                (end <= path.len() && path[idx + 1..end] == adjusted).then(|| (end - 1, *adjusted.last().unwrap()))
+                let end = idx + 1 + pattern.len();
+                let matches = end <= path.len() && path[idx + 1..end].iter().zip(pattern).all(|(&p, &v)| p == v * sign);
+                matches.then(|| (end - 1, *pattern.last().unwrap() * sign))
             });
 
             if let Some((new_idx, new_key)) = found {
@@ -136,6 +454,35 @@ impl MarketScanner {
                 break;
             }
         }
-        level.max(1)
+        level.clamp(1, self.limits.max_level.max(1))
+    }
+}
+
+/// Expands the compiled-in pattern graph and reports its path statistics with uncapped
+/// levels, without starting the server. Meant for CI checks that a graph edit didn't blow
+/// up the path count.
+pub fn analyze_graph() -> GraphStats {
+    let mut scanner = MarketScanner::with_limits(ScanLimits { max_level: u32::MAX, ..ScanLimits::default() });
+    scanner.initialize();
+    scanner.graph_stats()
+}
+
+/// Paths in `new` but not `old` (added) and in `old` but not `new` (removed).
+pub fn diff_paths(old: &BTreeSet<String>, new: &BTreeSet<String>) -> PathDiff {
+    PathDiff {
+        added: new.difference(old).cloned().collect(),
+        removed: old.difference(new).cloned().collect(),
+    }
+}
+
+/// Fingerprint of a box update at the instrument's point resolution, used to skip
+/// replayed updates that would detect exactly the same patterns.
+pub fn box_fingerprint(pair: &str, boxes: &[Box], price: f64) -> u64 {
+    let point = get_instrument_config(pair).point;
+    let mut hasher = DefaultHasher::new();
+    for b in boxes {
+        ((b.value / point).round() as i64).hash(&mut hasher);
     }
+    ((price / point).round() as i64).hash(&mut hasher);
+    hasher.finish()
 }
@@ -1,5 +1,7 @@
+use crate::instruments::{get_instrument_config, InstrumentConfig};
 use crate::types::{BoxDetail, PatternMatch, SignalMessage, SignalType, Target, StopLoss};
 use lazy_static::lazy_static;
+use std::collections::HashMap;
 
 #[derive(Debug, Clone, Copy)]
 pub enum PricePoint { HIGH, LOW, MID }
@@ -12,10 +14,19 @@ pub struct TradeRule {
     pub entry_point: PricePoint,
     pub stop_boxes: Vec<usize>,
     pub stop_point: PricePoint,
+    /// Boxes whose `target_point` is a target. The first is the anchor: the final target
+    /// sits one anchor-box size (times the target multiplier) past its edge.
     pub target_boxes: Vec<usize>,
     pub target_point: PricePoint,
 }
 
+impl TradeRule {
+    /// Primary boxes the rule reads: one past the highest entry, stop or target index.
+    pub fn boxes_needed(&self) -> usize {
+        self.stop_boxes.iter().chain(&self.target_boxes).copied().chain([self.entry_box]).max().unwrap_or(0) + 1
+    }
+}
+
 // Trade rules configuration:
 // - Levels represent pattern reversals: L1 = 1 reversal, L2 = 2 reversals, etc.
 // - Boxes are 0-indexed and sorted by absolute value descending (Box 0 = largest).
@@ -148,6 +159,34 @@ lazy_static! {
             target_point: PricePoint::LOW,
         },
     ];
+
+    // Experimental: entry at the middle of the entry box, evaluated alongside the
+    // rules above when enabled via `SignalGenerator::with_mid_entry_rules`.
+    static ref MID_ENTRY_LONG_RULES: Vec<TradeRule> = vec![
+        TradeRule {
+            id: "L1_RULE_2",
+            level: 1,
+            entry_box: 1,
+            entry_point: PricePoint::MID,
+            stop_boxes: vec![0],
+            stop_point: PricePoint::LOW,
+            target_boxes: vec![0],
+            target_point: PricePoint::HIGH,
+        },
+    ];
+
+    static ref MID_ENTRY_SHORT_RULES: Vec<TradeRule> = vec![
+        TradeRule {
+            id: "L1_RULE_2",
+            level: 1,
+            entry_box: 1,
+            entry_point: PricePoint::MID,
+            stop_boxes: vec![0],
+            stop_point: PricePoint::HIGH,
+            target_boxes: vec![0],
+            target_point: PricePoint::LOW,
+        },
+    ];
 }
 
 fn get_rules(signal_type: SignalType) -> &'static [TradeRule] {
@@ -157,109 +196,299 @@ fn get_rules(signal_type: SignalType) -> &'static [TradeRule] {
     }
 }
 
-#[derive(Default)]
-pub struct SignalGenerator;
+fn get_mid_entry_rules(signal_type: SignalType) -> &'static [TradeRule] {
+    match signal_type {
+        SignalType::LONG => &MID_ENTRY_LONG_RULES,
+        SignalType::SHORT => &MID_ENTRY_SHORT_RULES,
+    }
+}
+
+const DEFAULT_TARGET_MULTIPLIER: f64 = 1.0;
+const DEFAULT_ATR_MULTIPLIER: f64 = 1.0;
+
+/// How far past the structure the final target sits, from `TARGET_MODEL`.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum TargetModel {
+    /// The anchor box's edge plus its size times the target multiplier.
+    #[default]
+    BoxSize,
+    /// Entry plus the pair's ATR times the ATR multiplier; falls back to `BoxSize`
+    /// while no ATR is known.
+    Volatility,
+}
+
+impl std::fmt::Display for TargetModel {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self { Self::BoxSize => "box_size", Self::Volatility => "volatility" })
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ParseTargetModelError(pub String);
+
+impl std::fmt::Display for ParseTargetModelError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "unknown target model: {:?} (expected box_size or volatility)", self.0)
+    }
+}
+
+impl std::error::Error for ParseTargetModelError {}
+
+impl std::str::FromStr for TargetModel {
+    type Err = ParseTargetModelError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if s.eq_ignore_ascii_case("box_size") {
+            Ok(Self::BoxSize)
+        } else if s.eq_ignore_ascii_case("volatility") {
+            Ok(Self::Volatility)
+        } else {
+            Err(ParseTargetModelError(s.to_string()))
+        }
+    }
+}
+
+/// Which signal directions `SignalGenerator` emits, from `DIRECTION_FILTER`.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum DirectionFilter {
+    #[default]
+    Both,
+    Long,
+    Short,
+}
+
+impl DirectionFilter {
+    pub fn allows(self, signal_type: SignalType) -> bool {
+        match self {
+            Self::Both => true,
+            Self::Long => signal_type == SignalType::LONG,
+            Self::Short => signal_type == SignalType::SHORT,
+        }
+    }
+}
+
+impl std::fmt::Display for DirectionFilter {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self { Self::Both => "both", Self::Long => "long", Self::Short => "short" })
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ParseDirectionFilterError(pub String);
+
+impl std::fmt::Display for ParseDirectionFilterError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "unknown direction filter: {:?} (expected long, short or both)", self.0)
+    }
+}
+
+impl std::error::Error for ParseDirectionFilterError {}
+
+impl std::str::FromStr for DirectionFilter {
+    type Err = ParseDirectionFilterError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if s.eq_ignore_ascii_case("both") {
+            Ok(Self::Both)
+        } else if s.eq_ignore_ascii_case("long") {
+            Ok(Self::Long)
+        } else if s.eq_ignore_ascii_case("short") {
+            Ok(Self::Short)
+        } else {
+            Err(ParseDirectionFilterError(s.to_string()))
+        }
+    }
+}
+
+pub struct SignalGenerator {
+    mid_entry_rules: bool,
+    direction: DirectionFilter,
+    /// How many anchor-box sizes the final target extends.
+    target_multiplier: f64,
+    target_model: TargetModel,
+    /// How many ATRs from entry the final target sits under `TargetModel::Volatility`.
+    atr_multiplier: f64,
+    /// Box every rule's targets are anchored at, at the earliest; 0 keeps the rules' own.
+    target_box: usize,
+    /// Account currency risked per trade; `None` leaves sizes unset.
+    risk_per_trade: Option<f64>,
+    /// Minimum size, in ticks, of the pattern's first box; smaller boxes give no trade.
+    min_box_ticks: f64,
+}
+
+impl Default for SignalGenerator {
+    fn default() -> Self {
+        Self {
+            mid_entry_rules: false,
+            direction: DirectionFilter::Both,
+            target_multiplier: DEFAULT_TARGET_MULTIPLIER,
+            target_model: TargetModel::BoxSize,
+            atr_multiplier: DEFAULT_ATR_MULTIPLIER,
+            target_box: 0,
+            risk_per_trade: None,
+            min_box_ticks: 0.0,
+        }
+    }
+}
 
 impl SignalGenerator {
-    pub fn generate_signals(&self, pair: &str, patterns: &[PatternMatch], _boxes: &[crate::types::Box], _price: f64) -> Vec<SignalMessage> {
+    /// Also evaluates the experimental MID-entry rules; the best valid rule by final
+    /// risk/reward is used for each pattern.
+    pub fn with_mid_entry_rules(enabled: bool) -> Self {
+        Self { mid_entry_rules: enabled, ..Self::default() }
+    }
+
+    /// Scales the box size added to the final target (1.0 = one box). Non-positive or
+    /// non-finite values are ignored.
+    pub fn with_target_multiplier(mut self, multiplier: f64) -> Self {
+        if multiplier.is_finite() && multiplier > 0.0 {
+            self.target_multiplier = multiplier;
+        }
+        self
+    }
+
+    /// Sizes each opportunity so a stop-out loses `risk` (positive and finite, otherwise
+    /// ignored). See `position_size`.
+    pub fn with_risk_per_trade(mut self, risk: Option<f64>) -> Self {
+        self.risk_per_trade = risk.filter(|r| r.is_finite() && *r > 0.0);
+        self
+    }
+
+    /// Under `TargetModel::Volatility` the final target is `entry ± atr_multiplier * atr`,
+    /// with the ATR passed to `generate_signals_with_atr`.
+    pub fn with_target_model(mut self, model: TargetModel) -> Self {
+        self.target_model = model;
+        self
+    }
+
+    /// ATRs from entry to the final target under `TargetModel::Volatility` (default 1.0);
+    /// independent of `with_target_multiplier`. Non-positive or non-finite values are ignored.
+    pub fn with_atr_multiplier(mut self, multiplier: f64) -> Self {
+        if multiplier.is_finite() && multiplier > 0.0 {
+            self.atr_multiplier = multiplier;
+        }
+        self
+    }
+
+    pub fn target_model(&self) -> TargetModel {
+        self.target_model
+    }
+
+    /// Anchors targets at box `index` (0-indexed, largest first) instead of box 0, for more
+    /// conservative targets: a rule's target boxes before `index` are skipped, and the final
+    /// target extends past box `index` by that box's size. Rules with no target box at or
+    /// after `index` target box `index` alone. Patterns with fewer primary boxes get no
+    /// targets and are dropped.
+    pub fn with_target_box(mut self, index: usize) -> Self {
+        self.target_box = index;
+        self
+    }
+
+    /// Patterns whose first box (the one the final target is scaled from) spans fewer
+    /// than `ticks` points get no entry, stops or targets, so callers drop them. Tiny boxes
+    /// otherwise produce near-zero targets and meaningless risk/rewards.
+    pub fn with_min_box_size(mut self, ticks: f64) -> Self {
+        self.min_box_ticks = if ticks.is_finite() { ticks.max(0.0) } else { 0.0 };
+        self
+    }
+
+    /// Generates signals in one direction only; patterns the other way produce nothing.
+    pub fn with_direction_filter(mut self, direction: DirectionFilter) -> Self {
+        self.direction = direction;
+        self
+    }
+
+    pub fn direction_filter(&self) -> DirectionFilter {
+        self.direction
+    }
+
+    fn rules(&self, signal_type: SignalType) -> impl Iterator<Item = &'static TradeRule> {
+        let mid: &'static [TradeRule] = if self.mid_entry_rules { get_mid_entry_rules(signal_type) } else { &[] };
+        get_rules(signal_type).iter().chain(mid)
+    }
+
+    pub fn generate_signals(&self, pair: &str, patterns: &[PatternMatch], boxes: &[crate::types::Box], price: f64) -> Vec<SignalMessage> {
+        self.generate_signals_with_atr(pair, patterns, boxes, price, None)
+    }
+
+    /// `generate_signals` with the pair's current ATR (see `volatility::VolatilityTracker`),
+    /// used for the final target under `TargetModel::Volatility`. Without one, targets fall
+    /// back to the box-size model.
+    pub fn generate_signals_with_atr(
+        &self,
+        pair: &str,
+        patterns: &[PatternMatch],
+        _boxes: &[crate::types::Box],
+        _price: f64,
+        atr: Option<f64>,
+    ) -> Vec<SignalMessage> {
+        let instrument = get_instrument_config(pair);
+        let atr_distance = match self.target_model {
+            TargetModel::BoxSize => None,
+            TargetModel::Volatility => atr.filter(|a| a.is_finite() && *a > 0.0).map(|a| a * self.atr_multiplier),
+        };
+        if self.target_model == TargetModel::Volatility && atr_distance.is_none() && !patterns.is_empty() {
+            tracing::debug!("{}: no ATR yet, using box-size targets", pair);
+        }
         patterns.iter()
-            .filter(|p| get_rules(p.traversal_path.signal_type()).iter().any(|r| r.level == p.level))
-            .map(|p| self.create_signal(pair, p))
+            .filter(|p| self.direction.allows(p.traversal_path.signal_type()))
+            .filter(|p| self.rules(p.traversal_path.signal_type()).any(|r| r.level == p.level))
+            .map(|p| self.create_signal(pair, p, &instrument, atr_distance))
             .collect()
     }
 
-    fn create_signal(&self, pair: &str, pattern: &PatternMatch) -> SignalMessage {
+    /// Entry, stops and targets are rounded to the instrument's `digits` so they compare
+    /// cleanly against quoted prices during settlement.
+    fn create_signal(&self, pair: &str, pattern: &PatternMatch, instrument: &InstrumentConfig, atr_distance: Option<f64>) -> SignalMessage {
+        let (digits, contract_size) = (instrument.digits, instrument.contract_size);
         let _path_str = pattern.traversal_path.path.iter().map(|v| v.to_string()).collect::<Vec<_>>().join("_");
         
         let sig_type = pattern.traversal_path.signal_type();
         let mut primary: Vec<&BoxDetail> = pattern.box_details.iter()
             .filter(|b| matches!(sig_type, SignalType::LONG if b.integer_value > 0) || matches!(sig_type, SignalType::SHORT if b.integer_value < 0))
             .collect();
-        primary.sort_by(|a, b| b.integer_value.abs().cmp(&a.integer_value.abs()));
+        primary.sort_by_key(|b| std::cmp::Reverse(b.integer_value.abs()));
 
-        let rule = get_rules(sig_type).iter()
-            .find(|r| r.level == pattern.level);
+        let box1_ticks = primary.first().map_or(0.0, |b| (b.high - b.low) / instrument.point);
+        let too_small = box1_ticks < self.min_box_ticks;
+        if too_small {
+            tracing::debug!(
+                "{}: {:?} first box is {:.1} ticks, below the {} tick minimum",
+                pair, pattern.traversal_path.path, box1_ticks, self.min_box_ticks
+            );
+        }
 
-        let (entry, stop_losses, targets, risk_reward) = if let Some(rule) = rule {
-            let entry = get_price(&primary, rule.entry_box, rule.entry_point);
-            
-            let stop_loss_prices: Vec<f64> = rule.stop_boxes.iter()
-                .filter_map(|&box_idx| get_price(&primary, box_idx, rule.stop_point))
-                .collect();
-            
-            let stop_losses: Vec<StopLoss> = stop_loss_prices.iter()
-                .map(|&price| StopLoss { price, timestamp: None })
-                .collect();
-            
-            let target_prices = rule.target_boxes.first().and_then(|&first_box_idx| {
-                get_price(&primary, first_box_idx, rule.target_point).map(|base| {
-                    let mut calculated_targets = Vec::new();
-                    
-                    // Get first box size for the last target calculation
-                    let first_box_size = primary.get(first_box_idx)
-                        .map(|b| b.high - b.low)
-                        .unwrap_or(0.0);
-                    
-                    // All targets except the last: direct HIGH/LOW values of each box
-                    for &box_idx in &rule.target_boxes {
-                        if let Some(box_detail) = primary.get(box_idx) {
-                            let target = match rule.target_point {
-                                PricePoint::HIGH => box_detail.high,
-                                PricePoint::LOW => box_detail.low,
-                                PricePoint::MID => (box_detail.high + box_detail.low) / 2.0,
-                            };
-                            calculated_targets.push(target);
-                        }
-                    }
-                    
-                    // Last target (highest/furthest): base + first box size for LONG, base - first box size for SHORT
-                    let last_target = match sig_type {
-                        SignalType::LONG => base + first_box_size,
-                        SignalType::SHORT => base - first_box_size,
-                    };
-                    calculated_targets.push(last_target);
-                    
-                    // Sort targets: closest to furthest
-                    // LONG: ascending (smallest/closest first, largest/furthest last)
-                    // SHORT: descending (largest/closest first, smallest/furthest last)
-                    match sig_type {
-                        SignalType::LONG => calculated_targets.sort_by(|a, b| a.partial_cmp(b).unwrap_or(std::cmp::Ordering::Equal)),
-                        SignalType::SHORT => calculated_targets.sort_by(|a, b| b.partial_cmp(a).unwrap_or(std::cmp::Ordering::Equal)),
-                    }
-                    
-                    calculated_targets
-                })
-            }).unwrap_or_default();
-            
-            let targets: Vec<Target> = target_prices.iter()
-                .map(|&price| Target { price, timestamp: None })
-                .collect();
-            
-            let risk_reward: Vec<f64> = entry.zip(stop_loss_prices.first().copied()).map_or_else(
-                Vec::new,
-                |(e, s)| {
-                    let risk = (e - s).abs();
-                    if risk > 0.0 {
-                        target_prices.iter()
-                            .map(|&t| {
-                                let reward = match sig_type {
-                                    SignalType::LONG => (t - e).abs(),
-                                    SignalType::SHORT => (e - t).abs(),
-                                };
-                                (reward / risk).round()
-                            })
-                            .collect()
-                    } else {
-                        vec![]
-                    }
+        // Every rule for the level is evaluated; an invalid first rule is kept so the
+        // caller's validity check still sees (and drops) the pattern.
+        let opportunities: Vec<TradeOpportunity> = self.rules(sig_type)
+            .filter(|_| !too_small)
+            .filter(|r| r.level == pattern.level)
+            .map(|rule| {
+                let mut opportunity = apply_rule(rule, &primary, sig_type, digits, self.target_multiplier, self.target_box, atr_distance);
+                let needed = rule.boxes_needed().max(self.target_box + 1);
+                if !opportunity.is_valid() && primary.len() < needed {
+                    tracing::debug!(
+                        "{}: {} L{} {} needs {} primary boxes, {} of the pattern's {} boxes are {}",
+                        pair, sig_type, pattern.level, rule.id, needed, primary.len(), pattern.box_details.len(), sig_type
+                    );
                 }
-            );
-            
-            (entry, stop_losses, targets, risk_reward)
-        } else {
-            (None, vec![], vec![], vec![])
-        };
-        
+                opportunity.suggested_size = self.risk_per_trade.and_then(|risk| {
+                    let stop = opportunity.stop_losses.first()?.price;
+                    position_size(risk, opportunity.entry?, stop, contract_size)
+                });
+                opportunity
+            })
+            .collect();
+        let best = best_opportunity(&opportunities)
+            .or(opportunities.first())
+            .cloned();
+        let (entry, stop_losses, targets, risk_reward, suggested_size) = best.map_or(
+            (None, vec![], vec![], vec![], None),
+            |o| (o.entry, o.stop_losses, o.targets, o.risk_reward, o.suggested_size),
+        );
+
+        let confidence = score_pattern(pattern, risk_reward.last().copied());
+
         SignalMessage {
             id: None, // Will be set after Supabase insert
             pair: pair.to_string(),
@@ -272,8 +501,218 @@ impl SignalGenerator {
             stop_losses,
             targets,
             risk_reward,
+            confidence,
+            suggested_size,
+        }
+    }
+}
+
+/// Minimum final-target risk/reward a signal needs before it is tracked or forwarded.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct MinRr {
+    /// Applies to levels without an override.
+    pub default: f64,
+    pub by_level: HashMap<u32, f64>,
+}
+
+impl MinRr {
+    /// Reads `MIN_RR` and `MIN_RR_L<level>` overrides (e.g. `MIN_RR_L4=1.2`) from
+    /// `vars`, usually `std::env::vars()`. Unparsable values are ignored.
+    pub fn from_vars(vars: impl IntoIterator<Item = (String, String)>) -> Self {
+        let mut min_rr = Self::default();
+        for (key, value) in vars {
+            let Ok(rr) = value.trim().parse::<f64>() else { continue };
+            if key == "MIN_RR" {
+                min_rr.default = rr;
+            } else if let Some(level) = key.strip_prefix("MIN_RR_L").and_then(|l| l.parse().ok()) {
+                min_rr.by_level.insert(level, rr);
+            }
         }
+        min_rr
+    }
+
+    pub fn for_level(&self, level: u32) -> f64 {
+        self.by_level.get(&level).copied().unwrap_or(self.default)
+    }
+}
+
+/// Lots such that moving from `entry` to `stop` loses `risk`:
+/// `risk / (|entry - stop| * contract_size)`. `None` when entry equals stop.
+pub fn position_size(risk: f64, entry: f64, stop: f64, contract_size: f64) -> Option<f64> {
+    let per_lot = (entry - stop).abs() * contract_size;
+    (per_lot > 0.0 && per_lot.is_finite()).then(|| risk / per_lot)
+}
+
+const MAX_LEVEL: f64 = 6.0;
+const PATH_LENGTH_HALF: f64 = 8.0;
+
+/// Confidence in `0.0..=1.0`: the product of the level (out of L6), the path length
+/// (saturating, 8 values scores 0.5) and the final-target risk/reward (`rr / (rr + 1)`).
+/// A pattern without a risk/reward scores 0.
+pub fn score_pattern(pattern: &PatternMatch, rr: Option<f64>) -> f64 {
+    score(pattern.level, pattern.traversal_path.length(), rr)
+}
+
+/// `score_pattern` from the parts a stored signal keeps: its level, the length of its
+/// pattern sequence and its final risk/reward.
+pub fn score(level: u32, path_len: usize, rr: Option<f64>) -> f64 {
+    let level = (level as f64).min(MAX_LEVEL) / MAX_LEVEL;
+    let len = path_len as f64;
+    let length = len / (len + PATH_LENGTH_HALF);
+    let reward = rr.filter(|r| r.is_finite() && *r > 0.0).map_or(0.0, |r| r / (r + 1.0));
+    level * length * reward
+}
+
+/// Prices one trade rule produced for a pattern.
+#[derive(Debug, Clone)]
+pub struct TradeOpportunity {
+    pub rule_id: &'static str,
+    pub entry: Option<f64>,
+    pub stop_losses: Vec<StopLoss>,
+    pub targets: Vec<Target>,
+    pub risk_reward: Vec<f64>,
+    /// Lots to trade for the configured risk per trade; see `position_size`.
+    pub suggested_size: Option<f64>,
+}
+
+impl TradeOpportunity {
+    /// Has an entry, a stop and at least one target.
+    pub fn is_valid(&self) -> bool {
+        self.entry.is_some() && !self.stop_losses.is_empty() && !self.targets.is_empty()
     }
+
+    /// Risk/reward to the final target.
+    pub fn final_rr(&self) -> Option<f64> {
+        self.risk_reward.last().copied()
+    }
+}
+
+/// The valid opportunity with the highest final-target risk/reward; the earlier one wins
+/// ties. `None` if none are valid.
+pub fn best_opportunity(opportunities: &[TradeOpportunity]) -> Option<&TradeOpportunity> {
+    opportunities
+        .iter()
+        .filter(|o| o.is_valid())
+        .fold(None, |best: Option<&TradeOpportunity>, o| match best {
+            Some(b) if b.final_rr().unwrap_or(0.0) >= o.final_rr().unwrap_or(0.0) => Some(b),
+            _ => Some(o),
+        })
+}
+
+/// Entry, stops, targets and per-target risk/reward for one rule, rounded to `digits`. The
+/// final target extends `target_multiplier` anchor-box sizes beyond the anchor, the first
+/// target box at or after `target_box`. With an `atr_distance` (the scaled ATR), the final
+/// target is instead that far from the entry, and box targets beyond it are dropped so it
+/// stays the furthest.
+fn apply_rule(
+    rule: &TradeRule,
+    primary: &[&BoxDetail],
+    sig_type: SignalType,
+    digits: u8,
+    target_multiplier: f64,
+    target_box: usize,
+    atr_distance: Option<f64>,
+) -> TradeOpportunity {
+    let raw_entry = get_price(primary, rule.entry_box, rule.entry_point);
+    let entry = raw_entry.map(|price| round_to_digits(price, digits));
+    
+    let stop_loss_prices: Vec<f64> = rule.stop_boxes.iter()
+        .filter_map(|&box_idx| get_price(primary, box_idx, rule.stop_point))
+        .map(|price| round_to_digits(price, digits))
+        .collect();
+    
+    let stop_losses: Vec<StopLoss> = stop_loss_prices.iter()
+        .map(|&price| StopLoss { price, timestamp: None })
+        .collect();
+    
+    let mut target_boxes: Vec<usize> = rule.target_boxes.iter().copied().filter(|&b| b >= target_box).collect();
+    if target_boxes.is_empty() && !rule.target_boxes.is_empty() {
+        target_boxes.push(target_box);
+    }
+
+    let target_prices = target_boxes.first().and_then(|&first_box_idx| {
+        get_price(primary, first_box_idx, rule.target_point).map(|base| {
+            let mut calculated_targets = Vec::new();
+            
+            // Get the anchor box size for the last target calculation
+            let first_box_size = primary.get(first_box_idx)
+                .map(|b| (b.high - b.low) * target_multiplier)
+                .unwrap_or(0.0);
+            
+            // All targets except the last: direct HIGH/LOW values of each box
+            for &box_idx in &target_boxes {
+                if let Some(box_detail) = primary.get(box_idx) {
+                    let target = match rule.target_point {
+                        PricePoint::HIGH => box_detail.high,
+                        PricePoint::LOW => box_detail.low,
+                        PricePoint::MID => (box_detail.high + box_detail.low) / 2.0,
+                    };
+                    calculated_targets.push(target);
+                }
+            }
+            
+            // Last target (highest/furthest): base + scaled anchor box size for LONG, base - for
+            // SHORT; or entry +/- scaled ATR under the volatility model
+            let (origin, distance) = match (atr_distance, raw_entry) {
+                (Some(atr_distance), Some(entry)) => (entry, atr_distance),
+                _ => (base, first_box_size),
+            };
+            let last_target = match sig_type {
+                SignalType::LONG => origin + distance,
+                SignalType::SHORT => origin - distance,
+            };
+            // An ATR smaller than the boxes would otherwise sort a box edge past it and make
+            // that the final target
+            if atr_distance.is_some() {
+                calculated_targets.retain(|&t| match sig_type {
+                    SignalType::LONG => t < last_target,
+                    SignalType::SHORT => t > last_target,
+                });
+            }
+            calculated_targets.push(last_target);
+            
+            // Sort targets: closest to furthest
+            // LONG: ascending (smallest/closest first, largest/furthest last)
+            // SHORT: descending (largest/closest first, smallest/furthest last)
+            match sig_type {
+                SignalType::LONG => calculated_targets.sort_by(|a, b| a.partial_cmp(b).unwrap_or(std::cmp::Ordering::Equal)),
+                SignalType::SHORT => calculated_targets.sort_by(|a, b| b.partial_cmp(a).unwrap_or(std::cmp::Ordering::Equal)),
+            }
+            
+            calculated_targets.into_iter().map(|price| round_to_digits(price, digits)).collect::<Vec<_>>()
+        })
+    }).unwrap_or_default();
+    
+    let targets: Vec<Target> = target_prices.iter()
+        .map(|&price| Target { price, timestamp: None })
+        .collect();
+    
+    let risk_reward: Vec<f64> = entry.zip(stop_loss_prices.first().copied()).map_or_else(
+        Vec::new,
+        |(e, s)| {
+            let risk = (e - s).abs();
+            if risk > 0.0 {
+                target_prices.iter()
+                    .map(|&t| {
+                        let reward = match sig_type {
+                            SignalType::LONG => (t - e).abs(),
+                            SignalType::SHORT => (e - t).abs(),
+                        };
+                        (reward / risk).round()
+                    })
+                    .collect()
+            } else {
+                vec![]
+            }
+        }
+    );
+    
+    TradeOpportunity { rule_id: rule.id, entry, stop_losses, targets, risk_reward, suggested_size: None }
+}
+
+pub fn round_to_digits(price: f64, digits: u8) -> f64 {
+    let factor = 10f64.powi(digits as i32);
+    (price * factor).round() / factor
 }
 
 fn get_price(boxes: &[&BoxDetail], idx: usize, point: PricePoint) -> Option<f64> {
@@ -1,30 +1,276 @@
-use reqwest::Client;
-use serde::Serialize;
+use crate::types::{BoxDetail, SettlementReason, SignalType, StopLoss, Target};
+use reqwest::{Client, RequestBuilder, Response, StatusCode};
+use serde::{Deserialize, Serialize};
 use serde_json::Value as JsonValue;
-use tracing::{info, warn};
-use chrono::{DateTime, Utc, TimeZone};
+use tracing::{debug, info, warn};
+use chrono::{Utc, TimeZone};
+use std::sync::atomic::{AtomicI64, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+
+/// Default for `SupabaseClient::with_max_attempts`.
+pub const DEFAULT_MAX_ATTEMPTS: u32 = 3;
+const RETRY_BASE_DELAY: Duration = Duration::from_millis(250);
+const RETRY_MAX_DELAY: Duration = Duration::from_secs(4);
+
+/// Why a Supabase request failed.
+#[derive(Debug)]
+pub enum SupabaseError {
+    /// PostgREST answered with a non-success status.
+    Http { status: StatusCode, body: String },
+    /// No complete response within the client's timeout (`HTTP_TIMEOUT_SECS`).
+    Timeout(reqwest::Error),
+    /// The request never got a response for any other reason (connect, TLS...).
+    Transport(reqwest::Error),
+    /// A success response whose body wasn't what we expected.
+    Decode(String),
+}
+
+impl SupabaseError {
+    /// Timeouts, transport failures and 5xx responses may succeed if repeated; 4xx won't.
+    pub fn is_retryable(&self) -> bool {
+        match self {
+            Self::Timeout(_) | Self::Transport(_) => true,
+            Self::Http { status, .. } => status.is_server_error(),
+            Self::Decode(_) => false,
+        }
+    }
+}
+
+impl std::fmt::Display for SupabaseError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Http { status, body } => write!(f, "HTTP {}: {}", status, body),
+            Self::Timeout(e) => write!(f, "timed out: {}", e),
+            Self::Transport(e) => write!(f, "transport error: {}", e),
+            Self::Decode(e) => write!(f, "unexpected response: {}", e),
+        }
+    }
+}
+
+impl std::error::Error for SupabaseError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Self::Timeout(e) | Self::Transport(e) => Some(e),
+            Self::Http { .. } | Self::Decode(_) => None,
+        }
+    }
+}
+
+impl From<reqwest::Error> for SupabaseError {
+    fn from(e: reqwest::Error) -> Self {
+        if e.is_timeout() {
+            Self::Timeout(e)
+        } else if e.is_decode() {
+            Self::Decode(e.to_string())
+        } else {
+            Self::Transport(e)
+        }
+    }
+}
+
+/// Turns a non-success response into `SupabaseError::Http`, keeping its body.
+async fn check_status(response: Response) -> Result<Response, SupabaseError> {
+    let status = response.status();
+    if status.is_success() {
+        return Ok(response);
+    }
+    let body = response.text().await.unwrap_or_default();
+    Err(SupabaseError::Http { status, body })
+}
 
 #[derive(Clone)]
 pub struct SupabaseClient {
     client: Client,
     url: String,
     service_key: String,
+    /// Epoch ms of the last successful request; 0 until one succeeds. Shared by clones.
+    last_success_ms: Arc<AtomicI64>,
+    /// Tries per request, including the first, for timeouts, transport errors and 5xx.
+    max_attempts: u32,
+    signal_id_mode: SignalIdMode,
 }
 
-#[derive(Serialize)]
-struct UpdateSignalStatus {
-    status: String,
+/// A row of the `signals` table as needed to resume tracking it.
+#[derive(Debug, Deserialize)]
+pub struct SignalRow {
+    pub id: i64,
+    pub pair: String,
+    pub signal_type: SignalType,
+    pub level: u32,
+    pub entry: f64,
+    pub stop_losses: Option<Vec<StopLoss>>,
+    pub targets: Option<Vec<Target>>,
+    pub risk_reward: Option<Vec<f64>>,
+    pub pattern_sequence: Option<Vec<i32>>,
+    pub box_details: Option<Vec<BoxDetail>>,
+    pub created_at: Option<String>,
+    /// Best price seen by a trailing stop, persisted as it advances.
+    #[serde(default)]
+    pub water_mark: Option<f64>,
+}
+
+/// Final-settlement columns written by `update_signal_status`.
+#[derive(Debug, Serialize)]
+pub struct UpdateSignalStatus {
+    pub status: String,
+    pub settlement_reason: SettlementReason,
+    /// Maximum favorable / adverse excursion from entry, in price units.
+    pub mfe: f64,
+    pub mae: f64,
+    pub settled_price: f64,
+    pub settled_at: String,
+}
+
+/// A settled row of the `signals` table, as exported by `/api/signals/export`.
+#[derive(Debug, Clone, Deserialize)]
+pub struct SettledSignalRow {
+    pub id: i64,
+    pub pair: String,
+    pub signal_type: String,
+    pub level: u32,
+    pub entry: Option<f64>,
+    pub stop_losses: Option<Vec<StopLoss>>,
+    pub targets: Option<Vec<Target>>,
+    pub risk_reward: Option<Vec<f64>>,
+    pub status: String,
+    pub settled_price: Option<f64>,
+    pub created_at: Option<String>,
+    pub settled_at: Option<String>,
+}
+
+/// How the time part of `signal_key` is derived, chosen by `SIGNAL_ID_BUCKET_SECS`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum SignalIdMode {
+    /// The signal's creation time in ms: every detection gets its own row.
+    #[default]
+    Timestamp,
+    /// The creation time rounded down to a multiple of `bucket_secs`, so re-detecting the
+    /// same pattern within one bucket produces the same key and the upsert collapses it
+    /// into the existing row.
+    Bucketed { bucket_secs: u64 },
+}
+
+/// Client-generated idempotency key, stored in the unique `signal_id` column:
+/// `PAIR:TYPE:L<level>:<ms>:<pattern sequence>`.
+pub fn signal_key(signal: &crate::tracker::ActiveSignal, mode: SignalIdMode) -> String {
+    let time_ms = match mode {
+        SignalIdMode::Timestamp => signal.created_at,
+        SignalIdMode::Bucketed { bucket_secs } => {
+            let bucket_ms = (bucket_secs.max(1) * 1000) as i64;
+            signal.created_at - signal.created_at.rem_euclid(bucket_ms)
+        }
+    };
+    let sequence: Vec<String> = signal.pattern_sequence.iter().map(|v| v.to_string()).collect();
+    format!(
+        "{}:{}:L{}:{}:{}",
+        signal.pair, signal.signal_type, signal.level, time_ms, sequence.join(",")
+    )
+}
+
+/// `box_details` column payload. The frontend reads this column directly and expects the
+/// original snake_case keys, which `BoxDetail`'s camelCase wire format no longer produces.
+pub fn box_details_row(details: &[BoxDetail]) -> JsonValue {
+    details
+        .iter()
+        .map(|b| serde_json::json!({"integer_value": b.integer_value, "high": b.high, "low": b.low, "value": b.value}))
+        .collect()
 }
 
 impl SupabaseClient {
     pub fn new(url: &str, service_key: &str) -> Self {
         Self {
-            client: Client::new(),
+            client: crate::http::client(crate::http::DEFAULT_TIMEOUT),
             url: url.to_string(),
             service_key: service_key.to_string(),
+            last_success_ms: Arc::new(AtomicI64::new(0)),
+            max_attempts: DEFAULT_MAX_ATTEMPTS,
+            signal_id_mode: SignalIdMode::default(),
+        }
+    }
+
+    /// Sends requests through `client`, e.g. to share one connection pool across components.
+    pub fn with_client(mut self, client: Client) -> Self {
+        self.client = client;
+        self
+    }
+
+    /// How inserted signals' `signal_id` keys are derived; defaults to `Timestamp`.
+    pub fn with_signal_id_mode(mut self, mode: SignalIdMode) -> Self {
+        self.signal_id_mode = mode;
+        self
+    }
+
+    /// Tries per request for retryable failures; clamped to at least 1.
+    pub fn with_max_attempts(mut self, max_attempts: u32) -> Self {
+        self.max_attempts = max_attempts.max(1);
+        self
+    }
+
+    /// Sends the request built by `build`, retrying timeouts, transport errors and 5xx with
+    /// exponential backoff (250ms doubling, capped at 4s). Only used for requests that are
+    /// safe to repeat: reads, updates keyed by id, and the upserting insert.
+    async fn send_with_retry(
+        &self,
+        what: &str,
+        build: impl Fn() -> RequestBuilder,
+    ) -> Result<Response, SupabaseError> {
+        let mut delay = RETRY_BASE_DELAY;
+        let mut attempt = 1;
+        loop {
+            let result = match build().send().await {
+                Ok(response) => check_status(response).await,
+                Err(e) => Err(e.into()),
+            };
+            match result {
+                Err(e) if e.is_retryable() && attempt < self.max_attempts => {
+                    warn!(
+                        "[Supabase] {} failed (attempt {}/{}), retrying in {:?}: {}",
+                        what, attempt, self.max_attempts, delay, e
+                    );
+                    tokio::time::sleep(delay).await;
+                    delay = (delay * 2).min(RETRY_MAX_DELAY);
+                    attempt += 1;
+                }
+                result => return result,
+            }
+        }
+    }
+
+    fn record_success(&self) {
+        self.last_success_ms.store(Utc::now().timestamp_millis(), Ordering::Relaxed);
+    }
+
+    /// When Supabase last answered a request successfully, if ever.
+    pub fn last_success_ms(&self) -> Option<i64> {
+        let ms = self.last_success_ms.load(Ordering::Relaxed);
+        (ms > 0).then_some(ms)
+    }
+
+    /// Cheapest possible read, used to keep `last_success_ms` fresh while no signals are
+    /// being written.
+    pub async fn ping(&self) -> Result<(), SupabaseError> {
+        let response = self
+            .client
+            .get(format!("{}/rest/v1/signals", self.url))
+            .header("apikey", &self.service_key)
+            .header("Authorization", format!("Bearer {}", self.service_key))
+            .query(&[("select", "id"), ("limit", "1")])
+            .send()
+            .await;
+        let result = match response {
+            Ok(response) => check_status(response).await.map(drop),
+            Err(e) => Err(e.into()),
+        };
+        if let Err(e) = result {
+            warn!("[Supabase] Ping failed: {}", e);
+            return Err(e);
         }
+        self.record_success();
+        Ok(())
     }
 
+    #[allow(dead_code)]
     fn timestamp_ms_to_iso_string(ts_ms: i64) -> String {
         let seconds = ts_ms / 1000;
         let nanos = ((ts_ms % 1000) * 1_000_000) as u32;
@@ -37,59 +283,54 @@ impl SupabaseClient {
     pub async fn insert_active_signal(
         &self,
         signal: &crate::tracker::ActiveSignal,
-    ) -> Result<i64, reqwest::Error> {
+    ) -> Result<i64, SupabaseError> {
         let payload = serde_json::json!({
             "pair": signal.pair,
             "signal_type": signal.signal_type.to_string(),
             "level": signal.level as i32,
             "pattern_sequence": signal.pattern_sequence,
-            "box_details": signal.box_details,
+            "box_details": box_details_row(&signal.box_details),
             "entry": signal.entry,
             "stop_losses": signal.stop_losses,
             "targets": signal.targets,
             "risk_reward": signal.risk_reward,
             "status": "active",
             "subscribers": JsonValue::Null,
+            "signal_id": signal_key(signal, self.signal_id_mode),
         });
 
+        // Upsert on the client-side key so a retry after a lost response returns the
+        // existing row instead of inserting a duplicate
         let response = self
-            .client
-            .post(&format!("{}/rest/v1/signals", self.url))
-            .header("apikey", &self.service_key)
-            .header("Authorization", format!("Bearer {}", self.service_key))
-            .header("Content-Type", "application/json")
-            .header("Prefer", "return=representation")
-            .json(&payload)
-            .send()
-            .await?;
-
-        let response = response.error_for_status().map_err(|e| {
+            .send_with_retry("Insert signal", || {
+                self.client
+                    .post(format!("{}/rest/v1/signals", self.url))
+                    .header("apikey", &self.service_key)
+                    .header("Authorization", format!("Bearer {}", self.service_key))
+                    .header("Content-Type", "application/json")
+                    .header("Prefer", "return=representation,resolution=merge-duplicates")
+                    .query(&[("on_conflict", "signal_id")])
+                    .json(&payload)
+            })
+            .await
+            .map_err(|e| {
             let pair = payload.get("pair").and_then(|v| v.as_str()).unwrap_or("unknown");
             warn!("[Supabase] Failed to insert signal for {}: {}", pair, e);
             e
         })?;
 
         let inserted: serde_json::Value = response.json().await?;
-        let id = match inserted
+        let Some(id) = inserted
             .as_array()
             .and_then(|arr| arr.first())
             .and_then(|row| row.get("id"))
             .and_then(|v| v.as_i64())
-        {
-            Some(id) => id,
-            None => {
-                warn!("[Supabase] Insert succeeded but no id returned");
-                // Create an error by making a request that will fail
-                return Err(
-                    self.client
-                        .get("http://invalid-url-that-will-fail.example.com")
-                        .send()
-                        .await
-                        .expect_err("This should always fail")
-                );
-            }
+        else {
+            warn!("[Supabase] Insert succeeded but no id returned");
+            return Err(SupabaseError::Decode("insert returned no id".to_string()));
         };
 
+        self.record_success();
         let pair = payload.get("pair").and_then(|v| v.as_str()).unwrap_or("unknown");
         let signal_type = payload.get("signal_type").and_then(|v| v.as_str()).unwrap_or("unknown");
         let level = payload.get("level").and_then(|v| v.as_i64()).unwrap_or(0);
@@ -100,45 +341,181 @@ impl SupabaseClient {
         Ok(id)
     }
 
+    pub async fn fetch_active_signals(&self) -> Result<Vec<SignalRow>, SupabaseError> {
+        let response = self
+            .send_with_retry("Fetch active signals", || {
+                self.client
+                    .get(format!("{}/rest/v1/signals", self.url))
+                    .header("apikey", &self.service_key)
+                    .header("Authorization", format!("Bearer {}", self.service_key))
+                    .query(&[
+                        ("status", "eq.active"),
+                        ("select", "id,pair,signal_type,level,entry,stop_losses,targets,risk_reward,pattern_sequence,box_details,created_at,water_mark"),
+                    ])
+            })
+            .await
+            .map_err(|e| {
+                warn!("[Supabase] Failed to fetch active signals: {}", e);
+                e
+            })?;
+
+        let rows: Vec<SignalRow> = response.json().await?;
+        self.record_success();
+        info!("[Supabase] Fetched {} active signal(s)", rows.len());
+        Ok(rows)
+    }
+
+    /// The full `signals` row with this id, every column included, or `None` if there is none.
+    pub async fn fetch_signal(&self, signal_id: i64) -> Result<Option<serde_json::Value>, SupabaseError> {
+        let response = self
+            .send_with_retry("Fetch signal", || {
+                self.client
+                    .get(format!("{}/rest/v1/signals", self.url))
+                    .header("apikey", &self.service_key)
+                    .header("Authorization", format!("Bearer {}", self.service_key))
+                    .query(&[("id", format!("eq.{}", signal_id)), ("select", "*".to_string()), ("limit", "1".to_string())])
+            })
+            .await
+            .map_err(|e| {
+                warn!("[Supabase] Failed to fetch signal {}: {}", signal_id, e);
+                e
+            })?;
+
+        let rows: Vec<serde_json::Value> = response.json().await?;
+        self.record_success();
+        Ok(rows.into_iter().next())
+    }
+
+    /// One page of non-active signals settled in `[from, to)`, oldest settlement first.
+    /// Callers page with `offset` until a short page comes back.
+    pub async fn fetch_settled_signals(
+        &self,
+        from: &str,
+        to: &str,
+        offset: usize,
+        limit: usize,
+    ) -> Result<Vec<SettledSignalRow>, SupabaseError> {
+        let response = self
+            .send_with_retry("Fetch settled signals", || {
+                self.client
+                    .get(format!("{}/rest/v1/signals", self.url))
+                    .header("apikey", &self.service_key)
+                    .header("Authorization", format!("Bearer {}", self.service_key))
+                    .query(&[
+                        ("status", "neq.active".to_string()),
+                        ("settled_at", format!("gte.{}", from)),
+                        ("settled_at", format!("lt.{}", to)),
+                        ("select", "id,pair,signal_type,level,entry,stop_losses,targets,risk_reward,status,settled_price,created_at,settled_at".to_string()),
+                        ("order", "settled_at.asc,id.asc".to_string()),
+                        ("offset", offset.to_string()),
+                        ("limit", limit.to_string()),
+                    ])
+            })
+            .await
+            .map_err(|e| {
+                warn!("[Supabase] Failed to fetch settled signals: {}", e);
+                e
+            })?;
+
+        let rows: Vec<SettledSignalRow> = response.json().await?;
+        self.record_success();
+        Ok(rows)
+    }
+
     pub async fn update_signal_status(
         &self,
         signal_id: i64,
-        status: &str,
-    ) -> Result<(), reqwest::Error> {
-        let update = UpdateSignalStatus {
-            status: status.to_string(),
-        };
+        update: &UpdateSignalStatus,
+    ) -> Result<(), SupabaseError> {
+        self.send_with_retry("Update signal status", || {
+            self.client
+                .patch(format!("{}/rest/v1/signals", self.url))
+                .header("apikey", &self.service_key)
+                .header("Authorization", format!("Bearer {}", self.service_key))
+                .header("Content-Type", "application/json")
+                .header("Prefer", "return=minimal")
+                .query(&[("id", format!("eq.{}", signal_id))])
+                .json(update)
+        })
+        .await
+        .map_err(|e| {
+            warn!("[Supabase] Failed to update signal {} status: {}", signal_id, e);
+            e
+        })?;
+        self.record_success();
+        info!(
+            "[Supabase] Updated signal {} status to {} ({})",
+            signal_id, update.status, update.settlement_reason
+        );
+        Ok(())
+    }
 
-        let response = self
-            .client
-            .patch(&format!("{}/rest/v1/signals", self.url))
-            .header("apikey", &self.service_key)
-            .header("Authorization", format!("Bearer {}", self.service_key))
-            .header("Content-Type", "application/json")
-            .header("Prefer", "return=minimal")
-            .query(&[("id", format!("eq.{}", signal_id))])
-            .json(&update)
-            .send()
-            .await?;
+    pub async fn update_signal_realized(
+        &self,
+        signal_id: i64,
+        realized_fraction: f64,
+    ) -> Result<(), SupabaseError> {
+        let update = serde_json::json!({
+            "realized_fraction": realized_fraction,
+        });
 
-        if response.status().is_success() {
-            info!(
-                "[Supabase] Updated signal {} status to {}",
-                signal_id, status
-            );
-        } else {
-            let status = response.status();
-            let body = response.text().await.unwrap_or_default();
-            warn!("[Supabase] Failed to update signal {} status: {} - {}", signal_id, status, body);
-        }
+        self.send_with_retry("Update realized fraction", || {
+            self.client
+                .patch(format!("{}/rest/v1/signals", self.url))
+                .header("apikey", &self.service_key)
+                .header("Authorization", format!("Bearer {}", self.service_key))
+                .header("Content-Type", "application/json")
+                .header("Prefer", "return=minimal")
+                .query(&[("id", format!("eq.{}", signal_id))])
+                .json(&update)
+        })
+        .await
+        .map_err(|e| {
+            warn!("[Supabase] Failed to update signal {} realized fraction: {}", signal_id, e);
+            e
+        })?;
+        self.record_success();
+        info!(
+            "[Supabase] Updated signal {} realized fraction to {:.2}",
+            signal_id, realized_fraction
+        );
+        Ok(())
+    }
+
+    /// Persists a trailing stop's water mark so hydration resumes the stop where it was.
+    pub async fn update_signal_water_mark(
+        &self,
+        signal_id: i64,
+        water_mark: f64,
+    ) -> Result<(), SupabaseError> {
+        let update = serde_json::json!({
+            "water_mark": water_mark,
+        });
 
+        self.send_with_retry("Update water mark", || {
+            self.client
+                .patch(format!("{}/rest/v1/signals", self.url))
+                .header("apikey", &self.service_key)
+                .header("Authorization", format!("Bearer {}", self.service_key))
+                .header("Content-Type", "application/json")
+                .header("Prefer", "return=minimal")
+                .query(&[("id", format!("eq.{}", signal_id))])
+                .json(&update)
+        })
+        .await
+        .map_err(|e| {
+            warn!("[Supabase] Failed to update signal {} water mark: {}", signal_id, e);
+            e
+        })?;
+        self.record_success();
+        debug!("[Supabase] Updated signal {} water mark to {:.5}", signal_id, water_mark);
         Ok(())
     }
 
     pub async fn update_signal_hits(
         &self,
-        signal_id: i64,
-    ) -> Result<(), reqwest::Error> {
+        _signal_id: i64,
+    ) -> Result<(), SupabaseError> {
         // This method is called after hits are updated in memory
         // We need to fetch the signal from active tracking and update it
         // For now, we'll update targets and stop_losses directly
@@ -153,37 +530,34 @@ impl SupabaseClient {
         signal_id: i64,
         targets: &[crate::types::Target],
         stop_losses: &[crate::types::StopLoss],
-    ) -> Result<(), reqwest::Error> {
+    ) -> Result<(), SupabaseError> {
         let update = serde_json::json!({
             "targets": targets,
             "stop_losses": stop_losses,
         });
 
-        let response = self
-            .client
-            .patch(&format!("{}/rest/v1/signals", self.url))
-            .header("apikey", &self.service_key)
-            .header("Authorization", format!("Bearer {}", self.service_key))
-            .header("Content-Type", "application/json")
-            .header("Prefer", "return=minimal")
-            .query(&[("id", format!("eq.{}", signal_id))])
-            .json(&update)
-            .send()
-            .await?;
-
-        if response.status().is_success() {
-            let targets_hit = targets.iter().filter(|t| t.timestamp.is_some()).count();
-            let stop_hit = stop_losses.first().and_then(|sl| sl.timestamp.as_ref()).is_some();
-            info!(
-                "[Supabase] Updated signal {}: {}/{} targets hit, stop loss hit: {}",
-                signal_id, targets_hit, targets.len(), stop_hit
-            );
-        } else {
-            let status = response.status();
-            let body = response.text().await.unwrap_or_default();
-            warn!("[Supabase] Failed to update signal {}: {} - {}", signal_id, status, body);
-        }
-
+        self.send_with_retry("Update targets and stops", || {
+            self.client
+                .patch(format!("{}/rest/v1/signals", self.url))
+                .header("apikey", &self.service_key)
+                .header("Authorization", format!("Bearer {}", self.service_key))
+                .header("Content-Type", "application/json")
+                .header("Prefer", "return=minimal")
+                .query(&[("id", format!("eq.{}", signal_id))])
+                .json(&update)
+        })
+        .await
+        .map_err(|e| {
+            warn!("[Supabase] Failed to update signal {}: {}", signal_id, e);
+            e
+        })?;
+        self.record_success();
+        let targets_hit = targets.iter().filter(|t| t.timestamp.is_some()).count();
+        let stop_hit = stop_losses.first().and_then(|sl| sl.timestamp.as_ref()).is_some();
+        info!(
+            "[Supabase] Updated signal {}: {}/{} targets hit, stop loss hit: {}",
+            signal_id, targets_hit, targets.len(), stop_hit
+        );
         Ok(())
     }
 }
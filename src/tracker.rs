@@ -1,6 +1,8 @@
-use crate::supabase::SupabaseClient;
-use crate::types::{BoxDetail, SignalType, Target, StopLoss};
+use crate::supabase::{SignalRow, SupabaseClient, SupabaseError, UpdateSignalStatus};
+use crate::types::{BoxDetail, SettlementReason, SignalMessage, SignalType, Target, StopLoss};
+use crate::webhook::SettlementWebhook;
 use std::collections::HashMap;
+use std::sync::atomic::{AtomicI64, AtomicU64, Ordering};
 use tokio::sync::RwLock;
 use tracing::info;
 
@@ -16,61 +18,589 @@ pub struct ActiveSignal {
     pub risk_reward: Vec<f64>,
     pub pattern_sequence: Vec<i32>,
     pub box_details: Vec<BoxDetail>,
+    /// Scaled-exit legs as (price, fraction). Empty means the signal exits all at once.
+    pub take_profit_legs: Vec<(f64, f64)>,
+    /// Number of `take_profit_legs` already filled (legs fill in order).
+    pub legs_filled: usize,
+    /// Distance the stop trails behind the best price seen. `None` keeps the stop fixed.
+    pub trail_distance: Option<f64>,
+    /// Best price seen while active: high-water for LONG, low-water for SHORT.
+    pub water_mark: Option<f64>,
+    /// Maximum favorable excursion: furthest price distance into profit from entry.
+    pub mfe: f64,
+    /// Maximum adverse excursion: furthest price distance into drawdown from entry.
+    pub mae: f64,
     pub created_at: i64,
+    /// Time (ms) after which `expire_stale` settles the signal as "expired".
+    pub expires_at: Option<i64>,
+    /// A near-stop warning has been sent; it is sent at most once per signal.
+    pub warned: bool,
+}
+
+impl ActiveSignal {
+    /// Builds a tracked signal from a generated `SignalMessage`. Returns `None` without an
+    /// entry or with an unrecognized signal type.
+    pub fn from_message(signal: &SignalMessage, created_at: i64) -> Option<Self> {
+        let signal_type: SignalType = signal.signal_type.parse().ok()?;
+
+        Some(Self {
+            id: 0, // Will be set after Supabase insert
+            pair: signal.pair.to_uppercase(),
+            signal_type,
+            level: signal.level,
+            entry: signal.entry?,
+            stop_losses: signal.stop_losses.clone(),
+            targets: signal.targets.clone(),
+            risk_reward: signal.risk_reward.clone(),
+            pattern_sequence: signal.pattern_sequence.clone(),
+            box_details: signal.box_details.clone(),
+            take_profit_legs: Vec::new(),
+            legs_filled: 0,
+            trail_distance: None,
+            water_mark: None,
+            mfe: 0.0,
+            mae: 0.0,
+            created_at,
+            expires_at: None,
+            warned: false,
+        })
+    }
+
+    /// Rebuilds a tracked signal from a persisted Supabase row.
+    pub fn from_row(row: SignalRow) -> Self {
+        let created_at = row
+            .created_at
+            .as_deref()
+            .and_then(|ts| chrono::DateTime::parse_from_rfc3339(ts).ok())
+            .map(|dt| dt.timestamp_millis())
+            .unwrap_or_else(|| chrono::Utc::now().timestamp_millis());
+
+        Self {
+            id: row.id,
+            pair: row.pair.to_uppercase(),
+            signal_type: row.signal_type,
+            level: row.level,
+            entry: row.entry,
+            stop_losses: row.stop_losses.unwrap_or_default(),
+            targets: row.targets.unwrap_or_default(),
+            risk_reward: row.risk_reward.unwrap_or_default(),
+            pattern_sequence: row.pattern_sequence.unwrap_or_default(),
+            box_details: row.box_details.unwrap_or_default(),
+            take_profit_legs: Vec::new(),
+            legs_filled: 0,
+            trail_distance: None,
+            water_mark: row.water_mark,
+            mfe: 0.0,
+            mae: 0.0,
+            created_at,
+            expires_at: None,
+            warned: false,
+        }
+    }
+
+    /// Rebuilds the message this signal was forwarded as. Confidence is rescored from the
+    /// stored fields; the full box snapshot and suggested size aren't kept, so the snapshot
+    /// falls back to the matched boxes' values and the size is left out.
+    pub fn to_message(&self) -> SignalMessage {
+        SignalMessage {
+            id: Some(self.id),
+            pair: self.pair.clone(),
+            signal_type: self.signal_type.to_string(),
+            level: self.level,
+            pattern_sequence: self.pattern_sequence.clone(),
+            box_details: self.box_details.clone(),
+            complete_box_snapshot: self.box_details.iter().map(|b| b.integer_value).collect(),
+            entry: Some(self.entry),
+            stop_losses: self.stop_losses.clone(),
+            targets: self.targets.clone(),
+            risk_reward: self.risk_reward.clone(),
+            confidence: crate::signal::score(self.level, self.pattern_sequence.len(), self.risk_reward.last().copied()),
+            suggested_size: None,
+        }
+    }
+
+    /// Widens `mfe`/`mae` to include `price`.
+    pub fn record_excursion(&mut self, price: f64) {
+        let moved = match self.signal_type {
+            SignalType::LONG => price - self.entry,
+            SignalType::SHORT => self.entry - price,
+        };
+        self.mfe = self.mfe.max(moved);
+        self.mae = self.mae.max(-moved);
+    }
+
+    /// Fraction of the position still open after the filled take-profit legs.
+    /// Splits the exit across the targets: `fractions[i]` of the position closes at target
+    /// `i`, and whatever is left closes at the final target. Signals with explicit legs or a
+    /// single target keep their exit. Targets already stamped as hit count as filled legs, so
+    /// a hydrated signal resumes where it was.
+    pub fn scale_out(&mut self, fractions: &[f64]) {
+        if fractions.is_empty() || !self.take_profit_legs.is_empty() || self.targets.len() < 2 {
+            return;
+        }
+        let (last, intermediate) = self.targets.split_last().expect("at least two targets");
+        let mut legs: Vec<(f64, f64)> = intermediate.iter().zip(fractions).map(|(t, &f)| (t.price, f)).collect();
+        let rest = 1.0 - legs.iter().map(|&(_, f)| f).sum::<f64>();
+        if rest > 1e-9 {
+            legs.push((last.price, rest));
+        }
+        self.legs_filled = self.targets.iter().take(legs.len()).take_while(|t| t.timestamp.is_some()).count();
+        self.take_profit_legs = legs;
+    }
+
+    /// Trails the stop `risk_multiple` times the entry-to-initial-stop distance behind the
+    /// best price. Signals that already trail, or have no stop, are left as they are.
+    pub fn trail_by(&mut self, risk_multiple: f64) {
+        if self.trail_distance.is_some() {
+            return;
+        }
+        if let Some(stop) = self.stop_losses.first() {
+            self.trail_distance = Some(risk_multiple * (self.entry - stop.price).abs());
+        }
+    }
+
+    pub fn remaining_fraction(&self) -> f64 {
+        let realized: f64 = self.take_profit_legs.iter().take(self.legs_filled).map(|&(_, f)| f).sum();
+        (1.0 - realized).max(0.0)
+    }
+
+    /// How far `price` has moved from entry toward the initial stop, as a fraction of the
+    /// entry-to-stop distance: 0 at entry, 1 at the stop, negative in profit. 0 without a stop.
+    pub fn stop_progress(&self, price: f64) -> f64 {
+        let Some(stop) = self.stop_losses.first().map(|sl| sl.price) else {
+            return 0.0;
+        };
+        let distance = (self.entry - stop).abs();
+        if distance == 0.0 {
+            return 0.0;
+        }
+        match self.signal_type {
+            SignalType::LONG => (self.entry - price) / distance,
+            SignalType::SHORT => (price - self.entry) / distance,
+        }
+    }
+
+    /// Effective trailing stop derived from the water mark, if trailing is enabled.
+    pub fn trailing_stop(&self) -> Option<f64> {
+        let trail = self.trail_distance?;
+        let water_mark = self.water_mark?;
+        Some(match self.signal_type {
+            SignalType::LONG => water_mark - trail,
+            SignalType::SHORT => water_mark + trail,
+        })
+    }
 }
 
 #[derive(Debug)]
 pub struct Settlement {
     pub signal: ActiveSignal,
     pub status: &'static str,
+    pub reason: SettlementReason,
+    pub settled_price: f64,
+    /// Fraction of the position realized by this settlement.
+    pub fraction: f64,
+    /// Fraction still open afterwards; 0.0 once the signal has been removed from tracking.
+    pub remaining: f64,
+}
+
+impl Settlement {
+    pub fn is_final(&self) -> bool {
+        self.remaining <= 0.0
+    }
+
+    /// Supabase row update for a final settlement, including the signal's MFE/MAE and a
+    /// `settled_at` of now.
+    pub fn status_update(&self) -> UpdateSignalStatus {
+        UpdateSignalStatus {
+            status: self.status.to_string(),
+            settlement_reason: self.reason,
+            mfe: self.signal.mfe,
+            mae: self.signal.mae,
+            settled_price: self.settled_price,
+            settled_at: chrono::Utc::now().to_rfc3339(),
+        }
+    }
+}
+
+/// Price recorded for a settlement triggered by `check_price`, chosen by `SETTLEMENT_MODEL`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum SettlementModel {
+    /// The level that was crossed (stop, trailing stop, target or take-profit leg), even
+    /// when the price gapped through it.
+    #[default]
+    Touch,
+    /// The price of the update that crossed the level, including any overshoot.
+    Mark,
+}
+
+impl std::fmt::Display for SettlementModel {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self { Self::Touch => "touch", Self::Mark => "mark" })
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ParseSettlementModelError(pub String);
+
+impl std::fmt::Display for ParseSettlementModelError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "unknown settlement model: {:?} (expected touch or mark)", self.0)
+    }
+}
+
+impl std::error::Error for ParseSettlementModelError {}
+
+impl std::str::FromStr for SettlementModel {
+    type Err = ParseSettlementModelError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if s.eq_ignore_ascii_case("touch") {
+            Ok(Self::Touch)
+        } else if s.eq_ignore_ascii_case("mark") {
+            Ok(Self::Mark)
+        } else {
+            Err(ParseSettlementModelError(s.to_string()))
+        }
+    }
+}
+
+impl SettlementModel {
+    fn settled_price(self, level: f64, current_price: f64) -> f64 {
+        match self {
+            Self::Touch => level,
+            Self::Mark => current_price,
+        }
+    }
 }
 
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ParseTakeProfitFractionsError(pub String);
+
+impl std::fmt::Display for ParseTakeProfitFractionsError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "invalid take-profit fractions: {:?} (expected comma-separated fractions in (0, 1] summing to at most 1)",
+            self.0
+        )
+    }
+}
+
+impl std::error::Error for ParseTakeProfitFractionsError {}
+
+/// Parses `TAKE_PROFIT_FRACTIONS`, e.g. `0.5,0.3`: the fraction of the position closed at
+/// each target before the last. Every fraction must be in `(0, 1]` and together they may
+/// not exceed the whole position.
+pub fn parse_take_profit_fractions(list: &str) -> Result<Vec<f64>, ParseTakeProfitFractionsError> {
+    let err = || ParseTakeProfitFractionsError(list.to_string());
+    let fractions = list
+        .split(',')
+        .map(|f| f.trim().parse::<f64>().ok().filter(|f| f.is_finite() && *f > 0.0 && *f <= 1.0))
+        .collect::<Option<Vec<f64>>>()
+        .ok_or_else(err)?;
+    if fractions.iter().sum::<f64>() > 1.0 + 1e-9 {
+        return Err(err());
+    }
+    Ok(fractions)
+}
+
+/// Active signal counts split by level and by direction (`LONG`/`SHORT`).
+#[derive(Debug, Clone, Default, PartialEq, Eq, serde::Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ActiveBreakdown {
+    pub by_level: HashMap<u32, usize>,
+    pub by_type: HashMap<String, usize>,
+}
+
+/// Rows older than this are expired rather than re-tracked on hydration.
+pub const DEFAULT_MAX_HYDRATION_AGE: std::time::Duration = std::time::Duration::from_secs(7 * 24 * 3600);
+
 pub struct SignalTracker {
     active: RwLock<HashMap<String, Vec<ActiveSignal>>>,
-    supabase: SupabaseClient,
+    /// `None` keeps tracking purely in memory (backtests) and assigns local ids.
+    supabase: Option<SupabaseClient>,
+    next_local_id: AtomicI64,
+    /// Cap on concurrently active signals per pair; `None` is unlimited.
+    max_per_pair: Option<usize>,
+    rejected: AtomicU64,
+    /// Last price seen per pair, used to settle signals that expire between ticks.
+    last_prices: RwLock<HashMap<String, f64>>,
+    webhook: Option<SettlementWebhook>,
+    settlement_model: SettlementModel,
+    /// Active rows older than this are expired on hydration; `None` reloads them all.
+    max_hydration_age_ms: Option<i64>,
+    /// Fraction of the entry-to-stop distance that triggers a near-stop warning.
+    near_stop_fraction: Option<f64>,
+    /// Signals per pair between passing the cap check and being tracked, i.e. awaiting
+    /// their Supabase insert. Only incremented under the `active` write lock.
+    in_flight: std::sync::Mutex<HashMap<String, usize>>,
+    /// Scaled exits applied to every tracked signal; see `ActiveSignal::scale_out`.
+    take_profit_fractions: Vec<f64>,
+    /// (minimum level, risk multiple) of signals that get a trailing stop.
+    trailing_stop: Option<(u32, f64)>,
 }
 
 impl SignalTracker {
-    pub fn new(supabase: SupabaseClient) -> Self {
+    pub fn new(supabase: Option<SupabaseClient>, max_per_pair: Option<usize>) -> Self {
         Self {
             active: RwLock::new(HashMap::new()),
             supabase,
+            next_local_id: AtomicI64::new(1),
+            max_per_pair,
+            rejected: AtomicU64::new(0),
+            last_prices: RwLock::new(HashMap::new()),
+            webhook: None,
+            settlement_model: SettlementModel::default(),
+            max_hydration_age_ms: Some(DEFAULT_MAX_HYDRATION_AGE.as_millis() as i64),
+            near_stop_fraction: None,
+            in_flight: std::sync::Mutex::new(HashMap::new()),
+            take_profit_fractions: Vec::new(),
+            trailing_stop: None,
+        }
+    }
+
+    /// Also POST every final settlement to `webhook`, independently of Supabase.
+    pub fn with_settlement_webhook(mut self, webhook: SettlementWebhook) -> Self {
+        self.webhook = Some(webhook);
+        self
+    }
+
+    /// Price to record when `check_price` settles a signal; defaults to `Touch`.
+    pub fn with_settlement_model(mut self, model: SettlementModel) -> Self {
+        self.settlement_model = model;
+        self
+    }
+
+    /// Warn once per signal when price covers `fraction` (in `(0, 1)`) of the distance from
+    /// entry to the initial stop without reaching it. The warning goes to the settlement
+    /// webhook as a `near_stop` event; the signal stays active.
+    pub fn with_near_stop_warning(mut self, fraction: f64) -> Self {
+        self.near_stop_fraction = (fraction > 0.0 && fraction < 1.0).then_some(fraction);
+        self
+    }
+
+    /// Close the position in legs across the targets instead of all at the final target;
+    /// `fractions` come from `parse_take_profit_fractions`. Applies to signals added or
+    /// hydrated afterwards.
+    pub fn with_take_profit_fractions(mut self, fractions: Vec<f64>) -> Self {
+        self.take_profit_fractions = fractions;
+        self
+    }
+
+    /// Give signals of `min_level` and above a stop that trails the best price by
+    /// `risk_multiple` times their initial risk (see `ActiveSignal::trail_by`). Applies to
+    /// signals added or hydrated afterwards; a non-positive multiple disables trailing.
+    pub fn with_trailing_stop(mut self, min_level: u32, risk_multiple: f64) -> Self {
+        self.trailing_stop = (risk_multiple > 0.0).then_some((min_level, risk_multiple));
+        self
+    }
+
+    /// Applies the configured exit management (scaled exits, trailing stop) to a signal
+    /// about to be tracked.
+    fn manage_exits(&self, signal: &mut ActiveSignal) {
+        signal.scale_out(&self.take_profit_fractions);
+        if let Some((min_level, risk_multiple)) = self.trailing_stop {
+            if signal.level >= min_level {
+                signal.trail_by(risk_multiple);
+            }
+        }
+    }
+
+    /// Rows still active in Supabase but created longer ago than `max_age` most likely
+    /// missed their settling tick; hydration expires them instead of tracking them again.
+    /// `None` hydrates every row regardless of age.
+    pub fn with_max_hydration_age(mut self, max_age: Option<std::time::Duration>) -> Self {
+        self.max_hydration_age_ms = max_age.map(|age| age.as_millis() as i64);
+        self
+    }
+
+    /// Persists a final settlement: the webhook is dispatched first, on its own task, so it
+    /// runs concurrently with the Supabase update and can't hold it up.
+    async fn persist_settlement(&self, settlement: &Settlement) {
+        if let Some(webhook) = &self.webhook {
+            webhook.notify(settlement);
+        }
+        let Some(supabase) = &self.supabase else {
+            info!("[Tracker] No Supabase: would update signal {} status to {}", settlement.signal.id, settlement.status);
+            return;
+        };
+        // Transient failures were already retried inside SupabaseClient
+        match supabase.update_signal_status(settlement.signal.id, &settlement.status_update()).await {
+            Ok(()) => {}
+            Err(e @ SupabaseError::Http { .. }) if !e.is_retryable() => {
+                // A 4xx won't fix itself: the row or schema needs attention
+                tracing::error!(
+                    "[Tracker] Supabase rejected settlement of signal {} as {}: {}",
+                    settlement.signal.id, settlement.status, e
+                );
+            }
+            Err(e) => {
+                tracing::warn!("[Tracker] Failed to update signal status in Supabase: {}", e);
+            }
+        }
+    }
+
+    /// The Supabase client, or `None` when tracking is in-memory only.
+    pub fn supabase(&self) -> Option<&SupabaseClient> {
+        self.supabase.as_ref()
+    }
+
+    /// Reloads signals still marked active in Supabase so they keep settling after a restart.
+    /// Rows older than the max hydration age are settled as "expired" at their entry (no
+    /// price has been seen yet) so they stop showing as active.
+    pub async fn hydrate_from_supabase(&self) -> usize {
+        let Some(supabase) = &self.supabase else {
+            return 0;
+        };
+
+        let rows = match supabase.fetch_active_signals().await {
+            Ok(rows) => rows,
+            Err(e) => {
+                tracing::warn!("[Tracker] Failed to hydrate active signals from Supabase: {}", e);
+                return 0;
+            }
+        };
+
+        let cutoff = self.max_hydration_age_ms.map(|age| chrono::Utc::now().timestamp_millis() - age);
+        let mut stale = Vec::new();
+        let mut hydrated = 0;
+        {
+            let mut active = self.active.write().await;
+            for row in rows {
+                let mut signal = ActiveSignal::from_row(row);
+                self.manage_exits(&mut signal);
+                if cutoff.is_some_and(|cutoff| signal.created_at < cutoff) {
+                    stale.push(signal);
+                    continue;
+                }
+                let signals = active.entry(signal.pair.clone()).or_default();
+                if signals.iter().any(|s| s.id == signal.id) {
+                    continue;
+                }
+                signals.push(signal);
+                hydrated += 1;
+            }
+        }
+
+        info!("[Tracker] Hydrated {} active signal(s) from Supabase", hydrated);
+        if !stale.is_empty() {
+            tracing::warn!(
+                "[Tracker] Expiring {} active signal(s) older than the max hydration age instead of re-tracking them",
+                stale.len()
+            );
+        }
+        for signal in stale {
+            let settlement = Settlement {
+                fraction: signal.remaining_fraction(),
+                settled_price: signal.entry,
+                signal,
+                status: "expired",
+                reason: SettlementReason::Expired,
+                remaining: 0.0,
+            };
+            self.persist_settlement(&settlement).await;
         }
+        hydrated
     }
 
-    pub async fn add_signal(&self, mut signal: ActiveSignal) -> i64 {
+    /// Signals on `pair` (uppercase) counted against `max_per_pair`, active or still being
+    /// inserted, and the cap, if the count has reached it.
+    fn cap_reached(&self, active: &HashMap<String, Vec<ActiveSignal>>, pair: &str) -> Option<(usize, usize)> {
+        let max_per_pair = self.max_per_pair?;
+        let in_flight = self.in_flight.lock().unwrap().get(pair).copied().unwrap_or(0);
+        let count = active.get(pair).map_or(0, |v| v.len()) + in_flight;
+        (count >= max_per_pair).then_some((count, max_per_pair))
+    }
+
+    /// Whether `pair` is at `max_per_pair`, counting the rejection if so. Lets callers drop
+    /// a signal before any dedup state records it; `add_signal` enforces the cap either way.
+    pub async fn at_capacity(&self, pair: &str) -> bool {
+        let active = self.active.read().await;
+        let full = self.cap_reached(&active, &pair.to_uppercase()).is_some();
+        if full {
+            self.rejected.fetch_add(1, Ordering::Relaxed);
+        }
+        full
+    }
+
+    /// Starts tracking a signal and returns its id, or `None` when the pair is at
+    /// `max_per_pair` or Supabase returned the id of a signal that is already active.
+    /// The slot is reserved under the write lock before the Supabase insert, so concurrent
+    /// adds can't overshoot the cap.
+    pub async fn add_signal(&self, mut signal: ActiveSignal) -> Option<i64> {
         let pair_upper = signal.pair.to_uppercase();
         signal.pair = pair_upper.clone();
+        self.manage_exits(&mut signal);
+
+        if self.max_per_pair.is_none() {
+            return self.insert_and_track(signal).await;
+        }
+        {
+            let active = self.active.write().await;
+            if let Some((count, max_per_pair)) = self.cap_reached(&active, &pair_upper) {
+                self.rejected.fetch_add(1, Ordering::Relaxed);
+                info!(
+                    "[Tracker] Rejected {} {} L{}: {} already has {} active signal(s) (max {})",
+                    pair_upper, signal.signal_type, signal.level, pair_upper, count, max_per_pair
+                );
+                return None;
+            }
+            *self.in_flight.lock().unwrap().entry(pair_upper.clone()).or_default() += 1;
+        }
+        let id = self.insert_and_track(signal).await;
+        if let Some(count) = self.in_flight.lock().unwrap().get_mut(&pair_upper) {
+            *count -= 1;
+        }
+        id
+    }
+
+    async fn insert_and_track(&self, mut signal: ActiveSignal) -> Option<i64> {
+        let pair_upper = signal.pair.clone();
         let signal_type = signal.signal_type.to_string();
         let level = signal.level;
 
-        let id = match self
-            .supabase
-            .insert_active_signal(&signal)
-            .await
-        {
-            Ok(id) => id,
-            Err(e) => {
-                tracing::warn!("[Tracker] Failed to write signal to Supabase: {}", e);
-                return 0; // Return 0 on error - caller should handle
+        let id = match &self.supabase {
+            Some(supabase) => match supabase.insert_active_signal(&signal).await {
+                Ok(id) => id,
+                Err(e) => {
+                    tracing::warn!("[Tracker] Failed to write signal to Supabase: {}", e);
+                    return Some(0); // Return 0 on error - caller should handle
+                }
+            },
+            None => {
+                let id = self.next_local_id.fetch_add(1, Ordering::Relaxed);
+                info!("[Tracker] No Supabase: would insert {} {} L{} (local id: {})", pair_upper, signal_type, level, id);
+                id
             }
         };
 
         signal.id = id;
 
         let mut active = self.active.write().await;
-        active
-            .entry(pair_upper.clone())
-            .or_insert_with(Vec::new)
-            .push(signal);
+        let signals = active.entry(pair_upper.clone()).or_default();
+        // With bucketed signal ids the upsert can hand back a row that's already tracked
+        if id != 0 && signals.iter().any(|s| s.id == id) {
+            info!("[Tracker] {} {} L{} collapsed into already active signal {}", pair_upper, signal_type, level, id);
+            return None;
+        }
+        signals.push(signal);
 
         let total = active.values().map(|v| v.len()).sum::<usize>();
         drop(active);
         info!("[Tracker] Added active signal: {} {} L{} (id: {}, total: {})", pair_upper, signal_type, level, id, total);
-        id
+        Some(id)
     }
 
     pub async fn check_price(&self, pair: &str, current_price: f64) -> Vec<Settlement> {
+        self.check_price_range(pair, current_price, current_price, current_price).await
+    }
+
+    /// Like `check_price`, but checks stops against the adverse end of the update's
+    /// `[low, high]` range and targets against the favorable end, so a wick that touches a
+    /// level settles the signal even if `current_price` didn't cross it. If both a stop and
+    /// a target fall inside the range the stop wins, as the order within the range is
+    /// unknown. `current_price` is remembered as the pair's last price.
+    pub async fn check_price_range(&self, pair: &str, low: f64, high: f64, current_price: f64) -> Vec<Settlement> {
         let now_iso = chrono::Utc::now().to_rfc3339_opts(chrono::SecondsFormat::Micros, true);
         let pair_upper = pair.to_uppercase();
         let mut signals_to_update: Vec<i64> = Vec::new();
@@ -79,8 +609,24 @@ impl SignalTracker {
             tracing::warn!("[Tracker] Invalid price for {}: {}", pair, current_price);
             return vec![];
         }
+        let (low, high) = if low > 0.0 && low <= current_price && high >= current_price {
+            (low, high)
+        } else {
+            if low != current_price || high != current_price {
+                tracing::warn!("[Tracker] Ignoring invalid range for {}: [{}, {}] @ {}", pair, low, high, current_price);
+            }
+            (current_price, current_price)
+        };
+
+        self.last_prices.write().await.insert(pair_upper.clone(), current_price);
         
-        let to_settle: Vec<(usize, &'static str)> = {
+        let mut partial_fills: Vec<Settlement> = Vec::new();
+        let mut near_stop: Vec<Settlement> = Vec::new();
+        let mut water_marks: Vec<(i64, f64)> = Vec::new();
+        // (signal id, status, reason, fraction, settled price). Ids rather than indices: the
+        // lock is released for the Supabase updates below, and `close_by_id`/`expire_stale`
+        // can remove entries from the pair in the meantime
+        let to_settle: Vec<(i64, &'static str, SettlementReason, f64, f64)> = {
             let mut active = self.active.write().await;
             let Some(signals) = active.get_mut(&pair_upper) else {
                 tracing::debug!("[Tracker] No active signals found for pair: {} (checked: {})", pair, pair_upper);
@@ -92,118 +638,248 @@ impl SignalTracker {
                 return vec![];
             }
             
-            tracing::debug!("[Tracker] Checking {} signals for {} @ {:.5}", signals.len(), pair_upper, current_price);
+            tracing::debug!("[Tracker] Checking {} signals for {} @ {:.5} [{:.5}, {:.5}]", signals.len(), pair_upper, current_price, low, high);
 
             signals
                 .iter_mut()
-                .enumerate()
-                .filter_map(|(idx, signal)| {
+                .filter_map(|signal| {
+                    let remaining_before = signal.remaining_fraction();
+                    signal.record_excursion(low);
+                    signal.record_excursion(high);
+                    let (adverse, favorable) = match signal.signal_type {
+                        SignalType::LONG => (low, high),
+                        SignalType::SHORT => (high, low),
+                    };
+
+                    // A trailing stop that has moved past the initial stop takes precedence
+                    let mark_before = signal.water_mark.unwrap_or(signal.entry);
+                    if let Some(level) = self.check_trailing_stop(signal, adverse, favorable) {
+                        let settled_price = self.settlement_model.settled_price(level, adverse);
+                        return Some((signal.id, "trailed", SettlementReason::Trailed, remaining_before, settled_price));
+                    }
+                    if let Some(mark) = signal.water_mark.filter(|&mark| mark != mark_before) {
+                        water_marks.push((signal.id, mark));
+                    }
+
                     // Check if stop loss was hit
-                    let stop_loss_hit = self.check_stop_loss_hit(signal, current_price, &now_iso);
+                    let stop_loss_hit = self.check_stop_loss_hit(signal, adverse, &now_iso);
                     let hit_stop = stop_loss_hit;
                     
-                    // Check if any targets were hit
-                    let any_new_target_hit = self.check_target_hits(signal, current_price, &now_iso);
+                    // Check if any targets were hit; not when the stop was, since the stop wins
+                    let any_new_target_hit = !stop_loss_hit && self.check_target_hits(signal, favorable, &now_iso);
                     
                     // Collect signal IDs that need updating
                     if any_new_target_hit || stop_loss_hit {
                         signals_to_update.push(signal.id);
                     }
-                    
+
+                    if hit_stop {
+                        let targets_hit_count = signal.targets.iter().filter(|t| t.timestamp.is_some()).count();
+                        let has_partial_targets = targets_hit_count > 0 && targets_hit_count < signal.targets.len();
+                        let status = if has_partial_targets || signal.legs_filled > 0 { "partial" } else { "failed" };
+                        let level = signal.stop_losses.first().map_or(adverse, |sl| sl.price);
+                        let settled_price = self.settlement_model.settled_price(level, adverse);
+                        return Some((signal.id, status, SettlementReason::StopLoss, remaining_before, settled_price));
+                    }
+
+                    if !signal.warned && self.near_stop_fraction.is_some_and(|f| signal.stop_progress(adverse) >= f) {
+                        signal.warned = true;
+                        near_stop.push(Settlement {
+                            signal: signal.clone(),
+                            status: "near_stop",
+                            reason: SettlementReason::NearStop,
+                            settled_price: adverse,
+                            fraction: 0.0,
+                            remaining: remaining_before,
+                        });
+                    }
+
+                    // Scaled exits: legs drive the settlement instead of the final target
+                    if !signal.take_profit_legs.is_empty() {
+                        if !self.check_leg_fills(signal, favorable) {
+                            return None;
+                        }
+                        let filled = remaining_before - signal.remaining_fraction();
+                        let level = signal.take_profit_legs[signal.legs_filled - 1].0;
+                        let settled_price = self.settlement_model.settled_price(level, favorable);
+                        if signal.legs_filled == signal.take_profit_legs.len() {
+                            return Some((signal.id, "success", SettlementReason::Target, filled, settled_price));
+                        }
+                        partial_fills.push(Settlement {
+                            signal: signal.clone(),
+                            status: "leg_filled",
+                            reason: SettlementReason::Partial,
+                            settled_price,
+                            fraction: filled,
+                            remaining: signal.remaining_fraction(),
+                        });
+                        return None;
+                    }
+
                     // Determine if signal should be settled
-                    let hit_final_target = signal.targets.last().map_or(false, |target| {
+                    let final_target = signal.targets.last().map(|target| target.price).filter(|&price| {
                         match signal.signal_type {
-                            SignalType::LONG => current_price >= target.price,
-                            SignalType::SHORT => current_price <= target.price,
+                            SignalType::LONG => favorable >= price,
+                            SignalType::SHORT => favorable <= price,
                         }
                     });
-                    
-                    let targets_hit_count = signal.targets.iter().filter(|t| t.timestamp.is_some()).count();
-                    let has_partial_targets = targets_hit_count > 0 && targets_hit_count < signal.targets.len();
-                    
-                    if hit_stop {
-                        if has_partial_targets {
-                            Some((idx, "partial"))
-                        } else {
-                            Some((idx, "failed"))
-                        }
-                    } else if hit_final_target {
-                        Some((idx, "success"))
-                    } else {
-                        None
-                    }
+
+                    final_target.map(|level| {
+                        let settled_price = self.settlement_model.settled_price(level, favorable);
+                        (signal.id, "success", SettlementReason::Target, remaining_before, settled_price)
+                    })
                 })
                 .collect()
         };
 
         // Update Supabase with target hits and stop loss hits
-        {
-            let active = self.active.read().await;
-            for signal_id in signals_to_update {
-                // Find the signal in active tracking
-                if let Some(signal) = active.values()
+        if let Some(supabase) = &self.supabase {
+            // Copy the hits out so the lock isn't held across the requests
+            let updates: Vec<(i64, Vec<Target>, Vec<StopLoss>)> = {
+                let active = self.active.read().await;
+                active
+                    .values()
                     .flatten()
-                    .find(|s| s.id == signal_id)
-                {
-                    if let Err(e) = self
-                        .supabase
-                        .update_signal_targets_and_stops(signal_id, &signal.targets, &signal.stop_losses)
-                        .await
-                    {
-                        tracing::warn!("[Tracker] Failed to update signal hits in Supabase: {}", e);
-                    }
+                    .filter(|s| signals_to_update.contains(&s.id))
+                    .map(|s| (s.id, s.targets.clone(), s.stop_losses.clone()))
+                    .collect()
+            };
+            for (signal_id, targets, stop_losses) in updates {
+                if let Err(e) = supabase.update_signal_targets_and_stops(signal_id, &targets, &stop_losses).await {
+                    tracing::warn!("[Tracker] Failed to update signal hits in Supabase: {}", e);
+                }
+            }
+        }
+
+        if let Some(supabase) = &self.supabase {
+            for fill in &partial_fills {
+                let realized = 1.0 - fill.remaining;
+                if let Err(e) = supabase.update_signal_realized(fill.signal.id, realized).await {
+                    tracing::warn!("[Tracker] Failed to update realized fraction in Supabase: {}", e);
                 }
             }
+            for &(signal_id, water_mark) in &water_marks {
+                if let Err(e) = supabase.update_signal_water_mark(signal_id, water_mark).await {
+                    tracing::warn!("[Tracker] Failed to update water mark in Supabase: {}", e);
+                }
+            }
+        }
+
+        for warning in &near_stop {
+            let signal = &warning.signal;
+            info!(
+                "[Tracker] NEAR STOP: {} {} L{} (id: {}) @ {:.5}, stop {:.5}",
+                signal.pair, signal.signal_type, signal.level, signal.id, warning.settled_price,
+                signal.stop_losses.first().map_or(0.0, |sl| sl.price)
+            );
+            if let Some(webhook) = &self.webhook {
+                webhook.notify(warning);
+            }
         }
 
         if to_settle.is_empty() {
-            return vec![];
+            return partial_fills;
         }
 
-        let mut settlements = Vec::new();
+        let mut settlements = partial_fills;
         let mut active = self.active.write().await;
         let Some(signals) = active.get_mut(&pair_upper) else {
             tracing::warn!("[Tracker] Signals removed before settlement for pair: {}", pair_upper);
-            return vec![];
+            return settlements;
         };
 
-        for (idx, status) in to_settle.into_iter().rev() {
-            if idx < signals.len() {
+        for (id, status, reason, fraction, settled_price) in to_settle.into_iter().rev() {
+            // Skip signals closed or expired while the lock was released
+            if let Some(idx) = signals.iter().position(|s| s.id == id) {
                 let signal = signals.remove(idx);
                 
-                let settled_price = if status == "failed" {
-                    signal.stop_losses.first()
-                        .and_then(|sl| sl.timestamp.as_ref().map(|_| sl.price))
-                        .unwrap_or(0.0)
-                } else {
-                    signal.targets.last()
-                        .and_then(|t| t.timestamp.as_ref().map(|_| t.price))
-                        .unwrap_or(0.0)
-                };
-                
                 let targets_hit = signal.targets.iter().filter(|t| t.timestamp.is_some()).count();
                 info!(
-                    "[Tracker] SETTLED: {} {} L{} → {} @ {:.5} (targets hit: {}/{})",
-                    signal.pair, signal.signal_type, signal.level, status, settled_price,
+                    "[Tracker] SETTLED: {} {} L{} → {} ({}) @ {:.5} (targets hit: {}/{})",
+                    signal.pair, signal.signal_type, signal.level, status, reason, settled_price,
                     targets_hit,
                     signal.targets.len()
                 );
-                settlements.push(Settlement { signal, status });
+                settlements.push(Settlement { signal, status, reason, settled_price, fraction, remaining: 0.0 });
             }
         }
 
         drop(active);
 
-        for settlement in &settlements {
-            if let Err(e) = self
-                .supabase
-                .update_signal_status(settlement.signal.id, settlement.status)
-                .await
-            {
-                tracing::warn!("[Tracker] Failed to update signal status in Supabase: {}", e);
+        for settlement in settlements.iter().filter(|s| s.is_final()) {
+            self.persist_settlement(settlement).await;
+        }
+
+        settlements
+    }
+
+    /// Removes an active signal out-of-band, settling it as "manual" at the pair's
+    /// last-seen price. `None` if no active signal has this id.
+    pub async fn close_by_id(&self, signal_id: i64) -> Option<Settlement> {
+        let mut signal = {
+            let mut active = self.active.write().await;
+            let signals = active.values_mut().find(|signals| signals.iter().any(|s| s.id == signal_id))?;
+            let idx = signals.iter().position(|s| s.id == signal_id)?;
+            signals.remove(idx)
+        };
+
+        let settled_price = self.last_prices.read().await.get(&signal.pair).copied().unwrap_or(signal.entry);
+        signal.record_excursion(settled_price);
+        info!(
+            "[Tracker] MANUAL CLOSE: {} {} L{} (id: {}) @ {:.5}",
+            signal.pair, signal.signal_type, signal.level, signal.id, settled_price
+        );
+        let settlement = Settlement {
+            fraction: signal.remaining_fraction(),
+            signal,
+            status: "manual",
+            reason: SettlementReason::Manual,
+            settled_price,
+            remaining: 0.0,
+        };
+
+        self.persist_settlement(&settlement).await;
+        Some(settlement)
+    }
+
+    /// Settles every signal whose `expires_at` has passed as "expired" at the pair's last-seen price.
+    /// Runs from the sweep task, outside the pair executor; a price check in flight re-finds
+    /// its settlements by id, so removing signals here can't shift them.
+    pub async fn expire_stale(&self, now_ms: i64) -> Vec<Settlement> {
+        let last_prices = self.last_prices.read().await.clone();
+        let mut settlements = Vec::new();
+
+        {
+            let mut active = self.active.write().await;
+            for (pair, signals) in active.iter_mut() {
+                let mut idx = 0;
+                while idx < signals.len() {
+                    if signals[idx].expires_at.is_none_or(|at| at > now_ms) {
+                        idx += 1;
+                        continue;
+                    }
+                    let signal = signals.remove(idx);
+                    let settled_price = last_prices.get(pair).copied().unwrap_or(signal.entry);
+                    info!(
+                        "[Tracker] EXPIRED: {} {} L{} (id: {}) @ {:.5}",
+                        signal.pair, signal.signal_type, signal.level, signal.id, settled_price
+                    );
+                    settlements.push(Settlement {
+                        fraction: signal.remaining_fraction(),
+                        signal,
+                        status: "expired",
+                        reason: SettlementReason::Expired,
+                        settled_price,
+                        remaining: 0.0,
+                    });
+                }
             }
         }
 
+        for settlement in &settlements {
+            self.persist_settlement(settlement).await;
+        }
+
         settlements
     }
 
@@ -268,10 +944,90 @@ impl SignalTracker {
         any_new_hit
     }
 
+    /// Returns the trailing stop level if `adverse` crossed it. The stop is checked against
+    /// the water mark from before this update, then the mark advances to `favorable`, so a
+    /// single update's range never trails the stop up and knocks it out at once.
+    fn check_trailing_stop(&self, signal: &mut ActiveSignal, adverse: f64, favorable: f64) -> Option<f64> {
+        signal.trail_distance?;
+        let initial_stop = signal.stop_losses.first()?.price;
+
+        // Trailing starts from entry until a better price has been seen
+        let previous = signal.water_mark.unwrap_or(signal.entry);
+        signal.water_mark = Some(previous);
+        let hit = signal.trailing_stop().filter(|&trailing_stop| match signal.signal_type {
+            SignalType::LONG => trailing_stop > initial_stop && adverse <= trailing_stop,
+            SignalType::SHORT => trailing_stop < initial_stop && adverse >= trailing_stop,
+        });
+
+        signal.water_mark = Some(match signal.signal_type {
+            SignalType::LONG => previous.max(favorable),
+            SignalType::SHORT => previous.min(favorable),
+        });
+
+        if let Some(trailing_stop) = hit {
+            info!(
+                "[Tracker] Trailing stop hit: {} {} L{} (id: {}) trail = {:.5} @ {:.5}",
+                signal.pair, signal.signal_type, signal.level, signal.id, trailing_stop, adverse
+            );
+        }
+        hit
+    }
+
+    fn check_leg_fills(&self, signal: &mut ActiveSignal, current_price: f64) -> bool {
+        let mut any_new_fill = false;
+
+        while let Some(&(leg_price, fraction)) = signal.take_profit_legs.get(signal.legs_filled) {
+            let hit = match signal.signal_type {
+                SignalType::LONG => current_price >= leg_price,
+                SignalType::SHORT => current_price <= leg_price,
+            };
+            if !hit {
+                break;
+            }
+
+            signal.legs_filled += 1;
+            any_new_fill = true;
+            info!(
+                "[Tracker] Take-profit leg {} filled: {} {} L{} (id: {}) {:.0}% @ {:.5}",
+                signal.legs_filled, signal.pair, signal.signal_type, signal.level, signal.id, fraction * 100.0, current_price
+            );
+        }
+
+        any_new_fill
+    }
+
+    /// A copy of the active signal with this id, if it's still being tracked.
+    pub async fn find_by_id(&self, signal_id: i64) -> Option<ActiveSignal> {
+        self.active.read().await.values().flatten().find(|s| s.id == signal_id).cloned()
+    }
+
+    /// Every active signal as it was forwarded, oldest first.
+    pub async fn active_messages(&self) -> Vec<SignalMessage> {
+        let active = self.active.read().await;
+        let mut signals: Vec<&ActiveSignal> = active.values().flatten().collect();
+        signals.sort_by_key(|s| (s.created_at, s.id));
+        signals.into_iter().map(ActiveSignal::to_message).collect()
+    }
+
+    pub fn get_rejected_count(&self) -> u64 {
+        self.rejected.load(Ordering::Relaxed)
+    }
+
     pub async fn get_active_count(&self) -> usize {
         self.active.read().await.values().map(|v| v.len()).sum()
     }
 
+    /// Counts active signals by level and by type in a single pass under one read lock.
+    pub async fn get_active_breakdown(&self) -> ActiveBreakdown {
+        let active = self.active.read().await;
+        let mut breakdown = ActiveBreakdown::default();
+        for signal in active.values().flatten() {
+            *breakdown.by_level.entry(signal.level).or_default() += 1;
+            *breakdown.by_type.entry(signal.signal_type.to_string()).or_default() += 1;
+        }
+        breakdown
+    }
+
     pub async fn get_active_by_pair(&self) -> HashMap<String, usize> {
         self.active
             .read()
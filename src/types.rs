@@ -7,15 +7,153 @@ pub struct Box {
     pub value: f64,
 }
 
-#[derive(Debug, Clone)]
+impl Box {
+    /// Rejects boxes that would produce negative sizes or a direction-less value.
+    pub fn validate(&self) -> Result<(), InvalidBox> {
+        if !(self.high.is_finite() && self.low.is_finite() && self.value.is_finite()) {
+            return Err(InvalidBox::NonFinite);
+        }
+        if self.high < self.low {
+            return Err(InvalidBox::Inverted { high: self.high, low: self.low });
+        }
+        if self.value == 0.0 {
+            return Err(InvalidBox::ZeroValue);
+        }
+        Ok(())
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum InvalidBox {
+    NonFinite,
+    Inverted { high: f64, low: f64 },
+    ZeroValue,
+}
+
+impl std::fmt::Display for InvalidBox {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::NonFinite => f.write_str("non-finite high/low/value"),
+            Self::Inverted { high, low } => write!(f, "high {} below low {}", high, low),
+            Self::ZeroValue => f.write_str("zero value has no direction"),
+        }
+    }
+}
+
+impl std::error::Error for InvalidBox {}
+
+/// Field names stay snake_case, unlike the camelCase `SignalMessage`; deserializing also
+/// accepts the upstream `tickHigh`/`tickLow`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct BoxData {
     pub pair: String,
     pub boxes: Vec<Box>,
     pub price: f64,
+    /// Extremes traded since the previous update (`tickHigh`/`tickLow`), when sent.
+    #[serde(default, alias = "tickHigh", skip_serializing_if = "Option::is_none")]
+    pub tick_high: Option<f64>,
+    #[serde(default, alias = "tickLow", skip_serializing_if = "Option::is_none")]
+    pub tick_low: Option<f64>,
     pub timestamp: String,
 }
 
+impl BoxData {
+    /// `(low, high)` for settlement checks; `price` stands in for a missing side.
+    pub fn price_range(&self) -> (f64, f64) {
+        (self.tick_low.unwrap_or(self.price), self.tick_high.unwrap_or(self.price))
+    }
+}
+
+/// The `data` of a `boxUpdate`, checked by `parse_box_update`.
 #[derive(Debug, Clone)]
+pub struct ParsedUpdate {
+    pub boxes: Vec<Box>,
+    pub price: f64,
+    pub tick_high: Option<f64>,
+    pub tick_low: Option<f64>,
+    /// Upstream send time, when the frame carries one.
+    pub timestamp: Option<chrono::DateTime<chrono::Utc>>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum BoxUpdateError {
+    MissingBoxes,
+    BoxesNotArray,
+    /// An element of `boxes` isn't a `{high, low, value}` object.
+    MalformedBox { index: usize, error: String },
+    InvalidBox { index: usize, error: InvalidBox },
+    MissingPrice,
+    InvalidPrice(f64),
+    BadTimestamp(String),
+}
+
+impl std::fmt::Display for BoxUpdateError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::MissingBoxes => f.write_str("missing boxes"),
+            Self::BoxesNotArray => f.write_str("boxes is not an array"),
+            Self::MalformedBox { index, error } => write!(f, "box {} malformed: {}", index, error),
+            Self::InvalidBox { index, error } => write!(f, "box {} invalid: {}", index, error),
+            Self::MissingPrice => f.write_str("missing or non-numeric price"),
+            Self::InvalidPrice(price) => write!(f, "invalid price {}", price),
+            Self::BadTimestamp(e) => write!(f, "bad timestamp: {}", e),
+        }
+    }
+}
+
+impl std::error::Error for BoxUpdateError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Self::InvalidBox { error, .. } => Some(error),
+            _ => None,
+        }
+    }
+}
+
+/// Parses a `boxUpdate` `data` object. An empty `boxes` array is valid (nothing to
+/// detect); `tickHigh`/`tickLow` are optional and ignored unless numeric; `timestamp`
+/// is optional but must be RFC 3339 when present.
+pub fn parse_box_update(data: &serde_json::Value) -> Result<ParsedUpdate, BoxUpdateError> {
+    let raw_boxes = data
+        .get("boxes")
+        .ok_or(BoxUpdateError::MissingBoxes)?
+        .as_array()
+        .ok_or(BoxUpdateError::BoxesNotArray)?;
+    let mut boxes = Vec::with_capacity(raw_boxes.len());
+    for (index, raw) in raw_boxes.iter().enumerate() {
+        let b: Box = serde_json::from_value(raw.clone())
+            .map_err(|e| BoxUpdateError::MalformedBox { index, error: e.to_string() })?;
+        b.validate().map_err(|error| BoxUpdateError::InvalidBox { index, error })?;
+        boxes.push(b);
+    }
+
+    let price = data.get("price").and_then(|v| v.as_f64()).ok_or(BoxUpdateError::MissingPrice)?;
+    if !price.is_finite() || price <= 0.0 {
+        return Err(BoxUpdateError::InvalidPrice(price));
+    }
+
+    let timestamp = match data.get("timestamp") {
+        None | Some(serde_json::Value::Null) => None,
+        Some(serde_json::Value::String(s)) => Some(
+            chrono::DateTime::parse_from_rfc3339(s)
+                .map_err(|e| BoxUpdateError::BadTimestamp(format!("{:?}: {}", s, e)))?
+                .to_utc(),
+        ),
+        Some(other) => return Err(BoxUpdateError::BadTimestamp(format!("expected a string, got {}", other))),
+    };
+
+    Ok(ParsedUpdate {
+        boxes,
+        price,
+        tick_high: data.get("tickHigh").and_then(|v| v.as_f64()),
+        tick_low: data.get("tickLow").and_then(|v| v.as_f64()),
+        timestamp,
+    })
+}
+
+/// Serializes as the bare value array, e.g. `[100, -86]`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(transparent)]
 pub struct TraversalPath { pub path: Vec<i32> }
 
 impl TraversalPath {
@@ -24,7 +162,7 @@ impl TraversalPath {
     pub fn signal_type(&self) -> SignalType { if self.path[0] > 0 { SignalType::LONG } else { SignalType::SHORT } }
 }
 
-#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, Default)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize, Default)]
 pub enum SignalType { #[default] LONG, SHORT }
 
 impl std::fmt::Display for SignalType {
@@ -33,7 +171,70 @@ impl std::fmt::Display for SignalType {
     }
 }
 
-#[derive(Debug, Clone)]
+/// Why a signal (or part of it) was settled; finer-grained than the settlement status.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SettlementReason {
+    StopLoss,
+    Target,
+    Trailed,
+    Expired,
+    Partial,
+    Manual,
+    /// Not a settlement: price entered the warning zone before the stop.
+    NearStop,
+}
+
+impl std::fmt::Display for SettlementReason {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            Self::StopLoss => "stop_loss",
+            Self::Target => "target",
+            Self::Trailed => "trailed",
+            Self::Expired => "expired",
+            Self::Partial => "partial",
+            Self::Manual => "manual",
+            Self::NearStop => "near_stop",
+        })
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ParseSignalTypeError(pub String);
+
+impl std::fmt::Display for ParseSignalTypeError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "unknown signal type: {:?}", self.0)
+    }
+}
+
+impl std::error::Error for ParseSignalTypeError {}
+
+/// Case-insensitive; anything other than LONG or SHORT is an error.
+impl std::str::FromStr for SignalType {
+    type Err = ParseSignalTypeError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if s.eq_ignore_ascii_case("LONG") {
+            Ok(Self::LONG)
+        } else if s.eq_ignore_ascii_case("SHORT") {
+            Ok(Self::SHORT)
+        } else {
+            Err(ParseSignalTypeError(s.to_string()))
+        }
+    }
+}
+
+impl TryFrom<&str> for SignalType {
+    type Error = ParseSignalTypeError;
+
+    fn try_from(s: &str) -> Result<Self, Self::Error> {
+        s.parse()
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
 pub struct PatternMatch {
     pub pair: String,
     pub level: u32,
@@ -42,8 +243,37 @@ pub struct PatternMatch {
     pub box_details: Vec<BoxDetail>,
 }
 
+/// Where one path value of a match was found in the caller's `boxes` slice.
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ValueSource {
+    pub value: i32,
+    /// Index of the box whose high/low ended up in `box_details`.
+    pub box_index: Option<usize>,
+    /// Every box index with this integer value; more than one means the mapping was
+    /// ambiguous and boxes were bound in largest-first order, one per path position.
+    pub candidates: Vec<usize>,
+}
+
+impl ValueSource {
+    pub fn is_ambiguous(&self) -> bool {
+        self.candidates.len() > 1
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct MatchExplanation {
+    pub pattern: PatternMatch,
+    /// One entry per path value, in path order.
+    pub sources: Vec<ValueSource>,
+}
+
+/// Serialized camelCase for the JS consumers. Supabase keeps storing `integer_value`
+/// (see `SupabaseClient::insert_active_signal`), so rows read back in either spelling.
 #[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
 pub struct BoxDetail {
+    #[serde(alias = "integer_value")]
     pub integer_value: i32,
     pub high: f64,
     pub low: f64,
@@ -64,7 +294,10 @@ pub struct StopLoss {
     pub timestamp: Option<String>,
 }
 
+/// Outbound signal as forwarded to `FORWARD_URLS` and broadcast on `/ws/signals`. The
+/// downstream servers are JS, so keys are camelCase (`signalType`, `stopLosses`, ...).
 #[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
 pub struct SignalMessage { 
     pub id: Option<i64>, // Supabase id (set after insert)
     pub pair: String, 
@@ -77,5 +310,18 @@ pub struct SignalMessage {
     pub stop_losses: Vec<StopLoss>,
     pub targets: Vec<Target>,
     pub risk_reward: Vec<f64>,
+    pub confidence: f64, // 0..1, see signal::score_pattern
+    /// Lots risking `RISK_PER_TRADE` between entry and first stop, when configured.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub suggested_size: Option<f64>,
+}
+
+/// Frame sent to `/ws/signals` subscribers, tagged by `type`. A client first gets one
+/// `snapshot` of every active signal, then a `signal` per newly generated one.
+#[derive(Debug, Clone, Serialize)]
+#[serde(tag = "type", rename_all = "camelCase")]
+pub enum SignalFeedMessage<'a> {
+    Snapshot { signals: &'a [SignalMessage] },
+    Signal { data: &'a SignalMessage },
 }
 
@@ -0,0 +1,65 @@
+use std::collections::{HashMap, VecDeque};
+use tokio::sync::RwLock;
+
+/// Default number of box updates averaged into a pair's ATR.
+pub const DEFAULT_ATR_PERIOD: usize = 14;
+
+#[derive(Default)]
+struct PairRanges {
+    last_close: Option<f64>,
+    ranges: VecDeque<f64>,
+}
+
+/// Rolling average true range per pair, measured per box update rather than per bar.
+/// Each update's true range spans its high, its low and the previous update's price, so
+/// gaps between updates count even when the upstream sends no tick range.
+pub struct VolatilityTracker {
+    period: usize,
+    pairs: RwLock<HashMap<String, PairRanges>>,
+}
+
+impl VolatilityTracker {
+    pub fn new(period: usize) -> Self {
+        Self {
+            period: period.max(1),
+            pairs: RwLock::new(HashMap::new()),
+        }
+    }
+
+    pub fn period(&self) -> usize {
+        self.period
+    }
+
+    /// Adds one update: `high`/`low` are the range traded since the previous update (the
+    /// price itself when unknown) and `close` the price it ended at.
+    pub async fn record(&self, pair: &str, high: f64, low: f64, close: f64) {
+        if !(high.is_finite() && low.is_finite() && close.is_finite()) {
+            return;
+        }
+        let mut pairs = self.pairs.write().await;
+        let entry = pairs.entry(pair.to_uppercase()).or_default();
+        let (mut top, mut bottom) = (high.max(low), high.min(low));
+        if let Some(previous) = entry.last_close {
+            top = top.max(previous);
+            bottom = bottom.min(previous);
+        }
+        if entry.ranges.len() >= self.period {
+            entry.ranges.pop_front();
+        }
+        entry.ranges.push_back(top - bottom);
+        entry.last_close = Some(close);
+    }
+
+    /// Mean true range over the last `period` updates; `None` until that many were seen.
+    pub async fn atr(&self, pair: &str) -> Option<f64> {
+        let pairs = self.pairs.read().await;
+        let ranges = &pairs.get(&pair.to_uppercase())?.ranges;
+        (ranges.len() >= self.period).then(|| ranges.iter().sum::<f64>() / ranges.len() as f64)
+    }
+}
+
+impl Default for VolatilityTracker {
+    fn default() -> Self {
+        Self::new(DEFAULT_ATR_PERIOD)
+    }
+}
@@ -0,0 +1,88 @@
+use crate::tracker::Settlement;
+use crate::types::SettlementReason;
+use hmac::{Hmac, Mac};
+use serde::Serialize;
+use sha2::Sha256;
+use tracing::{debug, warn};
+
+/// Header carrying `sha256=<hex HMAC-SHA256 of the raw body>` when a secret is configured.
+pub const SIGNATURE_HEADER: &str = "X-Signature-256";
+
+/// JSON body POSTed to the settlement webhook.
+#[derive(Debug, Clone, Serialize)]
+pub struct SettlementEvent {
+    pub signal_id: i64,
+    pub pair: String,
+    pub status: String,
+    pub reason: SettlementReason,
+    pub settled_price: f64,
+    pub settled_at: String,
+}
+
+impl SettlementEvent {
+    pub fn new(settlement: &Settlement, settled_at: String) -> Self {
+        Self {
+            signal_id: settlement.signal.id,
+            pair: settlement.signal.pair.clone(),
+            status: settlement.status.to_string(),
+            reason: settlement.reason,
+            settled_price: settlement.settled_price,
+            settled_at,
+        }
+    }
+}
+
+/// Hex-encoded HMAC-SHA256 of `body` under `secret`.
+pub fn sign(secret: &str, body: &[u8]) -> String {
+    let mut mac = Hmac::<Sha256>::new_from_slice(secret.as_bytes()).expect("HMAC accepts any key length");
+    mac.update(body);
+    hex::encode(mac.finalize().into_bytes())
+}
+
+/// Notifies an external service of final settlements. Delivery is fire-and-forget: each
+/// event is sent on its own task so a slow or failing endpoint never delays persistence.
+#[derive(Clone)]
+pub struct SettlementWebhook {
+    client: reqwest::Client,
+    url: String,
+    secret: Option<String>,
+}
+
+impl SettlementWebhook {
+    pub fn new(url: &str, secret: Option<String>) -> Self {
+        Self {
+            client: crate::http::client(crate::http::DEFAULT_TIMEOUT),
+            url: url.to_string(),
+            secret,
+        }
+    }
+
+    /// Sends events through `client`, e.g. to share one connection pool across components.
+    pub fn with_client(mut self, client: reqwest::Client) -> Self {
+        self.client = client;
+        self
+    }
+
+    pub fn notify(&self, settlement: &Settlement) {
+        let event = SettlementEvent::new(settlement, chrono::Utc::now().to_rfc3339());
+        let Ok(body) = serde_json::to_vec(&event) else {
+            return;
+        };
+        let mut request = self
+            .client
+            .post(&self.url)
+            .header("Content-Type", "application/json");
+        if let Some(secret) = &self.secret {
+            request = request.header(SIGNATURE_HEADER, format!("sha256={}", sign(secret, &body)));
+        }
+        let request = request.body(body);
+
+        tokio::spawn(async move {
+            match request.send().await.and_then(|r| r.error_for_status()) {
+                Ok(_) => debug!("[Webhook] Delivered settlement of signal {} ({})", event.signal_id, event.status),
+                Err(e) if e.is_timeout() => warn!("[Webhook] Timed out delivering settlement of signal {}: {}", event.signal_id, e),
+                Err(e) => warn!("[Webhook] Failed to deliver settlement of signal {}: {}", event.signal_id, e),
+            }
+        });
+    }
+}
@@ -0,0 +1,71 @@
+use serde_json::json;
+use signals_rthmn::auth::{token_matches, ws_auth_token};
+
+#[test]
+fn test_ws_auth_accepts_matching_token() {
+    let msg = json!({"type": "auth", "token": "secret-key"});
+    assert!(token_matches(ws_auth_token(&msg), "secret-key"));
+}
+
+#[test]
+fn test_ws_auth_rejects_wrong_or_missing_token() {
+    let wrong = json!({"type": "auth", "token": "secret-kez"});
+    let prefix = json!({"type": "auth", "token": "secret"});
+    let missing = json!({"type": "auth"});
+    let not_a_string = json!({"type": "auth", "token": 42});
+
+    assert!(!token_matches(ws_auth_token(&wrong), "secret-key"));
+    assert!(!token_matches(ws_auth_token(&prefix), "secret-key"));
+    assert!(!token_matches(ws_auth_token(&missing), "secret-key"));
+    assert!(!token_matches(ws_auth_token(&not_a_string), "secret-key"));
+}
+
+#[test]
+fn test_empty_expected_token_never_matches() {
+    let msg = json!({"type": "auth", "token": ""});
+    assert!(!token_matches(ws_auth_token(&msg), ""));
+}
+
+#[test]
+fn test_parse_cors_origins() {
+    use signals_rthmn::auth::parse_cors_origins;
+
+    let origins = parse_cors_origins(" https://rthmn.com/, ,http://localhost:3000").unwrap();
+    assert_eq!(origins, ["https://rthmn.com", "http://localhost:3000"]);
+    assert!(parse_cors_origins("*").is_err());
+    assert!(parse_cors_origins("https://rthmn.com,rthmn.com").is_err());
+}
+
+#[tokio::test]
+async fn test_cors_rejects_disallowed_origin() {
+    use axum::{routing::get, Router};
+    use signals_rthmn::auth::{cors_layer, parse_cors_origins};
+
+    let origins = parse_cors_origins("https://rthmn.com").unwrap();
+    let app = Router::new().route("/api/status", get(|| async { "ok" })).layer(cors_layer(Some(origins)));
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let url = format!("http://{}/api/status", listener.local_addr().unwrap());
+    tokio::spawn(async move { axum::serve(listener, app).await.unwrap() });
+
+    let client = reqwest::Client::new();
+    let preflight = |origin: &'static str| {
+        client
+            .request(reqwest::Method::OPTIONS, &url)
+            .header("Origin", origin)
+            .header("Access-Control-Request-Method", "POST")
+            .header("Access-Control-Request-Headers", "authorization")
+            .send()
+    };
+
+    let allowed = preflight("https://rthmn.com").await.unwrap();
+    let headers = allowed.headers();
+    assert_eq!(headers["access-control-allow-origin"], "https://rthmn.com");
+    assert_eq!(headers["access-control-allow-credentials"], "true");
+    assert_eq!(headers["access-control-allow-methods"], "POST");
+
+    // The browser refuses the response without an allow-origin header
+    let denied = preflight("https://evil.example").await.unwrap();
+    assert!(denied.headers().get("access-control-allow-origin").is_none());
+    let denied = client.get(&url).header("Origin", "https://evil.example").send().await.unwrap();
+    assert!(denied.headers().get("access-control-allow-origin").is_none());
+}
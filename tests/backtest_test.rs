@@ -0,0 +1,62 @@
+use signals_rthmn::backtest::run_backtest;
+use signals_rthmn::deduplication::DedupConfig;
+use signals_rthmn::patterns::PatternGraph;
+use signals_rthmn::pipeline::{parse_pair_list, RuntimeConfig};
+use signals_rthmn::scanner::MarketScanner;
+use signals_rthmn::signal::{MinRr, SignalGenerator};
+use signals_rthmn::types::{Box, BoxData};
+
+fn scanner() -> MarketScanner {
+    let dir = std::env::temp_dir().join(format!("backtest-test-{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    let file = dir.join("patterns.json");
+    std::fs::write(&file, r#"{"starting_points": [100], "boxes": {"100": [[-86, 75]]}}"#).unwrap();
+    let graph = PatternGraph::from_file(&file).unwrap();
+    std::fs::remove_dir_all(&dir).unwrap();
+    let mut scanner = MarketScanner::default();
+    scanner.initialize_with(graph);
+    scanner
+}
+
+fn update(price: f64, timestamp: &str) -> BoxData {
+    BoxData {
+        pair: "EURUSD".to_string(),
+        boxes: vec![
+            Box { high: 1.101, low: 1.1, value: 0.001 },
+            Box { high: 1.101, low: 1.10014, value: -0.00086 },
+            Box { high: 1.1008, low: 1.10005, value: 0.00075 },
+        ],
+        price,
+        tick_high: None,
+        tick_low: None,
+        timestamp: timestamp.to_string(),
+    }
+}
+
+async fn replay(config: &RuntimeConfig) -> signals_rthmn::backtest::BacktestReport {
+    // Signal, a duplicate, an unreadable timestamp, the final target (1.102), then a
+    // retrace that would have stopped out a second signal
+    let updates = vec![
+        update(1.1005, "2024-01-01T00:00:00Z"),
+        update(1.1006, "2024-01-01T00:00:01Z"),
+        update(1.1007, "not a timestamp"),
+        update(1.1021, "2024-01-01T00:00:02Z"),
+        update(1.0999, "2024-01-01T00:00:03Z"),
+    ];
+    run_backtest(&scanner(), &SignalGenerator::default(), config, DedupConfig::default(), 14, updates.into_iter()).await
+}
+
+#[tokio::test]
+async fn test_replay_counts() {
+    let report = replay(&RuntimeConfig::default()).await;
+    assert_eq!((report.updates, report.skipped, report.signals), (5, 1, 1));
+    assert_eq!((report.wins, report.losses, report.partials, report.open), (1, 0, 0, 0));
+    assert_eq!(report.win_rate, 1.0);
+    assert_eq!(report.by_level[&1].wins, 1);
+
+    // The live filters apply: a min RR above the signal's 2.0, or a blocked pair
+    let min_rr = RuntimeConfig { min_rr: MinRr { default: 3.0, ..Default::default() }, ..Default::default() };
+    assert_eq!(replay(&min_rr).await.signals, 0);
+    let blocked = RuntimeConfig { pairs_blocklist: parse_pair_list("eurusd"), ..Default::default() };
+    assert_eq!(replay(&blocked).await.signals, 0);
+}
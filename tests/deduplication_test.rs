@@ -0,0 +1,177 @@
+use signals_rthmn::deduplication::{DedupConfig, Deduplicator};
+use signals_rthmn::types::{BoxDetail, PatternMatch, SignalMessage, SignalType, StopLoss, Target, TraversalPath};
+
+fn signal(entry: f64) -> SignalMessage {
+    SignalMessage {
+        id: None,
+        pair: "EURUSD".to_string(),
+        signal_type: "LONG".to_string(),
+        level: 2,
+        pattern_sequence: vec![100, -86, 75],
+        box_details: vec![],
+        complete_box_snapshot: vec![100, -86, 75],
+        entry: Some(entry),
+        stop_losses: vec![StopLoss { price: entry - 0.001, timestamp: None }],
+        targets: vec![Target { price: entry + 0.002, timestamp: None }],
+        risk_reward: vec![2.0],
+        confidence: 0.5,
+        suggested_size: None,
+    }
+}
+
+fn pattern(level: u32, path: Vec<i32>) -> PatternMatch {
+    PatternMatch {
+        pair: "EURUSD".to_string(),
+        level,
+        traversal_path: TraversalPath { path: path.clone() },
+        full_pattern: path,
+        box_details: vec![],
+    }
+}
+
+#[test]
+fn test_subset_pattern_removed_in_favor_of_higher_level() {
+    let dedup = Deduplicator::new();
+    let patterns = vec![
+        pattern(2, vec![100, -86, 75]),
+        pattern(4, vec![100, -86, 75, -65, 56]),
+        pattern(2, vec![-100, 86, -75]),
+    ];
+
+    let unique = dedup.remove_subset_duplicates(patterns);
+    let levels: Vec<(u32, i32)> = unique.iter().map(|p| (p.level, p.traversal_path.path[0])).collect();
+    assert_eq!(levels, vec![(4, 100), (2, -100)]);
+}
+
+#[tokio::test]
+async fn test_recent_signal_filtered_within_window() {
+    let dedup = Deduplicator::with_config(DedupConfig { price_tolerance: 0.00001, recent_window_ms: 1_000, ..DedupConfig::default() });
+
+    assert!(!dedup.should_filter_recent_signal("EURUSD", &signal(1.1), 0).await);
+    assert!(dedup.should_filter_recent_signal("EURUSD", &signal(1.1), 500).await);
+    assert!(!dedup.should_filter_recent_signal("EURUSD", &signal(1.1), 2_000).await);
+}
+
+#[tokio::test]
+async fn test_sweep_evicts_stale_recent_signals() {
+    let dedup = Deduplicator::with_config(DedupConfig { price_tolerance: 0.00001, recent_window_ms: 60_000, ..DedupConfig::default() });
+
+    for i in 0..1000 {
+        let entry = 1.0 + i as f64 * 0.001;
+        assert!(!dedup.should_filter_recent_signal("EURUSD", &signal(entry), i).await);
+    }
+    assert_eq!(dedup.recent_signal_count().await, 1000);
+
+    let evicted = dedup.sweep(1_000_000).await;
+    assert_eq!(evicted, 1000);
+    assert_eq!(dedup.recent_signal_count().await, 0);
+}
+
+fn detail(integer_value: i32, high: f64, low: f64) -> BoxDetail {
+    BoxDetail { integer_value, high, low, value: (high - low) * integer_value.signum() as f64 }
+}
+
+#[tokio::test]
+async fn test_structural_filter_ignores_non_structural_box_moves() {
+    let dedup = Deduplicator::new();
+    let first = vec![
+        detail(100, 1.1000, 1.0900),
+        detail(-86, 1.0990, 1.0904),
+        detail(75, 1.0980, 1.0905),
+        detail(50, 1.0960, 1.0910),
+    ];
+    assert!(!dedup.should_filter_structural_boxes("EURUSD", &first, SignalType::LONG, 2).await);
+
+    // The opposite-direction box and the entry box (index `level`) both move; the two
+    // tracked LONG boxes don't, so the signal is still a duplicate
+    let moved = vec![
+        detail(100, 1.1000, 1.0900),
+        detail(-86, 1.0995, 1.0909),
+        detail(75, 1.0980, 1.0905),
+        detail(50, 1.0970, 1.0920),
+    ];
+    assert!(dedup.should_filter_structural_boxes("EURUSD", &moved, SignalType::LONG, 2).await);
+
+    let structural_moved = vec![
+        detail(100, 1.1000, 1.0900),
+        detail(-86, 1.0995, 1.0909),
+        detail(75, 1.0985, 1.0910),
+        detail(50, 1.0970, 1.0920),
+    ];
+    assert!(!dedup.should_filter_structural_boxes("EURUSD", &structural_moved, SignalType::LONG, 2).await);
+}
+
+#[tokio::test]
+async fn test_filter_counts_name_the_reason() {
+    let dedup = Deduplicator::new();
+    let boxes = vec![detail(100, 1.1000, 1.0900), detail(75, 1.0980, 1.0905)];
+
+    dedup.should_filter_structural_boxes("EURUSD", &boxes, SignalType::LONG, 1).await;
+    dedup.should_filter_structural_boxes("EURUSD", &boxes, SignalType::LONG, 1).await;
+    dedup.should_filter_recent_signal("EURUSD", &signal(1.1), 0).await;
+    dedup.should_filter_recent_signal("EURUSD", &signal(1.1), 1).await;
+    dedup.should_filter_recent_signal("EURUSD", &signal(1.1), 2).await;
+
+    let counts = dedup.filter_counts();
+    assert_eq!(counts.structural_unchanged, 1);
+    assert_eq!(counts.recent_window, 2);
+    assert_eq!(counts.active_l1, 0);
+}
+
+#[tokio::test]
+async fn test_tolerance_scales_with_instrument_point() {
+    let dedup = Deduplicator::new();
+    assert!(dedup.tolerance_for("BTCUSD") > 1.0);
+    assert_eq!(dedup.tolerance_for("EURUSD"), dedup.config().price_tolerance);
+
+    // A $2 wiggle is noise on BTCUSD (10-dollar point) and still dedups
+    let btc = vec![detail(100, 65_000.0, 64_000.0)];
+    let btc_moved = vec![detail(100, 65_002.0, 63_998.0)];
+    assert!(!dedup.should_filter_structural_boxes("BTCUSD", &btc, SignalType::LONG, 1).await);
+    assert!(dedup.should_filter_structural_boxes("BTCUSD", &btc_moved, SignalType::LONG, 1).await);
+
+    // Two pips on EURUSD is a real change
+    let eur = vec![detail(100, 1.1000, 1.0900)];
+    let eur_moved = vec![detail(100, 1.1002, 1.0900)];
+    assert!(!dedup.should_filter_structural_boxes("EURUSD", &eur, SignalType::LONG, 1).await);
+    assert!(!dedup.should_filter_structural_boxes("EURUSD", &eur_moved, SignalType::LONG, 1).await);
+    assert!(dedup.should_filter_structural_boxes("EURUSD", &eur_moved, SignalType::LONG, 1).await);
+}
+
+#[tokio::test]
+async fn test_recent_signal_sub_tick_noise_and_bucket() {
+    let dedup = Deduplicator::with_config(DedupConfig {
+        price_tolerance: 0.000001,
+        recent_window_ms: 1_000,
+        recent_bucket_ms: 60_000,
+    });
+
+    // 0.8 ticks apart, beyond the half-tick tolerance, but both round to 1.10000
+    assert!(!dedup.should_filter_recent_signal("EURUSD", &signal(1.099996), 0).await);
+    assert!(dedup.should_filter_recent_signal("EURUSD", &signal(1.100004), 100).await);
+    // A whole tick apart is a different signal
+    assert!(!dedup.should_filter_recent_signal("EURUSD", &signal(1.10001), 200).await);
+
+    // Past the window but inside the same minute bucket
+    assert!(dedup.should_filter_recent_signal("EURUSD", &signal(1.1), 30_000).await);
+    assert!(!dedup.should_filter_recent_signal("EURUSD", &signal(1.1), 60_500).await);
+}
+
+#[tokio::test]
+async fn test_reset_clears_state() {
+    let dedup = Deduplicator::new();
+    let boxes = vec![detail(100, 1.1000, 1.0900), detail(75, 1.0980, 1.0905)];
+    for pair in ["EURUSD", "GBPUSD"] {
+        dedup.should_filter_recent_signal(pair, &signal(1.1), 0).await;
+        dedup.should_filter_structural_boxes(pair, &boxes, SignalType::LONG, 1).await;
+    }
+
+    assert_eq!(dedup.reset_pair("eurusd").await, 2);
+    assert!(!dedup.should_filter_recent_signal("EURUSD", &signal(1.1), 1).await);
+    assert!(!dedup.should_filter_structural_boxes("EURUSD", &boxes, SignalType::LONG, 1).await);
+    assert!(dedup.should_filter_recent_signal("GBPUSD", &signal(1.1), 1).await);
+
+    assert_eq!(dedup.reset().await, 4);
+    assert_eq!(dedup.recent_signal_count().await, 0);
+    assert!(!dedup.should_filter_structural_boxes("GBPUSD", &boxes, SignalType::LONG, 1).await);
+}
@@ -0,0 +1,72 @@
+use signals_rthmn::executor::PairExecutor;
+use signals_rthmn::tracker::{ActiveSignal, SignalTracker};
+use signals_rthmn::types::{SignalMessage, StopLoss, Target};
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::RwLock;
+
+const PAIRS: [&str; 4] = ["EURUSD", "GBPUSD", "USDJPY", "AUDUSD"];
+const UPDATES: usize = 50;
+
+fn long_signal(pair: &str) -> ActiveSignal {
+    let message = SignalMessage {
+        id: None,
+        pair: pair.to_string(),
+        signal_type: "LONG".to_string(),
+        level: 1,
+        pattern_sequence: vec![100, -86],
+        box_details: vec![],
+        complete_box_snapshot: vec![100, -86],
+        entry: Some(1.0),
+        stop_losses: vec![StopLoss { price: 0.5, timestamp: None }],
+        targets: vec![Target { price: 1.0 + UPDATES as f64, timestamp: None }],
+        risk_reward: vec![2.0],
+        confidence: 0.5,
+        suggested_size: None,
+    };
+    ActiveSignal::from_message(&message, 0).unwrap()
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+async fn test_pair_updates_stay_ordered() {
+    let executor = PairExecutor::new(8);
+    let tracker = Arc::new(SignalTracker::new(None, None));
+    for pair in PAIRS {
+        tracker.add_signal(long_signal(pair)).await.unwrap();
+    }
+    let seen: Arc<RwLock<HashMap<String, Vec<usize>>>> = Arc::default();
+
+    // Interleave the pairs; later updates finish faster, so anything run out of order
+    // would overtake its predecessors
+    let mut settled = Vec::new();
+    for i in 1..=UPDATES {
+        for pair in PAIRS {
+            let (tracker, seen) = (Arc::clone(&tracker), Arc::clone(&seen));
+            let job = async move {
+                tokio::time::sleep(Duration::from_micros(((UPDATES - i) * 20) as u64)).await;
+                seen.write().await.entry(pair.to_string()).or_default().push(i);
+                // Price climbs with each update and reaches the target on the last one
+                tracker.check_price(pair, 1.0 + i as f64).await
+            };
+            settled.push((pair, i, executor.submit(pair, job).await));
+        }
+    }
+
+    for (pair, i, done) in settled {
+        let settlements = done.await.unwrap();
+        if i < UPDATES {
+            assert!(settlements.is_empty(), "{} settled early at update {}", pair, i);
+        } else {
+            assert_eq!(settlements.len(), 1);
+            assert_eq!(settlements[0].settled_price, 1.0 + UPDATES as f64);
+            // Only the final update reached the target, so the best price seen was its own
+            assert_eq!(settlements[0].signal.mfe, UPDATES as f64);
+        }
+    }
+    let seen = seen.read().await;
+    for pair in PAIRS {
+        assert_eq!(seen[pair], (1..=UPDATES).collect::<Vec<_>>(), "{} ran out of order", pair);
+    }
+    assert_eq!(executor.pair_count().await, PAIRS.len());
+}
@@ -0,0 +1,50 @@
+use signals_rthmn::export::{settled_csv_record, SETTLED_CSV_HEADER};
+use signals_rthmn::supabase::SettledSignalRow;
+use signals_rthmn::types::{StopLoss, Target};
+
+fn row() -> SettledSignalRow {
+    SettledSignalRow {
+        id: 7,
+        pair: "EURUSD".to_string(),
+        signal_type: "LONG".to_string(),
+        level: 2,
+        entry: Some(1.1),
+        stop_losses: Some(vec![StopLoss { price: 1.09, timestamp: None }]),
+        targets: Some(vec![
+            Target { price: 1.11, timestamp: None },
+            Target { price: 1.12, timestamp: None },
+        ]),
+        risk_reward: Some(vec![1.0, 2.0]),
+        status: "success".to_string(),
+        settled_price: Some(1.12),
+        created_at: Some("2025-01-01T00:00:00+00:00".to_string()),
+        settled_at: Some("2025-01-01T01:00:00+00:00".to_string()),
+    }
+}
+
+#[test]
+fn test_settled_csv_record_uses_first_stop_and_final_target() {
+    assert_eq!(SETTLED_CSV_HEADER.trim_end().split(',').count(), 11);
+    assert_eq!(
+        settled_csv_record(&row()),
+        "EURUSD,LONG,2,1.1,1.09,1.12,2,success,1.12,2025-01-01T00:00:00+00:00,2025-01-01T01:00:00+00:00\n"
+    );
+}
+
+#[test]
+fn test_settled_csv_record_leaves_missing_values_empty_and_quotes() {
+    let row = SettledSignalRow {
+        pair: "ODD,\"PAIR\"".to_string(),
+        entry: None,
+        stop_losses: None,
+        targets: Some(vec![]),
+        risk_reward: None,
+        settled_price: None,
+        settled_at: None,
+        ..row()
+    };
+    assert_eq!(
+        settled_csv_record(&row),
+        "\"ODD,\"\"PAIR\"\"\",LONG,2,,,,,success,,2025-01-01T00:00:00+00:00,\n"
+    );
+}
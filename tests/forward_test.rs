@@ -0,0 +1,68 @@
+use signals_rthmn::forward::ForwardFormat;
+use signals_rthmn::types::{BoxDetail, SignalMessage, StopLoss, Target};
+
+fn signal() -> SignalMessage {
+    SignalMessage {
+        id: Some(42),
+        pair: "GBPCAD".to_string(),
+        signal_type: "SHORT".to_string(),
+        level: 2,
+        pattern_sequence: vec![-100, 86, -75],
+        box_details: vec![BoxDetail { integer_value: -100, high: 1.852, low: 1.843, value: -0.009 }],
+        complete_box_snapshot: vec![-100, 86, -75],
+        entry: Some(1.8435),
+        stop_losses: vec![StopLoss { price: 1.8452, timestamp: None }],
+        targets: vec![Target { price: 1.8401, timestamp: Some("2024-01-01T00:00:00Z".to_string()) }],
+        risk_reward: vec![2.0],
+        confidence: 0.75,
+        suggested_size: None,
+    }
+}
+
+#[test]
+fn test_forward_formats_round_trip() {
+    let signal = signal();
+    let expected = serde_json::to_value(&signal).unwrap();
+
+    let json = ForwardFormat::Json.encode(&signal).unwrap();
+    let decoded: serde_json::Value = serde_json::from_slice(&json).unwrap();
+    assert_eq!(decoded, expected);
+
+    let msgpack = ForwardFormat::Msgpack.encode(&signal).unwrap();
+    let decoded: serde_json::Value = rmp_serde::from_slice(&msgpack).unwrap();
+    assert_eq!(decoded, expected);
+}
+
+#[test]
+fn test_signal_message_wire_keys() {
+    // The JS servers read these exact keys; renaming a field is a breaking change
+    let json = serde_json::to_value(signal()).unwrap();
+    let mut keys: Vec<&str> = json.as_object().unwrap().keys().map(String::as_str).collect();
+    keys.sort_unstable();
+    assert_eq!(
+        keys,
+        [
+            "boxDetails", "completeBoxSnapshot", "confidence", "entry", "id", "level", "pair",
+            "patternSequence", "riskReward", "signalType", "stopLosses", "targets",
+        ]
+    );
+    assert_eq!(json["boxDetails"][0], serde_json::json!({"integerValue": -100, "high": 1.852, "low": 1.843, "value": -0.009}));
+    assert_eq!(json["targets"][0], serde_json::json!({"price": 1.8401, "timestamp": "2024-01-01T00:00:00Z"}));
+
+    let sized = SignalMessage { suggested_size: Some(0.5), ..signal() };
+    assert_eq!(serde_json::to_value(sized).unwrap()["suggestedSize"], 0.5);
+
+    // Supabase keeps the snake_case column payload the frontend reads, and old rows still parse
+    let row = signals_rthmn::supabase::box_details_row(&signal().box_details);
+    assert_eq!(row[0]["integer_value"], -100);
+    let back: Vec<BoxDetail> = serde_json::from_value(row).unwrap();
+    assert_eq!(back[0].integer_value, -100);
+}
+
+#[test]
+fn test_forward_format_parse() {
+    assert_eq!("json".parse(), Ok(ForwardFormat::Json));
+    assert_eq!("MsgPack".parse(), Ok(ForwardFormat::Msgpack));
+    assert!("protobuf".parse::<ForwardFormat>().is_err());
+    assert_eq!(ForwardFormat::Msgpack.content_type(), "application/msgpack");
+}
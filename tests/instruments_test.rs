@@ -0,0 +1,139 @@
+use signals_rthmn::instruments::get_instrument_config;
+
+fn approx(a: f64, b: f64) -> bool {
+    (a - b).abs() < 1e-9
+}
+
+#[test]
+fn test_forex_defaults() {
+    let eurusd = get_instrument_config("EURUSD");
+    assert_eq!((eurusd.point, eurusd.digits), (0.00001, 5));
+    assert_eq!(eurusd.contract_size, 100_000.0);
+    assert!(approx(eurusd.pip_value, 10.0));
+}
+
+#[test]
+fn test_jpy_defaults() {
+    let usdjpy = get_instrument_config("USDJPY");
+    assert_eq!((usdjpy.point, usdjpy.digits), (0.001, 3));
+    assert_eq!(usdjpy.contract_size, 100_000.0);
+    assert!(approx(usdjpy.pip_value, 1000.0));
+}
+
+#[test]
+fn test_crypto_defaults() {
+    let btcusd = get_instrument_config("BTCUSD");
+    assert_eq!((btcusd.point, btcusd.digits), (10.0, 0));
+    assert_eq!(btcusd.contract_size, 1.0);
+    assert!(approx(btcusd.pip_value, 10.0));
+}
+
+#[test]
+fn test_stock_defaults() {
+    let aapl = get_instrument_config("AAPL");
+    assert_eq!((aapl.point, aapl.digits), (0.01, 2));
+    assert_eq!(aapl.contract_size, 1.0);
+    assert!(approx(aapl.pip_value, 0.01));
+}
+
+#[test]
+fn test_asset_class_edge_cases() {
+    use signals_rthmn::instruments::{get_asset_class, AssetClass};
+
+    assert_eq!(get_asset_class("EURUSD"), AssetClass::Forex);
+    assert_eq!(get_asset_class("GBPJPY"), AssetClass::Forex);
+    assert_eq!(get_asset_class("XAUUSD"), AssetClass::Forex);
+    assert_eq!(get_asset_class("XAGUSD"), AssetClass::Forex);
+
+    assert_eq!(get_asset_class("ETHUSD"), AssetClass::Crypto);
+    assert_eq!(get_asset_class("SANDUSD"), AssetClass::Crypto);
+    assert_eq!(get_asset_class("BIGTIMEUSD"), AssetClass::Crypto);
+    assert_eq!(get_asset_class("USDTUSD"), AssetClass::Crypto);
+
+    assert_eq!(get_asset_class("AAPL"), AssetClass::Stocks);
+    assert_eq!(get_asset_class("SAND"), AssetClass::Stocks);
+    // Share a crypto prefix but aren't that token
+    assert_eq!(get_asset_class("UNIXUSD"), AssetClass::Stocks);
+    assert_eq!(get_asset_class("SANDISKUSD"), AssetClass::Stocks);
+    // Six letters, but the quote isn't a forex currency
+    assert_eq!(get_asset_class("ABCXYZ"), AssetClass::Stocks);
+    assert_eq!(get_asset_class("BRK1US"), AssetClass::Stocks);
+}
+
+#[test]
+fn test_config_recomputed_across_orders_of_magnitude() {
+    use signals_rthmn::instruments::update_instrument_price;
+
+    assert!(update_instrument_price("FETUSD", 8.0));
+    assert_eq!(get_instrument_config("FETUSD").point, 0.001);
+
+    // Same power of ten: cached config is kept
+    assert!(!update_instrument_price("FETUSD", 9.5));
+
+    assert!(update_instrument_price("FETUSD", 90.0));
+    assert_eq!(get_instrument_config("FETUSD").point, 0.01);
+    assert!(!update_instrument_price("FETUSD", 95.0));
+
+    assert!(update_instrument_price("FETUSD", 0.5));
+    assert_eq!(get_instrument_config("FETUSD").point, 0.0001);
+}
+
+#[test]
+fn test_config_not_recomputed_near_a_power_of_ten() {
+    use signals_rthmn::instruments::update_instrument_price;
+
+    assert!(update_instrument_price("XLMUSD", 9.98));
+    assert_eq!(get_instrument_config("XLMUSD").point, 0.001);
+    // Oscillating around 10 keeps the point it started with
+    for price in [10.02, 9.97, 10.5, 9.99, 10.9] {
+        assert!(!update_instrument_price("XLMUSD", price), "recomputed at {}", price);
+    }
+    assert_eq!(get_instrument_config("XLMUSD").point, 0.001);
+
+    // Clearly past the boundary it moves, and the same margin applies on the way back
+    assert!(update_instrument_price("XLMUSD", 11.0));
+    assert_eq!(get_instrument_config("XLMUSD").point, 0.01);
+    assert!(!update_instrument_price("XLMUSD", 9.5));
+    assert!(update_instrument_price("XLMUSD", 8.9));
+    assert_eq!(get_instrument_config("XLMUSD").point, 0.001);
+}
+
+#[test]
+fn test_point_overrides() {
+    use signals_rthmn::instruments::{parse_point_override, set_point_overrides, update_instrument_price};
+
+    assert_eq!(parse_point_override(" shibusd = 0.000000001"), Ok(("SHIBUSD".to_string(), 0.000000001)));
+    assert!(parse_point_override("SHIBUSD").is_err());
+    assert!(parse_point_override("SHIBUSD=0").is_err());
+    assert!(parse_point_override("=0.1").is_err());
+
+    set_point_overrides(vec![parse_point_override("SHIBUSD=0.000000001").unwrap()]);
+    assert_eq!(get_instrument_config("SHIBUSD").point, 0.000000001);
+    // A live price doesn't replace the override
+    assert!(!update_instrument_price("SHIBUSD", 0.00002));
+    assert_eq!(get_instrument_config("SHIBUSD").digits, 9);
+    // Nor does one from a feed that sends the pair in lowercase
+    assert!(!update_instrument_price("shibusd", 0.00002));
+    assert_eq!(get_instrument_config("shibusd").point, 0.000000001);
+}
+
+#[test]
+fn test_inverted_pairs_and_overrides_concurrently() {
+    use signals_rthmn::instruments::{set_inverted_pairs, set_point_overrides};
+
+    // Both take CONFIG_CACHE and INVERTED_PAIRS; run them side by side to catch a lock
+    // order inversion (this hangs if one is reintroduced)
+    let overrides = std::thread::spawn(|| {
+        for _ in 0..20_000 {
+            set_point_overrides(vec![("INVAUSD".to_string(), 0.01)]);
+        }
+    });
+    for _ in 0..20_000 {
+        set_inverted_pairs(["INVBUSD"]);
+    }
+    overrides.join().unwrap();
+
+    set_inverted_pairs(["INVAUSD"]);
+    assert!(get_instrument_config("INVAUSD").invert);
+    assert_eq!(get_instrument_config("INVAUSD").point, 0.01);
+}
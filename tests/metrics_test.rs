@@ -0,0 +1,34 @@
+use signals_rthmn::metrics::Metrics;
+
+#[test]
+fn test_outcome_stats() {
+    let metrics = Metrics::default();
+    assert_eq!(metrics.outcomes().total.win_rate, None);
+
+    metrics.record_outcome(1, "success");
+    metrics.record_outcome(1, "failed");
+    metrics.record_outcome(3, "success");
+    metrics.record_outcome(9, "failed");
+    metrics.record_outcome(1, "expired");
+
+    let stats = metrics.outcomes();
+    assert_eq!((stats.total.wins, stats.total.losses), (2, 2));
+    assert_eq!(stats.total.win_rate, Some(0.5));
+    assert_eq!(stats.by_level["1"].win_rate, Some(0.5));
+    assert_eq!(stats.by_level["3"].wins, 1);
+    assert_eq!(stats.by_level["7+"].losses, 1);
+    assert!(!stats.by_level.contains_key("2"));
+
+    let json = serde_json::to_value(&stats).unwrap();
+    assert_eq!(json["winRate"], 0.5);
+    assert_eq!(json["byLevel"]["3"]["winRate"], 1.0);
+}
+
+#[test]
+fn test_cooled_down_counter_rendered() {
+    let metrics = Metrics::default();
+    metrics.record_cooled_down();
+    metrics.record_cooled_down();
+    assert_eq!(metrics.signals_cooled_down(), 2);
+    assert!(metrics.render(&Default::default(), 0).contains("\nsignals_cooled_down_total 2\n"));
+}
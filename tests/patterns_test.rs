@@ -118,8 +118,15 @@ fn test_scanner_path_count() {
     println!("Memory saved: ~50%");
     
     // Verify we only have LONG paths (all starting points should be positive)
-    // Note: Since all paths are now LONG, we can't directly access them in integration tests
-    // But we can verify the count is approximately half of what it was before
+    assert!(
+        scanner.get_paths().iter().all(|p| p.starting_point() > 0),
+        "Only LONG paths should be stored; SHORT is matched by inverting them"
+    );
+    let by_start: usize = STARTING_POINTS
+        .iter()
+        .map(|&sp| scanner.paths_starting_at(sp).count())
+        .sum();
+    assert_eq!(by_start, path_count, "Every path should begin at a starting point");
     println!("✓ Path count: {} (should be ~50% of previous ~14.8M)", path_count);
     
     // The optimization is verified by:
@@ -196,3 +203,36 @@ fn test_memory_usage() {
     println!("\n✓ Memory analysis complete");
 }
 
+
+#[test]
+fn test_detect_patterns_with_shuffled_boxes() {
+    use signals_rthmn::scanner::MarketScanner;
+    use signals_rthmn::types::Box;
+
+    let mut scanner = MarketScanner::default();
+    scanner.initialize();
+
+    let path = scanner.paths_starting_at(1000).next().expect("paths start at 1000").path.clone();
+    let mut values: Vec<i32> = path.clone();
+    values.sort_by_key(|v| std::cmp::Reverse(v.abs()));
+    values.dedup();
+    let sorted: Vec<Box> = values
+        .iter()
+        .map(|&v| Box { high: 1.0 + v.abs() as f64 * 0.00001, low: 1.0, value: v as f64 * 0.00001 })
+        .collect();
+    let mut shuffled = sorted.clone();
+    shuffled.reverse();
+    shuffled.rotate_left(1);
+
+    let details = |boxes: &[Box]| -> Vec<Vec<(i32, f64, f64)>> {
+        scanner
+            .detect_patterns("EURUSD", boxes)
+            .iter()
+            .map(|m| m.box_details.iter().map(|d| (d.integer_value, d.high, d.low)).collect())
+            .collect()
+    };
+
+    let expected = details(&sorted);
+    assert!(!expected.is_empty(), "Boxes built from a stored path should match it");
+    assert_eq!(details(&shuffled), expected);
+}
@@ -0,0 +1,71 @@
+use signals_rthmn::deduplication::Deduplicator;
+use signals_rthmn::metrics::Metrics;
+use signals_rthmn::patterns::PatternGraph;
+use signals_rthmn::pipeline::{Pipeline, RuntimeConfig};
+use signals_rthmn::scanner::MarketScanner;
+use signals_rthmn::signal::SignalGenerator;
+use signals_rthmn::tracker::{ActiveSignal, SignalTracker};
+use signals_rthmn::types::{Box, ParsedUpdate};
+use signals_rthmn::volatility::VolatilityTracker;
+
+fn scanner() -> MarketScanner {
+    let dir = std::env::temp_dir().join(format!("pipeline-test-{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    let file = dir.join("patterns.json");
+    std::fs::write(&file, r#"{"starting_points": [100], "boxes": {"100": [[-86, 75]]}}"#).unwrap();
+    let graph = PatternGraph::from_file(&file).unwrap();
+    std::fs::remove_dir_all(&dir).unwrap();
+    let mut scanner = MarketScanner::default();
+    scanner.initialize_with(graph);
+    scanner
+}
+
+/// Boxes matching the scanner's one L1 LONG pattern.
+fn update(price: f64) -> ParsedUpdate {
+    ParsedUpdate {
+        boxes: vec![
+            Box { high: 1.101, low: 1.1, value: 0.001 },
+            Box { high: 1.101, low: 1.10014, value: -0.00086 },
+            Box { high: 1.1008, low: 1.10005, value: 0.00075 },
+        ],
+        price,
+        tick_high: None,
+        tick_low: None,
+        timestamp: None,
+    }
+}
+
+#[tokio::test]
+async fn test_signal_over_the_pair_cap_leaves_no_dedup_state() {
+    let (scanner, generator, volatility) = (scanner(), SignalGenerator::default(), VolatilityTracker::new(14));
+    let (tracker, deduplicator, metrics) = (SignalTracker::new(None, Some(1)), Deduplicator::new(), Metrics::default());
+    let (config, last_fingerprints, last_fired_at) = (RuntimeConfig::default(), Default::default(), Default::default());
+    let pipeline = Pipeline {
+        scanner: &scanner,
+        generator: &generator,
+        volatility: &volatility,
+        tracker: &tracker,
+        deduplicator: &deduplicator,
+        metrics: &metrics,
+        config: &config,
+        last_fingerprints: &last_fingerprints,
+        last_fired_at: &last_fired_at,
+        signal_ttl_ms: None,
+        trace: false,
+    };
+
+    // A signal tracked from elsewhere fills the pair's only slot
+    let first = update(1.1005);
+    let message = generator.generate_signals("EURUSD", &scanner.detect_patterns("EURUSD", &first.boxes), &first.boxes, 1.1005);
+    let occupant = tracker.add_signal(ActiveSignal::from_message(&message[0], 0).unwrap()).await.unwrap();
+
+    assert!(pipeline.run("EURUSD", &first, 1_000).await.signals.is_empty());
+    assert_eq!(tracker.get_rejected_count(), 1);
+
+    // Once the slot frees up, the same structure is emitted: the rejected signal wasn't
+    // remembered by the L1, structural or recent-window filters
+    tracker.close_by_id(occupant).await.unwrap();
+    let processed = pipeline.run("EURUSD", &update(1.1006), 2_000).await;
+    assert_eq!(processed.signals.len(), 1);
+    assert_eq!(tracker.get_rejected_count(), 1);
+}
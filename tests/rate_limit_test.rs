@@ -0,0 +1,24 @@
+use signals_rthmn::rate_limit::PairRateLimiter;
+use std::time::{Duration, Instant};
+
+#[tokio::test]
+async fn test_token_bucket_per_pair() {
+    let limiter = PairRateLimiter::new(2.0, 3.0);
+    let start = Instant::now();
+
+    for _ in 0..3 {
+        assert!(limiter.try_acquire("EURUSD", start).await.is_ok());
+    }
+    assert_eq!(limiter.try_acquire("EURUSD", start).await, Err(Duration::from_millis(500)));
+    // Another pair has its own bucket
+    assert!(limiter.try_acquire("gbpusd", start).await.is_ok());
+
+    // Refills at 2/s: one token after 500ms, never more than the burst
+    assert!(limiter.try_acquire("eurusd", start + Duration::from_millis(500)).await.is_ok());
+    assert!(limiter.try_acquire("EURUSD", start + Duration::from_millis(600)).await.is_err());
+    let later = start + Duration::from_secs(60);
+    for _ in 0..3 {
+        assert!(limiter.try_acquire("EURUSD", later).await.is_ok());
+    }
+    assert!(limiter.try_acquire("EURUSD", later).await.is_err());
+}
@@ -0,0 +1,337 @@
+use signals_rthmn::scanner::box_fingerprint;
+use signals_rthmn::types::Box;
+
+fn boxes(values: &[f64]) -> Vec<Box> {
+    values.iter().map(|&v| Box { high: 1.1, low: 1.0, value: v }).collect()
+}
+
+#[test]
+fn test_box_fingerprint_ignores_sub_point_noise() {
+    let a = box_fingerprint("EURUSD", &boxes(&[0.01, -0.00866]), 1.10001);
+    let b = box_fingerprint("EURUSD", &boxes(&[0.0100000001, -0.00866]), 1.100010001);
+    assert_eq!(a, b);
+
+    assert_ne!(a, box_fingerprint("EURUSD", &boxes(&[0.01, -0.00866]), 1.10002));
+    assert_ne!(a, box_fingerprint("EURUSD", &boxes(&[0.01, -0.0075]), 1.10001));
+}
+
+#[test]
+fn test_indexed_detection_matches_brute_force() {
+    use signals_rthmn::scanner::MarketScanner;
+    use std::collections::HashSet;
+
+    let mut scanner = MarketScanner::default();
+    scanner.initialize();
+
+    let long_path = scanner.paths_starting_at(1000).next().expect("paths start at 1000").path.clone();
+    let short_path = scanner.paths_starting_at(866).next().expect("paths start at 866").path.clone();
+    let mut values: Vec<i32> = long_path.iter().copied().chain(short_path.iter().map(|v| -v)).collect();
+    values.sort_by_key(|v| std::cmp::Reverse(v.abs()));
+    values.dedup();
+    let live: Vec<Box> = values
+        .iter()
+        .map(|&v| Box { high: 1.0 + v.abs() as f64 * 0.00001, low: 1.0, value: v as f64 * 0.00001 })
+        .collect();
+
+    let value_set: HashSet<i32> = values.iter().copied().collect();
+    let mut expected: Vec<Vec<i32>> = Vec::new();
+    for path in scanner.get_paths() {
+        if path.path.iter().all(|v| value_set.contains(v)) {
+            expected.push(path.path.clone());
+        }
+        if path.path.iter().all(|v| value_set.contains(&-v)) {
+            expected.push(path.path.iter().map(|v| -v).collect());
+        }
+    }
+
+    let actual: Vec<Vec<i32>> = scanner
+        .detect_patterns("EURUSD", &live)
+        .into_iter()
+        .map(|m| m.traversal_path.path)
+        .collect();
+    assert!(expected.iter().any(|p| p[0] > 0) && expected.iter().any(|p| p[0] < 0));
+    assert_eq!(actual, expected);
+    assert_eq!(scanner.start_index_len(), signals_rthmn::patterns::STARTING_POINTS.len());
+}
+
+#[test]
+fn test_pattern_graph_from_file() {
+    use signals_rthmn::patterns::{PatternGraph, PatternGraphError};
+    use signals_rthmn::scanner::MarketScanner;
+
+    let dir = std::env::temp_dir().join(format!("patterns-test-{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    let file = dir.join("patterns.json");
+    std::fs::write(&file, r#"{"starting_points": [100], "boxes": {"100": [[-86, 75], [100]], "75": [[-65, 56]]}}"#).unwrap();
+
+    let graph = PatternGraph::from_file(&file).unwrap();
+    assert_eq!(graph.leaf_values().into_iter().collect::<Vec<_>>(), vec![56, 65, 86]);
+
+    let mut scanner = MarketScanner::default();
+    scanner.initialize_with(graph);
+    let mut paths: Vec<Vec<i32>> = scanner.get_paths().iter().map(|p| p.path.clone()).collect();
+    paths.sort();
+    assert_eq!(paths, vec![vec![100], vec![100, -86, 75, -65, 56]]);
+
+    std::fs::write(&file, r#"{"starting_points": [100], "boxes": {"100": [[]]}}"#).unwrap();
+    assert!(matches!(PatternGraph::from_file(&file), Err(PatternGraphError::EmptyPattern(100))));
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn test_scan_limits_bound_cyclic_graph() {
+    use signals_rthmn::patterns::PatternGraph;
+    use signals_rthmn::scanner::{MarketScanner, ScanLimits};
+    use std::collections::HashMap;
+
+    // 100 -> 75 -> 100 -> ... never closes on its own key, so traversal only ends at the limit
+    let graph = PatternGraph {
+        starting_points: vec![100],
+        boxes: HashMap::from([(100, vec![vec![-86, 75]]), (75, vec![vec![-65, 100]])]),
+    };
+
+    let mut scanner = MarketScanner::with_limits(ScanLimits { max_depth: 10, max_paths: 100, ..ScanLimits::default() });
+    scanner.initialize_with(graph.clone());
+    assert_eq!(scanner.path_count(), 1);
+    assert_eq!(scanner.truncated_count(), 1);
+    assert!(scanner.get_paths()[0].path.len() >= 10);
+    assert!(!scanner.path_limit_hit());
+
+    // Exactly max_paths paths from a complete traversal isn't hitting the limit
+    let mut scanner = MarketScanner::with_limits(ScanLimits { max_depth: 10, max_paths: 1, ..ScanLimits::default() });
+    scanner.initialize_with(graph.clone());
+    assert_eq!(scanner.path_count(), 1);
+    assert!(!scanner.path_limit_hit());
+
+    let mut scanner = MarketScanner::with_limits(ScanLimits { max_depth: 10, max_paths: 0, ..ScanLimits::default() });
+    scanner.initialize_with(graph);
+    assert_eq!(scanner.path_count(), 0);
+    assert!(scanner.path_limit_hit());
+}
+
+#[test]
+fn test_detect_patterns_explained_reports_ambiguous_values() {
+    use signals_rthmn::patterns::PatternGraph;
+    use signals_rthmn::scanner::MarketScanner;
+    use std::collections::HashMap;
+
+    let mut scanner = MarketScanner::default();
+    scanner.initialize_with(PatternGraph {
+        starting_points: vec![100],
+        boxes: HashMap::from([(100, vec![vec![-86, 75]])]),
+    });
+
+    let live = vec![
+        Box { high: 1.2, low: 1.19925, value: 0.00075 },
+        Box { high: 1.2, low: 1.199, value: 0.001 },
+        Box { high: 1.2, low: 1.19914, value: -0.00086 },
+        Box { high: 1.3, low: 1.29925, value: 0.00075 },
+    ];
+    let explained = scanner.detect_patterns_explained("EURUSD", &live);
+    assert_eq!(explained.len(), 1);
+
+    let sources: Vec<(i32, Option<usize>, bool)> = explained[0]
+        .sources
+        .iter()
+        .map(|s| (s.value, s.box_index, s.is_ambiguous()))
+        .collect();
+    assert_eq!(sources, vec![(100, Some(1), false), (-86, Some(2), false), (75, Some(0), true)]);
+}
+
+#[test]
+fn test_duplicate_integer_values_bind_to_distinct_boxes() {
+    use signals_rthmn::patterns::PatternGraph;
+    use signals_rthmn::scanner::MarketScanner;
+    use std::collections::HashMap;
+
+    let mut scanner = MarketScanner::default();
+    scanner.initialize_with(PatternGraph {
+        starting_points: vec![100],
+        boxes: HashMap::from([(100, vec![vec![-86, 100]])]),
+    });
+
+    let live = vec![
+        Box { high: 1.2, low: 1.199, value: 0.001 },
+        Box { high: 1.3, low: 1.299, value: 0.001 },
+        Box { high: 1.2, low: 1.19914, value: -0.00086 },
+    ];
+    let matches = scanner.detect_patterns("EURUSD", &live);
+    let long = matches.iter().find(|m| m.traversal_path.path == vec![100, -86, 100]).unwrap();
+    let highs: Vec<f64> = long.box_details.iter().map(|d| d.high).collect();
+    assert_eq!(highs, vec![1.2, 1.2, 1.3]);
+}
+
+#[test]
+fn test_levels_above_max_level_are_capped() {
+    use signals_rthmn::patterns::PatternGraph;
+    use signals_rthmn::scanner::{MarketScanner, ScanLimits};
+    use std::collections::HashMap;
+
+    let graph = PatternGraph {
+        starting_points: vec![100],
+        boxes: HashMap::from([(100, vec![vec![-86, 75]]), (75, vec![vec![-65, 56]]), (56, vec![vec![-49, 42]])]),
+    };
+    let live: Vec<Box> = [100, -86, 75, -65, 56, -49, 42]
+        .iter()
+        .map(|&v: &i32| Box { high: 1.0 + v.abs() as f64 * 0.00001, low: 1.0, value: v as f64 * 0.00001 })
+        .collect();
+
+    let level = |max_level| {
+        let mut scanner = MarketScanner::with_limits(ScanLimits { max_level, ..ScanLimits::default() });
+        scanner.initialize_with(graph.clone());
+        scanner.detect_patterns("EURUSD", &live)[0].level
+    };
+    assert_eq!(level(6), 3);
+    assert_eq!(level(2), 2);
+}
+
+#[test]
+fn test_value_tolerance_matches_across_rounding_boundary() {
+    use signals_rthmn::patterns::PatternGraph;
+    use signals_rthmn::scanner::MarketScanner;
+    use std::collections::HashMap;
+
+    let graph = || PatternGraph {
+        starting_points: vec![100],
+        boxes: HashMap::from([(100, vec![vec![-86]])]),
+    };
+    let live = |ticks: f64| {
+        vec![
+            Box { high: 1.2, low: 1.199, value: 0.001 },
+            Box { high: 1.2, low: 1.2 - ticks * 0.00001, value: -ticks * 0.00001 },
+        ]
+    };
+    let long_paths = |scanner: &MarketScanner, ticks: f64| -> Vec<Vec<i32>> {
+        scanner
+            .detect_patterns("EURUSD", &live(ticks))
+            .into_iter()
+            .map(|m| m.traversal_path.path)
+            .filter(|p| p[0] > 0)
+            .collect()
+    };
+
+    let mut exact = MarketScanner::default();
+    exact.initialize_with(graph());
+    assert_eq!(long_paths(&exact, 86.49), vec![vec![100, -86]]);
+    assert!(long_paths(&exact, 86.51).is_empty());
+
+    let mut tolerant = MarketScanner::default().with_value_tolerance(0.6);
+    tolerant.initialize_with(graph());
+    assert_eq!(long_paths(&tolerant, 86.49), vec![vec![100, -86]]);
+    assert_eq!(long_paths(&tolerant, 86.51), vec![vec![100, -86]]);
+
+    let matched = tolerant.detect_patterns("EURUSD", &live(86.51));
+    assert_eq!(matched[0].box_details[1].integer_value, -86);
+}
+
+#[test]
+fn test_graph_stats() {
+    use signals_rthmn::patterns::PatternGraph;
+    use signals_rthmn::scanner::MarketScanner;
+    use std::collections::{BTreeMap, HashMap};
+
+    let mut scanner = MarketScanner::default();
+    scanner.initialize_with(PatternGraph {
+        starting_points: vec![100],
+        boxes: HashMap::from([(100, vec![vec![-86, 75], vec![-86]]), (75, vec![vec![-65]])]),
+    });
+    let stats = scanner.graph_stats();
+    assert_eq!(stats.total_paths, 2);
+    assert_eq!(stats.by_length, BTreeMap::from([(2, 1), (4, 1)]));
+    assert_eq!(stats.by_level, BTreeMap::from([(1, 1), (2, 1)]));
+    assert_eq!(stats.longest_path, vec![100, -86, 75, -65]);
+
+    let builtin = signals_rthmn::scanner::analyze_graph();
+    assert!(builtin.total_paths > 0);
+    assert_eq!(builtin.by_length.values().sum::<usize>(), builtin.total_paths);
+    assert_eq!(builtin.by_level.values().sum::<usize>(), builtin.total_paths);
+    assert_eq!(builtin.by_length.keys().last(), Some(&builtin.longest_path.len()));
+}
+
+#[test]
+fn test_min_boxes_skips_short_updates() {
+    use signals_rthmn::patterns::PatternGraph;
+    use signals_rthmn::scanner::{MarketScanner, ScanLimits};
+    use std::collections::HashMap;
+
+    let graph = || PatternGraph {
+        starting_points: vec![100],
+        boxes: HashMap::from([(100, vec![vec![-86]])]),
+    };
+    let live = vec![
+        Box { high: 1.2, low: 1.199, value: 0.001 },
+        Box { high: 1.2, low: 1.19914, value: -0.00086 },
+    ];
+
+    let mut scanner = MarketScanner::default();
+    scanner.initialize_with(graph());
+    assert_eq!(scanner.limits().min_boxes, 1);
+    assert!(!scanner.detect_patterns("EURUSD", &live).is_empty());
+
+    let mut strict = MarketScanner::with_limits(ScanLimits { min_boxes: 3, ..ScanLimits::default() });
+    strict.initialize_with(graph());
+    assert!(strict.detect_patterns("EURUSD", &live).is_empty());
+}
+
+#[test]
+fn test_path_fingerprints_diff() {
+    use signals_rthmn::patterns::PatternGraph;
+    use signals_rthmn::scanner::{diff_paths, MarketScanner};
+    use std::collections::{BTreeSet, HashMap};
+
+    let fingerprints = |boxes: HashMap<i32, Vec<Vec<i32>>>| {
+        let mut scanner = MarketScanner::default();
+        scanner.initialize_with(PatternGraph { starting_points: vec![100], boxes });
+        scanner.path_fingerprints()
+    };
+    let before = fingerprints(HashMap::from([(100, vec![vec![-86, 75], vec![-86]]), (75, vec![vec![-65]])]));
+    let after = fingerprints(HashMap::from([(100, vec![vec![-86, 75], vec![-86]]), (75, vec![vec![-56]])]));
+    assert_eq!(before, BTreeSet::from(["100_-86".to_string(), "100_-86_75_-65".to_string()]));
+
+    let diff = diff_paths(&before, &after);
+    assert_eq!(diff.added, BTreeSet::from(["100_-86_75_-56".to_string()]));
+    assert_eq!(diff.removed, BTreeSet::from(["100_-86_75_-65".to_string()]));
+    assert!(diff_paths(&before, &before).is_empty());
+}
+
+#[test]
+fn test_inverted_pair_detects_mirrored_patterns() {
+    use signals_rthmn::patterns::PatternGraph;
+    use signals_rthmn::scanner::MarketScanner;
+    use signals_rthmn::types::SignalType;
+    use std::collections::HashMap;
+
+    signals_rthmn::instruments::set_inverted_pairs(["INVTEST"]);
+    assert!(signals_rthmn::instruments::get_instrument_config("INVTEST").invert);
+    assert!(!signals_rthmn::instruments::get_instrument_config("REGTEST").invert);
+
+    let mut scanner = MarketScanner::default();
+    scanner.initialize_with(PatternGraph {
+        starting_points: vec![100],
+        boxes: HashMap::from([(100, vec![vec![-86]])]),
+    });
+    // Stock-style pairs: point 0.01 until a price is seen
+    let live = vec![Box { high: 21.0, low: 20.0, value: 1.0 }, Box { high: 21.0, low: 20.14, value: -0.86 }];
+    let detect = |pair| {
+        let matches = scanner.detect_patterns(pair, &live);
+        assert_eq!(matches.len(), 1);
+        let m = &matches[0];
+        (m.traversal_path.signal_type(), m.traversal_path.path.clone(), m.box_details[0].high)
+    };
+
+    assert_eq!(detect("REGTEST"), (SignalType::LONG, vec![100, -86], 21.0));
+    assert_eq!(detect("INVTEST"), (SignalType::SHORT, vec![-100, 86], 21.0));
+}
+
+#[test]
+fn test_starting_points_without_patterns() {
+    use signals_rthmn::patterns::{self, PatternGraph};
+    use std::collections::HashMap;
+
+    let graph = PatternGraph {
+        starting_points: vec![100, 75, 56],
+        boxes: HashMap::from([(75, vec![vec![-65]])]),
+    };
+    assert_eq!(graph.starts_without_patterns(), vec![100, 56]);
+    assert_eq!(patterns::validate().err().unwrap_or_default(), PatternGraph::builtin().starts_without_patterns());
+}
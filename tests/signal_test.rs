@@ -0,0 +1,328 @@
+use signals_rthmn::signal::{best_opportunity, position_size, score_pattern, MinRr, SignalGenerator, TradeOpportunity};
+use signals_rthmn::types::{BoxDetail, PatternMatch, StopLoss, Target, TraversalPath};
+
+fn detail(integer_value: i32, high: f64, low: f64) -> BoxDetail {
+    BoxDetail { integer_value, high, low, value: integer_value as f64 }
+}
+
+fn l1_long(pair: &str, box_details: Vec<BoxDetail>) -> PatternMatch {
+    let path: Vec<i32> = box_details.iter().map(|b| b.integer_value).collect();
+    PatternMatch {
+        pair: pair.to_string(),
+        level: 1,
+        traversal_path: TraversalPath { path: path.clone() },
+        full_pattern: path,
+        box_details,
+    }
+}
+
+fn prices(pair: &str, pattern: PatternMatch) -> (f64, f64, Vec<f64>) {
+    let signals = SignalGenerator::default().generate_signals(pair, &[pattern], &[], 0.0);
+    let signal = &signals[0];
+    let targets = signal.targets.iter().map(|t| t.price).collect();
+    (signal.entry.unwrap(), signal.stop_losses[0].price, targets)
+}
+
+#[test]
+fn test_eurusd_prices_rounded_to_five_digits() {
+    let pattern = l1_long("EURUSD", vec![
+        detail(500, 1.1050000001, 1.1000000003),
+        detail(-300, 1.1040000009, 1.1010000007),
+        detail(200, 1.1030000004, 1.1010000002),
+    ]);
+
+    let (entry, stop, targets) = prices("EURUSD", pattern);
+    assert_eq!(entry, 1.103);
+    assert_eq!(stop, 1.1);
+    assert_eq!(targets, vec![1.105, 1.11]);
+}
+
+#[test]
+fn test_usdjpy_prices_rounded_to_three_digits() {
+    let pattern = l1_long("USDJPY", vec![
+        detail(500, 150.5004321, 150.0001234),
+        detail(200, 150.3009876, 150.1000001),
+    ]);
+
+    let (entry, stop, targets) = prices("USDJPY", pattern);
+    assert_eq!(entry, 150.301);
+    assert_eq!(stop, 150.0);
+    assert_eq!(targets, vec![150.5, 151.001]);
+}
+
+#[test]
+fn test_btcusd_prices_rounded_to_whole_units() {
+    let pattern = l1_long("BTCUSD", vec![
+        detail(2000, 98000.4, 78000.3),
+        detail(1000, 90000.6, 80000.2),
+    ]);
+
+    let (entry, stop, targets) = prices("BTCUSD", pattern);
+    assert_eq!(entry, 90001.0);
+    assert_eq!(stop, 78000.0);
+    assert_eq!(targets, vec![98000.0, 118000.0]);
+}
+
+#[test]
+fn test_score_pattern_prefers_deeper_longer_higher_rr() {
+    let shallow = l1_long("EURUSD", vec![detail(500, 1.105, 1.1), detail(-300, 1.104, 1.101)]);
+    let mut deep = l1_long("EURUSD", vec![
+        detail(500, 1.105, 1.1),
+        detail(-300, 1.104, 1.101),
+        detail(200, 1.103, 1.101),
+        detail(-100, 1.103, 1.102),
+    ]);
+    deep.level = 4;
+
+    assert!(score_pattern(&deep, Some(2.0)) > score_pattern(&shallow, Some(2.0)));
+    assert!(score_pattern(&shallow, Some(3.0)) > score_pattern(&shallow, Some(1.0)));
+    assert_eq!(score_pattern(&deep, None), 0.0);
+    assert!(score_pattern(&deep, Some(1e9)) <= 1.0);
+}
+
+#[test]
+fn test_mid_entry_rule_used_when_enabled_and_better() {
+    let pattern = || l1_long("EURUSD", vec![
+        detail(500, 1.105, 1.1),
+        detail(-300, 1.104, 1.101),
+        detail(200, 1.103, 1.101),
+    ]);
+
+    let default = SignalGenerator::default().generate_signals("EURUSD", &[pattern()], &[], 0.0);
+    assert_eq!(default[0].entry, Some(1.103));
+
+    // MID of box 1 (1.102) is closer to the 1.1 stop, so its final RR is higher
+    let mid = SignalGenerator::with_mid_entry_rules(true).generate_signals("EURUSD", &[pattern()], &[], 0.0);
+    assert_eq!(mid[0].entry, Some(1.102));
+    assert!(mid[0].risk_reward.last() > default[0].risk_reward.last());
+}
+
+fn opportunity(rule_id: &'static str, valid: bool, rr: f64) -> TradeOpportunity {
+    TradeOpportunity {
+        rule_id,
+        entry: valid.then_some(1.0),
+        stop_losses: vec![StopLoss { price: 0.9, timestamp: None }],
+        targets: vec![Target { price: 1.0 + rr * 0.1, timestamp: None }],
+        risk_reward: vec![rr],
+        suggested_size: None,
+    }
+}
+
+#[test]
+fn test_best_opportunity_by_final_rr() {
+    let opportunities = [
+        opportunity("A", true, 1.0),
+        opportunity("B", false, 9.0),
+        opportunity("C", true, 3.0),
+    ];
+    assert_eq!(best_opportunity(&opportunities).map(|o| o.rule_id), Some("C"));
+}
+
+#[test]
+fn test_best_opportunity_tie_prefers_earlier() {
+    let opportunities = [opportunity("A", true, 2.0), opportunity("B", true, 2.0)];
+    assert_eq!(best_opportunity(&opportunities).map(|o| o.rule_id), Some("A"));
+}
+
+#[test]
+fn test_best_opportunity_all_invalid() {
+    let opportunities = [opportunity("A", false, 2.0), opportunity("B", false, 3.0)];
+    assert!(best_opportunity(&opportunities).is_none());
+    assert!(best_opportunity(&[]).is_none());
+}
+
+#[test]
+fn test_final_target_and_rr_scale_with_multiplier() {
+    // Entry 1.1 (box 1 high), stop 1.0 (box 0 low): risk 0.1; box 0 is 0.2 tall
+    let long = l1_long("EURUSD", vec![detail(200, 1.2, 1.0), detail(100, 1.1, 1.05)]);
+    let short = PatternMatch {
+        traversal_path: TraversalPath { path: vec![-200, -100] },
+        full_pattern: vec![-200, -100],
+        box_details: vec![detail(-200, 1.2, 1.0), detail(-100, 1.15, 1.1)],
+        ..long.clone()
+    };
+
+    for multiplier in [1.0, 1.5, 2.0] {
+        let generator = SignalGenerator::default().with_target_multiplier(multiplier);
+        let signals = generator.generate_signals("EURUSD", &[long.clone(), short.clone()], &[], 0.0);
+
+        let long_signal = &signals[0];
+        assert_eq!(long_signal.targets.last().unwrap().price, round5(1.2 + 0.2 * multiplier));
+        assert_eq!(long_signal.risk_reward.last().copied(), Some(1.0 + 2.0 * multiplier));
+
+        let short_signal = &signals[1];
+        assert_eq!(short_signal.targets.last().unwrap().price, round5(1.0 - 0.2 * multiplier));
+        assert_eq!(short_signal.risk_reward.last().copied(), Some(1.0 + 2.0 * multiplier));
+    }
+}
+
+fn round5(price: f64) -> f64 {
+    signals_rthmn::signal::round_to_digits(price, 5)
+}
+
+#[test]
+fn test_suggested_size_from_risk_per_trade() {
+    assert_eq!(position_size(100.0, 1.1, 1.1, 100_000.0), None);
+    assert_eq!(position_size(50.0, 90.0, 80.0, 1.0), Some(5.0));
+
+    let pattern = l1_long("EURUSD", vec![detail(200, 1.2, 1.0), detail(100, 1.1, 1.05)]);
+    let plain = SignalGenerator::default().generate_signals("EURUSD", std::slice::from_ref(&pattern), &[], 0.0);
+    assert_eq!(plain[0].suggested_size, None);
+
+    // 0.1 of risk per unit on a 100,000-unit lot: $100 buys 0.01 lots
+    let sized = SignalGenerator::default()
+        .with_risk_per_trade(Some(100.0))
+        .generate_signals("EURUSD", &[pattern], &[], 0.0);
+    let size = sized[0].suggested_size.unwrap();
+    assert!((size - 0.01).abs() < 1e-9);
+}
+
+#[test]
+fn test_min_rr_per_level_overrides() {
+    let vars = [("MIN_RR", "2.0"), ("MIN_RR_L4", "1.2"), ("MIN_RR_L5", "bad"), ("MIN_RR_LX", "1.0"), ("PORT", "3000")];
+    let min_rr = MinRr::from_vars(vars.map(|(k, v)| (k.to_string(), v.to_string())));
+
+    assert_eq!(min_rr.for_level(1), 2.0);
+    assert_eq!(min_rr.for_level(4), 1.2);
+    assert_eq!(min_rr.for_level(5), 2.0);
+    assert_eq!(min_rr.by_level.len(), 1);
+
+    // An L4 signal at 1.5 passes its level's minimum but would fail the global one
+    let rr = 1.5;
+    assert!(rr >= min_rr.for_level(4));
+    assert!(rr < min_rr.default);
+
+    assert_eq!(MinRr::from_vars(std::iter::empty()).for_level(3), 0.0);
+}
+
+#[test]
+fn test_min_box_size_rejects_degenerate_first_box() {
+    let degenerate = || l1_long("EURUSD", vec![
+        detail(500, 1.1, 1.1),
+        detail(-300, 1.104, 1.101),
+        detail(200, 1.103, 1.101),
+    ]);
+    let healthy = l1_long("EURUSD", vec![
+        detail(500, 1.105, 1.1),
+        detail(-300, 1.104, 1.101),
+        detail(200, 1.103, 1.101),
+    ]);
+    let generator = SignalGenerator::default().with_min_box_size(10.0);
+
+    // Without a minimum the zero-size box still yields a (meaningless) trade
+    assert!(SignalGenerator::default().generate_signals("EURUSD", &[degenerate()], &[], 0.0)[0].entry.is_some());
+
+    let signal = &generator.generate_signals("EURUSD", &[degenerate()], &[], 0.0)[0];
+    assert!(signal.entry.is_none() && signal.stop_losses.is_empty() && signal.targets.is_empty());
+    assert!(generator.generate_signals("EURUSD", &[healthy], &[], 0.0)[0].entry.is_some());
+}
+
+#[test]
+fn test_direction_filter_drops_other_direction() {
+    use signals_rthmn::signal::DirectionFilter;
+
+    let short = || {
+        let mut pattern = l1_long("EURUSD", vec![detail(-100, 1.1000, 1.0900), detail(86, 1.0990, 1.0910)]);
+        pattern.traversal_path.path = vec![-100, 86];
+        pattern
+    };
+    let long = || l1_long("EURUSD", vec![detail(100, 1.1000, 1.0900), detail(-86, 1.0990, 1.0910)]);
+    assert_eq!("LONG".parse(), Ok(DirectionFilter::Long));
+    assert!("up".parse::<DirectionFilter>().is_err());
+
+    assert_eq!(SignalGenerator::default().generate_signals("EURUSD", &[short()], &[], 0.0).len(), 1);
+    let long_only = SignalGenerator::default().with_direction_filter(DirectionFilter::Long);
+    assert!(long_only.generate_signals("EURUSD", &[short()], &[], 0.0).is_empty());
+    let signals = long_only.generate_signals("EURUSD", &[long(), short()], &[], 0.0);
+    assert_eq!((signals.len(), signals[0].signal_type.as_str()), (1, "LONG"));
+    let short_only = SignalGenerator::default().with_direction_filter(DirectionFilter::Short);
+    assert!(short_only.generate_signals("EURUSD", &[long()], &[], 0.0).is_empty());
+}
+
+#[test]
+fn test_targets_anchored_at_box_one() {
+    let boxes = vec![
+        detail(500, 1.1050, 1.0950),
+        detail(300, 1.1030, 1.0990),
+        detail(200, 1.1020, 1.1000),
+    ];
+    let l2 = PatternMatch { level: 2, ..l1_long("EURUSD", boxes) };
+    let targets = |generator: SignalGenerator| {
+        let signal = &generator.generate_signals("EURUSD", std::slice::from_ref(&l2), &[], 0.0)[0];
+        (signal.targets.iter().map(|t| t.price).collect::<Vec<_>>(), signal.risk_reward.clone())
+    };
+
+    // Box 0 anchor: box 0 and box 1 highs, then box 0's high plus box 0's size
+    assert_eq!(targets(SignalGenerator::default()), (vec![1.103, 1.105, 1.115], vec![0.0, 1.0, 4.0]));
+    // Box 1 anchor: box 0 is skipped and the final target adds box 1's size to its high
+    assert_eq!(targets(SignalGenerator::default().with_target_box(1)), (vec![1.103, 1.107], vec![0.0, 2.0]));
+    assert_eq!(
+        targets(SignalGenerator::default().with_target_box(1).with_target_multiplier(2.0)),
+        (vec![1.103, 1.111], vec![0.0, 3.0])
+    );
+    // Past the last primary box there is nothing to anchor to
+    assert!(targets(SignalGenerator::default().with_target_box(3)).0.is_empty());
+}
+
+#[test]
+fn test_too_few_primary_boxes_for_rule() {
+    use signals_rthmn::signal::{PricePoint, TradeRule};
+
+    let l3 = TradeRule {
+        id: "L3_RULE_1",
+        level: 3,
+        entry_box: 3,
+        entry_point: PricePoint::HIGH,
+        stop_boxes: vec![2],
+        stop_point: PricePoint::LOW,
+        target_boxes: vec![0, 1, 2],
+        target_point: PricePoint::HIGH,
+    };
+    assert_eq!(l3.boxes_needed(), 4);
+
+    // Five boxes, but only two of them point the pattern's way
+    let boxes = vec![
+        detail(500, 1.1050, 1.0950),
+        detail(-400, 1.1040, 1.0960),
+        detail(300, 1.1030, 1.0990),
+        detail(-200, 1.1020, 1.1000),
+        detail(-100, 1.1015, 1.1005),
+    ];
+    let pattern = PatternMatch { level: 3, ..l1_long("EURUSD", boxes) };
+    let signal = &SignalGenerator::default().generate_signals("EURUSD", &[pattern], &[], 0.0)[0];
+    // No box 2 or 3 for the stop and entry; the targets from boxes 0 and 1 alone don't
+    // make a trade
+    assert!(signal.entry.is_none() && signal.stop_losses.is_empty());
+    assert_eq!(signal.targets.len(), 3);
+}
+
+#[test]
+fn test_volatility_target_from_entry() {
+    use signals_rthmn::signal::TargetModel;
+
+    let boxes = vec![
+        detail(500, 1.1050, 1.0950),
+        detail(300, 1.1030, 1.0990),
+        detail(200, 1.1020, 1.1000),
+    ];
+    let l2 = PatternMatch { level: 2, ..l1_long("EURUSD", boxes) };
+    let targets = |generator: SignalGenerator, atr: Option<f64>| {
+        let signals = generator.generate_signals_with_atr("EURUSD", std::slice::from_ref(&l2), &[], 0.0, atr);
+        signals[0].targets.iter().map(|t| t.price).collect::<Vec<_>>()
+    };
+    assert_eq!("Volatility".parse(), Ok(TargetModel::Volatility));
+
+    let volatility = || SignalGenerator::default().with_target_model(TargetModel::Volatility);
+    // Entry 1.102 plus one ATR replaces box 0's high plus its size (1.115)
+    assert_eq!(targets(volatility(), Some(0.006)), vec![1.103, 1.105, 1.108]);
+    assert_eq!(targets(volatility().with_atr_multiplier(2.0), Some(0.006)), vec![1.103, 1.105, 1.114]);
+    assert_eq!(targets(volatility().with_target_multiplier(2.0), Some(0.006)), vec![1.103, 1.105, 1.108]);
+    // An ATR inside the boxes: box targets beyond it are dropped, so the ATR target stays
+    // final and sets the risk/reward (0.0005 over the 0.003 risk rounds to 0)
+    assert_eq!(targets(volatility(), Some(0.0005)), vec![1.1025]);
+    let signals = volatility().generate_signals_with_atr("EURUSD", std::slice::from_ref(&l2), &[], 0.0, Some(0.0005));
+    assert_eq!(signals[0].risk_reward, vec![0.0]);
+    // No ATR yet, or the box-size model: the ATR plays no part
+    assert_eq!(targets(volatility(), None), vec![1.103, 1.105, 1.115]);
+    assert_eq!(targets(SignalGenerator::default(), Some(0.006)), vec![1.103, 1.105, 1.115]);
+}
@@ -0,0 +1,359 @@
+use axum::extract::{Query, State};
+use axum::http::{HeaderMap, StatusCode};
+use axum::routing::patch;
+use axum::{Json, Router};
+use signals_rthmn::tracker::ActiveSignal;
+use signals_rthmn::types::{SignalMessage, StopLoss, Target};
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use signals_rthmn::supabase::{SupabaseClient, SupabaseError, UpdateSignalStatus};
+use signals_rthmn::types::SettlementReason;
+
+fn long_signal() -> ActiveSignal {
+    let message = SignalMessage {
+        id: None,
+        pair: "EURUSD".to_string(),
+        signal_type: "LONG".to_string(),
+        level: 1,
+        pattern_sequence: vec![100, -86],
+        box_details: vec![],
+        complete_box_snapshot: vec![100, -86],
+        entry: Some(1.1),
+        stop_losses: vec![StopLoss { price: 1.09, timestamp: None }],
+        targets: vec![Target { price: 1.12, timestamp: None }],
+        risk_reward: vec![2.0],
+        confidence: 0.5,
+        suggested_size: None,
+    };
+    ActiveSignal::from_message(&message, 0).unwrap()
+}
+
+fn update() -> UpdateSignalStatus {
+    UpdateSignalStatus {
+        status: "success".to_string(),
+        settlement_reason: SettlementReason::Target,
+        mfe: 0.0,
+        mae: 0.0,
+        settled_price: 1.0,
+        settled_at: "2025-01-01T00:00:00+00:00".to_string(),
+    }
+}
+
+async fn serve(app: Router) -> String {
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let url = format!("http://{}", listener.local_addr().unwrap());
+    tokio::spawn(async move { axum::serve(listener, app).await.unwrap() });
+    url
+}
+
+#[tokio::test]
+async fn test_failures_are_typed() {
+    let app = Router::new().route(
+        "/rest/v1/signals",
+        patch(|| async { (StatusCode::CONFLICT, "duplicate key") }),
+    );
+    let url = serve(app).await;
+
+    let err = SupabaseClient::new(&url, "key").update_signal_status(1, &update()).await.unwrap_err();
+    match &err {
+        SupabaseError::Http { status, body } => {
+            assert_eq!(*status, StatusCode::CONFLICT);
+            assert_eq!(body, "duplicate key");
+        }
+        other => panic!("expected Http, got {:?}", other),
+    }
+    assert!(!err.is_retryable());
+
+    let client = SupabaseClient::new("http://127.0.0.1:9", "key").with_max_attempts(1);
+    let err = client.update_signal_status(1, &update()).await.unwrap_err();
+    assert!(matches!(err, SupabaseError::Transport(_)));
+    assert!(err.is_retryable());
+    assert_eq!(client.last_success_ms(), None);
+}
+
+#[tokio::test]
+async fn test_transient_failures_are_retried() {
+    let calls = Arc::new(AtomicUsize::new(0));
+    let app = Router::new()
+        .route(
+            "/rest/v1/signals",
+            patch(|State(calls): State<Arc<AtomicUsize>>| async move {
+                match calls.fetch_add(1, Ordering::SeqCst) {
+                    0 => StatusCode::BAD_GATEWAY,
+                    _ => StatusCode::NO_CONTENT,
+                }
+            })
+            .post(
+                |Query(query): Query<HashMap<String, String>>, headers: HeaderMap, Json(body): Json<serde_json::Value>| async move {
+                    assert_eq!(query.get("on_conflict").map(String::as_str), Some("signal_id"));
+                    assert!(headers["prefer"].to_str().unwrap().contains("resolution=merge-duplicates"));
+                    assert_eq!(body["signal_id"], "EURUSD:LONG:L1:0:100,-86");
+                    Json(serde_json::json!([{"id": 7}]))
+                },
+            ),
+        )
+        .with_state(calls.clone());
+    let client = SupabaseClient::new(&serve(app).await, "key");
+
+    client.update_signal_status(1, &update()).await.unwrap();
+    assert_eq!(calls.load(Ordering::SeqCst), 2);
+
+    let signal = long_signal();
+    assert_eq!(client.insert_active_signal(&signal).await.unwrap(), 7);
+}
+
+#[tokio::test]
+async fn test_reinsert_returns_existing_row() {
+    // Emulates PostgREST merge-duplicates on a unique signal_id: one row per key
+    let rows: Arc<std::sync::Mutex<HashMap<String, i64>>> = Default::default();
+    let app = Router::new()
+        .route(
+            "/rest/v1/signals",
+            axum::routing::post(
+                |State(rows): State<Arc<std::sync::Mutex<HashMap<String, i64>>>>, Json(body): Json<serde_json::Value>| async move {
+                    let mut rows = rows.lock().unwrap();
+                    let next = rows.len() as i64 + 1;
+                    let key = body["signal_id"].as_str().unwrap().to_string();
+                    let id = *rows.entry(key).or_insert(next);
+                    (StatusCode::CREATED, Json(serde_json::json!([{"id": id}])))
+                },
+            ),
+        )
+        .with_state(rows.clone());
+    let client = SupabaseClient::new(&serve(app).await, "key");
+
+    let first = client.insert_active_signal(&long_signal()).await.unwrap();
+    let second = client.insert_active_signal(&long_signal()).await.unwrap();
+    assert_eq!(first, second);
+    assert_eq!(rows.lock().unwrap().len(), 1);
+
+    // The tracker doesn't track a second copy of a row it already has
+    let tracker = signals_rthmn::tracker::SignalTracker::new(Some(client), None);
+    assert_eq!(tracker.add_signal(long_signal()).await, Some(first));
+    assert_eq!(tracker.add_signal(long_signal()).await, None);
+    assert_eq!(tracker.get_active_count().await, 1);
+}
+
+#[tokio::test]
+async fn test_hung_request_times_out() {
+    let app = Router::new().route(
+        "/rest/v1/signals",
+        patch(|| async {
+            tokio::time::sleep(std::time::Duration::from_secs(30)).await;
+            StatusCode::NO_CONTENT
+        }),
+    );
+    let client = SupabaseClient::new(&serve(app).await, "key")
+        .with_client(signals_rthmn::http::client(std::time::Duration::from_millis(200)))
+        .with_max_attempts(1);
+
+    let started = std::time::Instant::now();
+    let err = client.update_signal_status(1, &update()).await.unwrap_err();
+    assert!(matches!(err, SupabaseError::Timeout(_)), "expected Timeout, got {:?}", err);
+    assert!(err.to_string().starts_with("timed out"));
+    assert!(started.elapsed() < std::time::Duration::from_secs(5));
+}
+
+#[test]
+fn test_signal_key_modes() {
+    use signals_rthmn::supabase::{signal_key, SignalIdMode};
+
+    let at = |created_at| ActiveSignal { created_at, ..long_signal() };
+    let bucketed = SignalIdMode::Bucketed { bucket_secs: 60 };
+
+    assert_eq!(signal_key(&at(120_500), SignalIdMode::Timestamp), "EURUSD:LONG:L1:120500:100,-86");
+    assert_ne!(signal_key(&at(120_500), SignalIdMode::Timestamp), signal_key(&at(120_900), SignalIdMode::Timestamp));
+
+    assert_eq!(signal_key(&at(120_500), bucketed), "EURUSD:LONG:L1:120000:100,-86");
+    assert_eq!(signal_key(&at(120_500), bucketed), signal_key(&at(179_999), bucketed));
+    assert_ne!(signal_key(&at(179_999), bucketed), signal_key(&at(180_000), bucketed));
+}
+
+#[tokio::test]
+async fn test_fetch_signal_and_tracker_lookup() {
+    let app = Router::new().route(
+        "/rest/v1/signals",
+        axum::routing::get(|Query(q): Query<HashMap<String, String>>| async move {
+            let rows = if q.get("id").map(String::as_str) == Some("eq.7") {
+                serde_json::json!([{"id": 7, "status": "failed", "settlement_reason": "stop_loss"}])
+            } else {
+                serde_json::json!([])
+            };
+            Json(rows)
+        }),
+    );
+    let client = SupabaseClient::new(&serve(app).await, "key");
+    let row = client.fetch_signal(7).await.unwrap().unwrap();
+    assert_eq!(row["settlement_reason"], "stop_loss");
+    assert!(client.fetch_signal(8).await.unwrap().is_none());
+
+    let tracker = signals_rthmn::tracker::SignalTracker::new(None, None);
+    let id = tracker.add_signal(long_signal()).await.unwrap();
+    assert_eq!(tracker.find_by_id(id).await.map(|s| s.pair), Some("EURUSD".to_string()));
+    assert!(tracker.find_by_id(id + 1).await.is_none());
+}
+
+#[tokio::test]
+async fn test_hydration_expires_old_rows() {
+    let patched: Arc<std::sync::Mutex<Vec<String>>> = Default::default();
+    let recent = chrono::Utc::now().to_rfc3339();
+    let app = Router::new()
+        .route(
+            "/rest/v1/signals",
+            axum::routing::get(move || async move {
+                let row = |id: i64, created_at: &str| {
+                    serde_json::json!({"id": id, "pair": "EURUSD", "signal_type": "LONG", "level": 1, "entry": 1.1, "created_at": created_at})
+                };
+                Json(serde_json::json!([row(1, "2020-01-01T00:00:00Z"), row(2, &recent)]))
+            })
+            .patch(
+                |State(patched): State<Arc<std::sync::Mutex<Vec<String>>>>,
+                 Query(query): Query<HashMap<String, String>>,
+                 Json(body): Json<serde_json::Value>| async move {
+                    patched.lock().unwrap().push(format!("{} {}", query["id"], body["status"].as_str().unwrap()));
+                    StatusCode::NO_CONTENT
+                },
+            ),
+        )
+        .with_state(patched.clone());
+    let client = SupabaseClient::new(&serve(app).await, "key");
+
+    let tracker = signals_rthmn::tracker::SignalTracker::new(Some(client.clone()), None);
+    assert_eq!(tracker.hydrate_from_supabase().await, 1);
+    assert!(tracker.find_by_id(1).await.is_none());
+    assert!(tracker.find_by_id(2).await.is_some());
+    assert_eq!(*patched.lock().unwrap(), vec!["eq.1 expired".to_string()]);
+
+    let unbounded = signals_rthmn::tracker::SignalTracker::new(Some(client), None).with_max_hydration_age(None);
+    assert_eq!(unbounded.hydrate_from_supabase().await, 2);
+}
+
+/// Supabase whose first targets/stops update blocks until `release` is notified, holding a
+/// price check between collecting its settlements and removing them. `entered` fires once
+/// the check is waiting there.
+struct HeldSupabase {
+    client: SupabaseClient,
+    entered: Arc<tokio::sync::Notify>,
+    release: Arc<tokio::sync::Notify>,
+}
+
+async fn held_supabase() -> HeldSupabase {
+    #[derive(Clone, Default)]
+    struct Gate {
+        entered: Arc<tokio::sync::Notify>,
+        release: Arc<tokio::sync::Notify>,
+        held: Arc<std::sync::atomic::AtomicBool>,
+        next_id: Arc<AtomicUsize>,
+    }
+    let gate = Gate::default();
+    let app = Router::new()
+        .route(
+            "/rest/v1/signals",
+            axum::routing::post(|State(gate): State<Gate>| async move {
+                let id = gate.next_id.fetch_add(1, Ordering::SeqCst) + 1;
+                (StatusCode::CREATED, Json(serde_json::json!([{"id": id}])))
+            })
+            .patch(|State(gate): State<Gate>, Json(body): Json<serde_json::Value>| async move {
+                // Settlement writes go straight through
+                if body.get("targets").is_some() && !gate.held.swap(true, Ordering::SeqCst) {
+                    gate.entered.notify_one();
+                    gate.release.notified().await;
+                }
+                StatusCode::NO_CONTENT
+            }),
+        )
+        .with_state(gate.clone());
+    HeldSupabase {
+        client: SupabaseClient::new(&serve(app).await, "key"),
+        entered: gate.entered,
+        release: gate.release,
+    }
+}
+
+#[tokio::test]
+async fn test_close_during_settlement_settles_the_right_signal() {
+    let supabase = held_supabase().await;
+    let tracker = Arc::new(signals_rthmn::tracker::SignalTracker::new(Some(supabase.client), None));
+
+    let far_target = ActiveSignal { targets: vec![Target { price: 1.2, timestamp: None }], ..long_signal() };
+    let closed = tracker.add_signal(far_target).await.unwrap();
+    let hit = tracker.add_signal(long_signal()).await.unwrap();
+
+    let check = tokio::spawn({
+        let tracker = Arc::clone(&tracker);
+        async move { tracker.check_price("EURUSD", 1.125).await }
+    });
+    supabase.entered.notified().await;
+    // Shifts the hit signal from index 1 to 0 while the check is waiting on Supabase
+    assert_eq!(tracker.close_by_id(closed).await.unwrap().signal.id, closed);
+    supabase.release.notify_one();
+
+    let settlements = check.await.unwrap();
+    assert_eq!(settlements.len(), 1);
+    assert_eq!((settlements[0].signal.id, settlements[0].status), (hit, "success"));
+    assert_eq!(tracker.get_active_count().await, 0);
+}
+
+#[tokio::test]
+async fn test_expiry_during_settlement_settles_the_right_signal() {
+    let supabase = held_supabase().await;
+    let tracker = Arc::new(signals_rthmn::tracker::SignalTracker::new(Some(supabase.client), None));
+
+    let expiring = ActiveSignal {
+        targets: vec![Target { price: 1.2, timestamp: None }],
+        expires_at: Some(10),
+        ..long_signal()
+    };
+    let expired = tracker.add_signal(expiring).await.unwrap();
+    let hit = tracker.add_signal(long_signal()).await.unwrap();
+
+    let check = tokio::spawn({
+        let tracker = Arc::clone(&tracker);
+        async move { tracker.check_price("EURUSD", 1.125).await }
+    });
+    supabase.entered.notified().await;
+    let expirations = tracker.expire_stale(20).await;
+    assert_eq!(expirations.iter().map(|s| s.signal.id).collect::<Vec<_>>(), [expired]);
+    supabase.release.notify_one();
+
+    let settlements = check.await.unwrap();
+    assert_eq!(settlements.len(), 1);
+    assert_eq!((settlements[0].signal.id, settlements[0].status), (hit, "success"));
+    assert_eq!(tracker.get_active_count().await, 0);
+}
+
+#[tokio::test]
+async fn test_trailing_stop_survives_hydration() {
+    let patched: Arc<std::sync::Mutex<Vec<serde_json::Value>>> = Default::default();
+    let app = Router::new()
+        .route(
+            "/rest/v1/signals",
+            axum::routing::get(|| async {
+                Json(serde_json::json!([{
+                    "id": 1, "pair": "EURUSD", "signal_type": "LONG", "level": 3, "entry": 1.1,
+                    "stop_losses": [{"price": 1.09, "timestamp": null}],
+                    "targets": [{"price": 1.2, "timestamp": null}],
+                    "created_at": chrono::Utc::now().to_rfc3339(), "water_mark": 1.12
+                }]))
+            })
+            .patch(|State(patched): State<Arc<std::sync::Mutex<Vec<serde_json::Value>>>>, Json(body): Json<serde_json::Value>| async move {
+                patched.lock().unwrap().push(body);
+                StatusCode::NO_CONTENT
+            }),
+        )
+        .with_state(patched.clone());
+    let client = SupabaseClient::new(&serve(app).await, "key");
+
+    let tracker = signals_rthmn::tracker::SignalTracker::new(Some(client), None).with_trailing_stop(3, 0.5);
+    assert_eq!(tracker.hydrate_from_supabase().await, 1);
+    let stop = tracker.find_by_id(1).await.unwrap().trailing_stop().unwrap();
+    assert!((stop - 1.115).abs() < 1e-9);
+
+    // A new high is persisted, and the resumed stop settles the retrace
+    assert!(tracker.check_price("EURUSD", 1.13).await.is_empty());
+    assert_eq!(patched.lock().unwrap().last().unwrap()["water_mark"], 1.13);
+    let settlements = tracker.check_price("EURUSD", 1.124).await;
+    assert_eq!(settlements[0].status, "trailed");
+    assert!((settlements[0].settled_price - 1.125).abs() < 1e-9);
+}
@@ -0,0 +1,252 @@
+use signals_rthmn::tracker::{ActiveSignal, SignalTracker};
+use signals_rthmn::types::{SettlementReason, SignalMessage, StopLoss, Target};
+
+fn long_signal() -> ActiveSignal {
+    let message = SignalMessage {
+        id: None,
+        pair: "EURUSD".to_string(),
+        signal_type: "LONG".to_string(),
+        level: 1,
+        pattern_sequence: vec![100, -86],
+        box_details: vec![],
+        complete_box_snapshot: vec![100, -86],
+        entry: Some(1.1),
+        stop_losses: vec![StopLoss { price: 1.09, timestamp: None }],
+        targets: vec![Target { price: 1.12, timestamp: None }],
+        risk_reward: vec![2.0],
+        confidence: 0.5,
+        suggested_size: None,
+    };
+    ActiveSignal::from_message(&message, 0).unwrap()
+}
+
+#[tokio::test]
+async fn test_settlement_reasons() {
+    let tracker = SignalTracker::new(None, None);
+    tracker.add_signal(long_signal()).await.unwrap();
+    tracker.add_signal(long_signal()).await.unwrap();
+
+    let settlements = tracker.check_price("EURUSD", 1.125).await;
+    assert_eq!(settlements.len(), 2);
+    assert!(settlements.iter().all(|s| s.status == "success" && s.reason == SettlementReason::Target));
+
+    tracker.add_signal(long_signal()).await.unwrap();
+    let settlements = tracker.check_price("EURUSD", 1.085).await;
+    assert_eq!(settlements.len(), 1);
+    assert_eq!((settlements[0].status, settlements[0].reason), ("failed", SettlementReason::StopLoss));
+
+    let mut expiring = long_signal();
+    expiring.expires_at = Some(10);
+    tracker.add_signal(expiring).await.unwrap();
+    let settlements = tracker.expire_stale(20).await;
+    assert_eq!((settlements[0].status, settlements[0].reason), ("expired", SettlementReason::Expired));
+}
+
+#[tokio::test]
+async fn test_excursions_tracked_until_settlement() {
+    let tracker = SignalTracker::new(None, None);
+    tracker.add_signal(long_signal()).await.unwrap();
+
+    assert!(tracker.check_price("EURUSD", 1.105).await.is_empty());
+    assert!(tracker.check_price("EURUSD", 1.095).await.is_empty());
+    let settlements = tracker.check_price("EURUSD", 1.125).await;
+
+    let update = settlements[0].status_update();
+    assert!((update.mfe - 0.025).abs() < 1e-9);
+    assert!((update.mae - 0.005).abs() < 1e-9);
+}
+
+#[tokio::test]
+async fn test_close_by_id() {
+    let tracker = SignalTracker::new(None, None);
+    let id = tracker.add_signal(long_signal()).await.unwrap();
+    tracker.check_price("EURUSD", 1.104).await;
+
+    assert!(tracker.close_by_id(id + 1).await.is_none());
+    let settlement = tracker.close_by_id(id).await.unwrap();
+    assert_eq!((settlement.status, settlement.reason), ("manual", SettlementReason::Manual));
+    assert_eq!(settlement.settled_price, 1.104);
+    assert_eq!(tracker.get_active_count().await, 0);
+    assert!(tracker.close_by_id(id).await.is_none());
+}
+
+#[tokio::test]
+async fn test_settlement_model_on_gap() {
+    use signals_rthmn::tracker::SettlementModel;
+
+    assert_eq!("MARK".parse::<SettlementModel>(), Ok(SettlementModel::Mark));
+    assert!("close".parse::<SettlementModel>().is_err());
+
+    let touch = SignalTracker::new(None, None);
+    touch.add_signal(long_signal()).await.unwrap();
+    assert_eq!(touch.check_price("EURUSD", 1.15).await[0].settled_price, 1.12);
+    touch.add_signal(long_signal()).await.unwrap();
+    assert_eq!(touch.check_price("EURUSD", 1.05).await[0].settled_price, 1.09);
+
+    let mark = SignalTracker::new(None, None).with_settlement_model(SettlementModel::Mark);
+    mark.add_signal(long_signal()).await.unwrap();
+    assert_eq!(mark.check_price("EURUSD", 1.15).await[0].settled_price, 1.15);
+}
+
+#[tokio::test]
+async fn test_wick_only_touches() {
+    let tracker = SignalTracker::new(None, None);
+    tracker.add_signal(long_signal()).await.unwrap();
+
+    // Close stays inside the levels, and so does the range
+    assert!(tracker.check_price_range("EURUSD", 1.095, 1.115, 1.105).await.is_empty());
+
+    // Only the high wick reaches the target
+    let settlements = tracker.check_price_range("EURUSD", 1.10, 1.121, 1.105).await;
+    assert_eq!((settlements[0].status, settlements[0].settled_price), ("success", 1.12));
+    assert!((settlements[0].signal.mfe - 0.021).abs() < 1e-9);
+
+    // Only the low wick reaches the stop
+    tracker.add_signal(long_signal()).await.unwrap();
+    let settlements = tracker.check_price_range("EURUSD", 1.089, 1.11, 1.105).await;
+    assert_eq!((settlements[0].status, settlements[0].settled_price), ("failed", 1.09));
+
+    // Both touched within one update: the stop wins
+    tracker.add_signal(long_signal()).await.unwrap();
+    let settlements = tracker.check_price_range("EURUSD", 1.08, 1.13, 1.105).await;
+    assert_eq!(settlements[0].status, "failed");
+
+    // With several targets, none spanned by the same range counts as hit
+    let targets = vec![Target { price: 1.115, timestamp: None }, Target { price: 1.14, timestamp: None }];
+    tracker.add_signal(ActiveSignal { targets, ..long_signal() }).await.unwrap();
+    let settlements = tracker.check_price_range("EURUSD", 1.08, 1.13, 1.105).await;
+    assert_eq!(settlements[0].status, "failed");
+    assert!(settlements[0].signal.targets.iter().all(|t| t.timestamp.is_none()));
+}
+
+#[tokio::test]
+async fn test_active_breakdown() {
+    let tracker = SignalTracker::new(None, None);
+    tracker.add_signal(long_signal()).await.unwrap();
+    tracker.add_signal(ActiveSignal { level: 3, ..long_signal() }).await.unwrap();
+    tracker
+        .add_signal(ActiveSignal { signal_type: signals_rthmn::types::SignalType::SHORT, ..long_signal() })
+        .await
+        .unwrap();
+
+    let breakdown = tracker.get_active_breakdown().await;
+    assert_eq!(breakdown.by_level, [(1, 2), (3, 1)].into());
+    assert_eq!(breakdown.by_type, [("LONG".to_string(), 2), ("SHORT".to_string(), 1)].into());
+}
+
+#[tokio::test]
+async fn test_to_message_rebuilds_forwarded_signal() {
+    let tracker = SignalTracker::new(None, None);
+    let id = tracker.add_signal(long_signal()).await.unwrap();
+
+    let message = tracker.find_by_id(id).await.unwrap().to_message();
+    assert_eq!(message.id, Some(id));
+    assert_eq!((message.pair.as_str(), message.signal_type.as_str(), message.level), ("EURUSD", "LONG", 1));
+    assert_eq!(message.entry, Some(1.1));
+    assert_eq!(message.targets[0].price, 1.12);
+    assert_eq!(message.confidence, signals_rthmn::signal::score(1, 2, Some(2.0)));
+}
+
+#[tokio::test]
+async fn test_active_messages_snapshot() {
+    use signals_rthmn::types::SignalFeedMessage;
+
+    let tracker = SignalTracker::new(None, None);
+    let older = tracker.add_signal(ActiveSignal { created_at: 5, ..long_signal() }).await.unwrap();
+    let newer = tracker
+        .add_signal(ActiveSignal { pair: "GBPUSD".to_string(), created_at: 9, ..long_signal() })
+        .await
+        .unwrap();
+
+    let signals = tracker.active_messages().await;
+    assert_eq!(signals.iter().map(|s| s.id).collect::<Vec<_>>(), [Some(older), Some(newer)]);
+
+    let snapshot = serde_json::to_value(SignalFeedMessage::Snapshot { signals: &signals }).unwrap();
+    assert_eq!(snapshot["type"], "snapshot");
+    assert_eq!(snapshot["signals"][1]["pair"], "GBPUSD");
+    let live = serde_json::to_value(SignalFeedMessage::Signal { data: &signals[0] }).unwrap();
+    assert_eq!((live["type"].as_str(), live["data"]["id"].as_i64()), (Some("signal"), Some(older)));
+}
+
+#[tokio::test]
+async fn test_take_profit_legs() {
+    use signals_rthmn::tracker::parse_take_profit_fractions;
+
+    assert_eq!(parse_take_profit_fractions("0.5, 0.3"), Ok(vec![0.5, 0.3]));
+    assert!(parse_take_profit_fractions("0.6,0.5").is_err());
+    assert!(parse_take_profit_fractions("0.5,0").is_err());
+    assert!(parse_take_profit_fractions("half").is_err());
+
+    let two_targets = || ActiveSignal {
+        targets: vec![Target { price: 1.11, timestamp: None }, Target { price: 1.12, timestamp: None }],
+        ..long_signal()
+    };
+    let tracker = SignalTracker::new(None, None).with_take_profit_fractions(vec![0.6]);
+    let id = tracker.add_signal(two_targets()).await.unwrap();
+
+    // First target: 60% closes, the signal stays active
+    let settlements = tracker.check_price("EURUSD", 1.111).await;
+    assert_eq!(settlements.len(), 1);
+    assert_eq!((settlements[0].status, settlements[0].reason), ("leg_filled", SettlementReason::Partial));
+    assert!((settlements[0].fraction - 0.6).abs() < 1e-9 && (settlements[0].remaining - 0.4).abs() < 1e-9);
+    assert!(!settlements[0].is_final());
+    assert_eq!(tracker.find_by_id(id).await.unwrap().legs_filled, 1);
+
+    // Final target: the rest closes as a success
+    let settlements = tracker.check_price("EURUSD", 1.12).await;
+    assert_eq!((settlements[0].status, settlements[0].settled_price), ("success", 1.12));
+    assert!((settlements[0].fraction - 0.4).abs() < 1e-9);
+    assert!(settlements[0].is_final());
+    assert_eq!(tracker.get_active_count().await, 0);
+
+    // Stopped out after a leg: the remainder closes as a partial loss
+    tracker.add_signal(two_targets()).await.unwrap();
+    tracker.check_price("EURUSD", 1.111).await;
+    let settlements = tracker.check_price("EURUSD", 1.085).await;
+    assert_eq!((settlements[0].status, settlements[0].reason), ("partial", SettlementReason::StopLoss));
+    assert!((settlements[0].fraction - 0.4).abs() < 1e-9);
+}
+
+#[tokio::test]
+async fn test_trailing_stop_ratchets() {
+    let tracker = SignalTracker::new(None, None).with_trailing_stop(3, 0.5);
+    let fixed = tracker.add_signal(long_signal()).await.unwrap();
+    let trailing = tracker.add_signal(ActiveSignal { level: 3, ..long_signal() }).await.unwrap();
+    let stop = || async { tracker.find_by_id(trailing).await.unwrap().trailing_stop().unwrap() };
+    assert!(tracker.find_by_id(fixed).await.unwrap().trail_distance.is_none());
+
+    // New highs move the stop half the initial risk behind them
+    assert!(tracker.check_price("EURUSD", 1.105).await.is_empty());
+    assert!((stop().await - 1.1).abs() < 1e-9);
+    assert!(tracker.check_price("EURUSD", 1.11).await.is_empty());
+    assert!((stop().await - 1.105).abs() < 1e-9);
+
+    // A pullback that stays above it leaves the stop where it was
+    assert!(tracker.check_price("EURUSD", 1.107).await.is_empty());
+    assert!((stop().await - 1.105).abs() < 1e-9);
+
+    // A retrace into it settles only the trailing signal
+    let settlements = tracker.check_price("EURUSD", 1.104).await;
+    assert_eq!(settlements.len(), 1);
+    assert_eq!((settlements[0].signal.id, settlements[0].status), (trailing, "trailed"));
+    assert_eq!(settlements[0].reason, SettlementReason::Trailed);
+    assert!((settlements[0].settled_price - 1.105).abs() < 1e-9);
+    assert!(tracker.find_by_id(fixed).await.is_some());
+}
+
+#[tokio::test]
+async fn test_max_per_pair() {
+    let tracker = SignalTracker::new(None, Some(1));
+    let first = tracker.add_signal(long_signal()).await.unwrap();
+    assert!(tracker.add_signal(long_signal()).await.is_none());
+    assert_eq!(tracker.get_rejected_count(), 1);
+    assert!(tracker.at_capacity("eurusd").await);
+    assert_eq!(tracker.get_rejected_count(), 2);
+
+    // Other pairs have their own slots, and a settled signal frees one
+    assert!(tracker.add_signal(ActiveSignal { pair: "GBPUSD".to_string(), ..long_signal() }).await.is_some());
+    tracker.close_by_id(first).await.unwrap();
+    assert!(!tracker.at_capacity("EURUSD").await);
+    assert!(tracker.add_signal(long_signal()).await.is_some());
+    assert_eq!(tracker.get_rejected_count(), 2);
+}
@@ -0,0 +1,89 @@
+use signals_rthmn::types::{parse_box_update, Box, BoxUpdateError, InvalidBox, SignalType};
+
+#[test]
+fn test_signal_type_parse() {
+    assert_eq!("LONG".parse::<SignalType>(), Ok(SignalType::LONG));
+    assert_eq!("SHORT".parse::<SignalType>(), Ok(SignalType::SHORT));
+    assert_eq!("long".parse::<SignalType>(), Ok(SignalType::LONG));
+    assert_eq!(SignalType::try_from("Short"), Ok(SignalType::SHORT));
+}
+
+#[test]
+fn test_signal_type_parse_rejects_garbage() {
+    assert!("".parse::<SignalType>().is_err());
+    assert!("LONGG".parse::<SignalType>().is_err());
+    assert!(SignalType::try_from("NEUTRAL").is_err());
+}
+
+#[test]
+fn test_box_validate() {
+    assert_eq!(Box { high: 1.1, low: 1.0, value: 0.1 }.validate(), Ok(()));
+    assert_eq!(Box { high: 1.1, low: 1.0, value: -0.1 }.validate(), Ok(()));
+    assert_eq!(
+        Box { high: 1.0, low: 1.1, value: 0.1 }.validate(),
+        Err(InvalidBox::Inverted { high: 1.0, low: 1.1 })
+    );
+    assert_eq!(Box { high: 1.1, low: 1.0, value: 0.0 }.validate(), Err(InvalidBox::ZeroValue));
+    assert_eq!(Box { high: f64::NAN, low: 1.0, value: 0.1 }.validate(), Err(InvalidBox::NonFinite));
+}
+
+#[test]
+fn test_parse_box_update_errors() {
+    use serde_json::json;
+
+    let ok = parse_box_update(&json!({
+        "boxes": [{"high": 1.105, "low": 1.1, "value": 0.005}],
+        "price": 1.1032,
+        "tickHigh": 1.104,
+        "timestamp": "2024-01-01T00:00:00Z"
+    }))
+    .unwrap();
+    assert_eq!((ok.boxes.len(), ok.price, ok.tick_high, ok.tick_low), (1, 1.1032, Some(1.104), None));
+    assert_eq!(ok.timestamp.unwrap().to_rfc3339(), "2024-01-01T00:00:00+00:00");
+    assert!(parse_box_update(&json!({"boxes": [], "price": 1.0})).unwrap().boxes.is_empty());
+
+    let err = |data| parse_box_update(&data).unwrap_err();
+    assert_eq!(err(json!({"price": 1.0})), BoxUpdateError::MissingBoxes);
+    assert_eq!(err(json!({"boxes": {}, "price": 1.0})), BoxUpdateError::BoxesNotArray);
+    assert!(matches!(err(json!({"boxes": [{"high": 1.1}], "price": 1.0})), BoxUpdateError::MalformedBox { index: 0, .. }));
+    assert_eq!(
+        err(json!({"boxes": [{"high": 1.1, "low": 1.0, "value": 0.1}, {"high": 1.1, "low": 1.0, "value": 0.0}], "price": 1.0})),
+        BoxUpdateError::InvalidBox { index: 1, error: InvalidBox::ZeroValue }
+    );
+    assert_eq!(err(json!({"boxes": [], "price": "1.0"})), BoxUpdateError::MissingPrice);
+    assert_eq!(err(json!({"boxes": [], "price": 0.0})), BoxUpdateError::InvalidPrice(0.0));
+    assert!(matches!(err(json!({"boxes": [], "price": 1.0, "timestamp": "yesterday"})), BoxUpdateError::BadTimestamp(_)));
+    assert!(matches!(err(json!({"boxes": [], "price": 1.0, "timestamp": 1703123456789_i64})), BoxUpdateError::BadTimestamp(_)));
+}
+
+#[test]
+fn test_pattern_match_json_shape() {
+    use signals_rthmn::types::{BoxData, BoxDetail, PatternMatch, TraversalPath};
+
+    let pattern = PatternMatch {
+        pair: "EURUSD".to_string(),
+        level: 1,
+        traversal_path: TraversalPath { path: vec![100, -86] },
+        full_pattern: vec![100, -86, 75],
+        box_details: vec![BoxDetail { integer_value: 100, high: 1.1, low: 1.09, value: 0.01 }],
+    };
+    let json = serde_json::to_value(&pattern).unwrap();
+    assert_eq!(
+        json,
+        serde_json::json!({
+            "pair": "EURUSD",
+            "level": 1,
+            "traversalPath": [100, -86],
+            "fullPattern": [100, -86, 75],
+            "boxDetails": [{"integerValue": 100, "high": 1.1, "low": 1.09, "value": 0.01}]
+        })
+    );
+    let back: PatternMatch = serde_json::from_value(json).unwrap();
+    assert_eq!(back.traversal_path.path, vec![100, -86]);
+
+    let data: BoxData = serde_json::from_value(serde_json::json!({
+        "pair": "EURUSD", "boxes": [], "price": 1.1, "tickHigh": 1.2, "timestamp": "2025-01-01T00:00:00Z"
+    }))
+    .unwrap();
+    assert_eq!((data.tick_high, data.tick_low), (Some(1.2), None));
+}
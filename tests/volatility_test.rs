@@ -0,0 +1,22 @@
+use signals_rthmn::volatility::VolatilityTracker;
+
+#[tokio::test]
+async fn test_atr_over_period() {
+    let tracker = VolatilityTracker::new(3);
+
+    tracker.record("EURUSD", 1.1010, 1.1000, 1.1005).await;
+    // Opened below its range: the true range reaches down to the previous 1.1005
+    tracker.record("EURUSD", 1.1020, 1.1010, 1.1015).await;
+    assert_eq!(tracker.atr("EURUSD").await, None);
+
+    // Gapped up: the true range reaches back to 1.1015
+    tracker.record("eurusd", 1.1050, 1.1045, 1.1050).await;
+    let atr = tracker.atr("EURUSD").await.unwrap();
+    assert!((atr - (0.0010 + 0.0015 + 0.0035) / 3.0).abs() < 1e-9);
+
+    // The oldest range rolls off; a price with no tick range still measures the move
+    tracker.record("EURUSD", 1.1040, 1.1040, 1.1040).await;
+    let atr = tracker.atr("EURUSD").await.unwrap();
+    assert!((atr - (0.0015 + 0.0035 + 0.0010) / 3.0).abs() < 1e-9);
+    assert_eq!(tracker.atr("GBPUSD").await, None);
+}
@@ -0,0 +1,88 @@
+use signals_rthmn::tracker::{ActiveSignal, SignalTracker};
+use signals_rthmn::types::{SignalMessage, StopLoss, Target};
+use signals_rthmn::webhook::{sign, SettlementEvent, SettlementWebhook};
+
+fn short_signal() -> ActiveSignal {
+    let message = SignalMessage {
+        id: None,
+        pair: "GBPCAD".to_string(),
+        signal_type: "SHORT".to_string(),
+        level: 1,
+        pattern_sequence: vec![-100, 86],
+        box_details: vec![],
+        complete_box_snapshot: vec![-100, 86],
+        entry: Some(1.84),
+        stop_losses: vec![StopLoss { price: 1.85, timestamp: None }],
+        targets: vec![Target { price: 1.82, timestamp: None }],
+        risk_reward: vec![2.0],
+        confidence: 0.5,
+        suggested_size: None,
+    };
+    ActiveSignal::from_message(&message, 0).unwrap()
+}
+
+#[test]
+fn test_sign_matches_rfc4231_vector() {
+    assert_eq!(
+        sign("Jefe", b"what do ya want for nothing?"),
+        "5bdcc146bf60754e6a042426089575c75a003f089d2739839dec58b964ec3843"
+    );
+}
+
+#[tokio::test]
+async fn test_unreachable_webhook_does_not_block_settlement() {
+    let tracker = SignalTracker::new(None, None)
+        .with_settlement_webhook(SettlementWebhook::new("http://127.0.0.1:9/hook", Some("secret".into())));
+    let id = tracker.add_signal(short_signal()).await.unwrap();
+
+    let settlements = tracker.check_price("GBPCAD", 1.815).await;
+    assert_eq!(settlements.len(), 1);
+    assert_eq!(settlements[0].signal.id, id);
+
+    let event = SettlementEvent::new(&settlements[0], "2025-01-01T00:00:00+00:00".into());
+    let body = serde_json::to_value(&event).unwrap();
+    assert_eq!(body["signal_id"], id);
+    assert_eq!(body["pair"], "GBPCAD");
+    assert_eq!(body["status"], "success");
+    assert_eq!(body["reason"], "target");
+    assert_eq!(body["settled_at"], "2025-01-01T00:00:00+00:00");
+}
+
+#[tokio::test]
+async fn test_near_stop_warning_sent_once() {
+    use axum::{extract::State, routing::post, Json, Router};
+    use std::sync::{Arc, Mutex};
+
+    let events: Arc<Mutex<Vec<serde_json::Value>>> = Default::default();
+    let app = Router::new()
+        .route(
+            "/hook",
+            post(|State(events): State<Arc<Mutex<Vec<serde_json::Value>>>>, Json(body): Json<serde_json::Value>| async move {
+                events.lock().unwrap().push(body);
+            }),
+        )
+        .with_state(events.clone());
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let url = format!("http://{}/hook", listener.local_addr().unwrap());
+    tokio::spawn(async move { axum::serve(listener, app).await.unwrap() });
+
+    let tracker = SignalTracker::new(None, None)
+        .with_settlement_webhook(SettlementWebhook::new(&url, None))
+        .with_near_stop_warning(0.8);
+    let id = tracker.add_signal(short_signal()).await.unwrap();
+
+    // Entry 1.84, stop 1.85: the zone starts at 1.848
+    assert!(tracker.check_price("GBPCAD", 1.847).await.is_empty());
+    assert!(!tracker.find_by_id(id).await.unwrap().warned);
+    assert!(tracker.check_price("GBPCAD", 1.8485).await.is_empty());
+    assert!(tracker.check_price("GBPCAD", 1.849).await.is_empty());
+    assert!(tracker.find_by_id(id).await.unwrap().warned);
+
+    tokio::time::sleep(std::time::Duration::from_millis(200)).await;
+    let events = events.lock().unwrap();
+    assert_eq!(events.len(), 1);
+    assert_eq!(events[0]["status"], "near_stop");
+    assert_eq!(events[0]["reason"], "near_stop");
+    assert_eq!(events[0]["signal_id"], id);
+    assert_eq!(events[0]["settled_price"], 1.8485);
+}